@@ -1,119 +1,154 @@
 use abstutil::{retain_btreemap, Timer};
-use geom::PolyLine;
-use map_model::raw::{OriginalIntersection, OriginalRoad, RawMap};
+use geom::{Distance, Line, PolyLine, Polygon, Pt2D, EPSILON_DIST};
+use map_model::raw::{
+    OriginalBuilding, OriginalIntersection, OriginalRoad, RawIntersection, RawMap,
+};
 use map_model::IntersectionType;
+use std::collections::BTreeSet;
+use std::fmt;
 
-// TODO This needs to update turn restrictions too
-pub fn clip_map(map: &mut RawMap, timer: &mut Timer) {
+// Buildings clipped by the boundary smaller than this (in m^2) are dropped
+const MIN_CLIPPED_BUILDING_AREA: f64 = 10.0;
+// Same for parking lots. This is a few stalls and an aisle.
+const MIN_CLIPPED_PARKING_LOT_AREA: f64 = 50.0;
+
+// Which roads crossing the boundary to keep
+pub enum ClipMode {
+    // Keep roads with at least one endpoint inside the boundary
+    EitherEndpoint,
+    // Also keep roads that pass all the way through the boundary with both endpoints outside,
+    // like a highway crossing a small area. These get trimmed on both ends.
+    PassThrough,
+}
+
+// What clip_map changed, for figuring out why a clipped map looks the way it does
+#[derive(Debug, Default, PartialEq)]
+pub struct ClipStats {
+    pub roads_removed: usize,
+    pub roads_trimmed: usize,
+    pub buildings_dropped: usize,
+    pub parking_lots_dropped: usize,
+    pub borders_created: usize,
+}
+
+impl fmt::Display for ClipStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "removed {} roads, trimmed {} roads, dropped {} buildings, dropped {} parking lots, \
+             created {} borders",
+            self.roads_removed,
+            self.roads_trimmed,
+            self.buildings_dropped,
+            self.parking_lots_dropped,
+            self.borders_created
+        )
+    }
+}
+
+// Roads crossing the boundary are cut where they cross the boundary shrunk by buffer, so that
+// border intersections don't sit right on the edge of the map. The boundary polygon (and the GPS
+// bounds) stay the same, so the trimmed roads just end a bit short of them. Buildings and areas
+// are still clipped exactly.
+pub fn clip_map(
+    map: &mut RawMap,
+    mode: ClipMode,
+    buffer: Distance,
+    timer: &mut Timer,
+) -> ClipStats {
     timer.start("clipping map to boundary");
+    let mut stats = ClipStats::default();
+    let num_borders_before = count_borders(map);
+    let num_roads_before = map.roads.len();
+    let num_bldgs_before = map.buildings.len();
 
     // So we can use retain_btreemap without borrowing issues
     let boundary_polygon = map.boundary_polygon.clone();
-    let boundary_lines: Vec<PolyLine> = map
+    // Points outside the bounding box are trivially outside, so check that first.
+    let bbox = boundary_polygon.get_bounds();
+    let in_bounds = |pt: Pt2D| bbox.contains(pt) && boundary_polygon.contains_pt(pt);
+    let boundary_lines: Vec<Line> = map
         .boundary_polygon
         .points()
         .windows(2)
-        .map(|pair| PolyLine::new(pair.to_vec()))
+        .map(|pair| Line::new(pair[0], pair[1]))
         .collect();
 
     // This is kind of indirect and slow, but first pass -- just remove roads that start or end
     // outside the boundary polygon.
     retain_btreemap(&mut map.roads, |_, r| {
-        let first_in = boundary_polygon.contains_pt(r.center_points[0]);
-        let last_in = boundary_polygon.contains_pt(*r.center_points.last().unwrap());
-        first_in || last_in
+        let first_in = in_bounds(r.center_points[0]);
+        let last_in = in_bounds(*r.center_points.last().unwrap());
+        first_in
+            || last_in
+            || match mode {
+                ClipMode::EitherEndpoint => false,
+                ClipMode::PassThrough => {
+                    // Loops start and end at the same point, so don't use PolyLine::new
+                    let crossings = border_crossings(
+                        &PolyLine::unchecked_new(r.center_points.clone()),
+                        &boundary_lines,
+                    );
+                    // Just grazing a corner of the boundary doesn't count
+                    crossings.len() >= 2
+                        && crossings.last().unwrap().0 - crossings[0].0
+                            > EPSILON_DIST + buffer * 2.0
+                }
+            }
     });
+    stats.roads_removed = num_roads_before - map.roads.len();
 
+    let inset = inset_boundary(&boundary_polygon, buffer);
+    let inset_lines: Vec<Line> = inset
+        .points()
+        .windows(2)
+        .map(|pair| Line::new(pair[0], pair[1]))
+        .collect();
     let road_ids: Vec<OriginalRoad> = map.roads.keys().cloned().collect();
     for id in road_ids {
-        let r = &map.roads[&id];
-        let first_in = map.boundary_polygon.contains_pt(r.center_points[0]);
-        let last_in = map
-            .boundary_polygon
-            .contains_pt(*r.center_points.last().unwrap());
+        let center = PolyLine::unchecked_new(map.roads[&id].center_points.clone());
+        if !border_crossings(&center, &boundary_lines).is_empty() {
+            if split_road_at_boundary(map, id, &inset, &inset_lines).is_empty() {
+                stats.roads_removed += 1;
+            } else {
+                stats.roads_trimmed += 1;
+            }
+        }
+    }
+    // Restrictions involving roads that're gone entirely don't apply anymore
+    let remaining: BTreeSet<OriginalRoad> = map.roads.keys().cloned().collect();
+    for r in map.roads.values_mut() {
+        r.turn_restrictions.retain(|(_, to)| remaining.contains(to));
+        r.complicated_turn_restrictions
+            .retain(|(via, to)| remaining.contains(via) && remaining.contains(to));
+    }
 
-        // Some roads start and end in-bounds, but dip out of bounds. Leave those alone for now.
-        if first_in && last_in {
+    // Trim buildings that straddle the boundary, keeping the biggest piece inside.
+    let bldg_ids: Vec<OriginalBuilding> = map.buildings.keys().cloned().collect();
+    for id in bldg_ids {
+        let b = map.buildings.get_mut(&id).unwrap();
+        if b.polygon.points().iter().all(|pt| in_bounds(*pt)) {
             continue;
         }
-
-        let mut move_i = if first_in { id.i2 } else { id.i1 };
-
-        // The road crosses the boundary. If the intersection happens to have another connected
-        // road, then we need to copy the intersection before trimming it. This effectively
-        // disconnects two roads in the map that would be connected if we left in some
-        // partly-out-of-bounds road.
-        if map
-            .roads
-            .keys()
-            .filter(|r2| r2.i1 == move_i || r2.i2 == move_i)
-            .count()
-            > 1
-        {
-            let copy = map.intersections[&move_i].clone();
-            // Start low, so we don't conflict with IDs generated by map_editor.
-            move_i = OriginalIntersection {
-                osm_node_id: map.new_osm_node_id(-1),
-            };
-            map.intersections.insert(move_i, copy);
-            println!("Disconnecting {} from some other stuff", id);
-            // We don't need to mark the existing intersection as a border and make sure to split
-            // all other roads up too. That'll happen later in this loop.
-        }
-
-        let i = map.intersections.get_mut(&move_i).unwrap();
-        i.intersection_type = IntersectionType::Border;
-
-        // Now trim it.
-        let mut mut_r = map.roads.remove(&id).unwrap();
-        let center = PolyLine::new(mut_r.center_points.clone());
-        let border_pt = boundary_lines
-            .iter()
-            .find_map(|l| center.intersection(l).map(|(pt, _)| pt))
-            .unwrap();
-        if first_in {
-            mut_r.center_points = center
-                .get_slice_ending_at(border_pt)
-                .unwrap()
-                .points()
-                .clone();
-            i.point = *mut_r.center_points.last().unwrap();
-            // This has no effect unless we made a copy of the intersection to disconnect it from
-            // other roads.
-            map.roads.insert(
-                OriginalRoad {
-                    osm_way_id: id.osm_way_id,
-                    i1: id.i1,
-                    i2: move_i,
-                },
-                mut_r,
-            );
-        } else {
-            mut_r.center_points = center
-                .reversed()
-                .get_slice_ending_at(border_pt)
-                .unwrap()
-                .reversed()
-                .points()
-                .clone();
-            i.point = mut_r.center_points[0];
-            map.roads.insert(
-                OriginalRoad {
-                    osm_way_id: id.osm_way_id,
-                    i1: move_i,
-                    i2: id.i2,
-                },
-                mut_r,
-            );
+        if !b.polygon.get_bounds().intersects(&bbox) {
+            map.buildings.remove(&id);
+            continue;
+        }
+        let clipped = boundary_polygon
+            .intersection(&b.polygon)
+            .into_iter()
+            .max_by(|p1, p2| p1.area().partial_cmp(&p2.area()).unwrap());
+        match clipped {
+            Some(polygon) if polygon.area() >= MIN_CLIPPED_BUILDING_AREA => {
+                b.polygon = polygon;
+            }
+            _ => {
+                map.buildings.remove(&id);
+            }
         }
     }
 
-    retain_btreemap(&mut map.buildings, |_, b| {
-        b.polygon
-            .points()
-            .iter()
-            .all(|pt| boundary_polygon.contains_pt(*pt))
-    });
+    stats.buildings_dropped = num_bldgs_before - map.buildings.len();
 
     let mut result_areas = Vec::new();
     for orig_area in map.areas.drain(..) {
@@ -125,12 +160,774 @@ pub fn clip_map(map: &mut RawMap, timer: &mut Timer) {
     }
     map.areas = result_areas;
 
-    // TODO Don't touch parking lots. It'll be visually obvious if a clip intersects one of these.
-    // The boundary should be manually adjusted.
+    // Parking lots are clipped like buildings. Their spots get inferred later from the polygon and
+    // aisles, so there's nothing else to fix up.
+    let num_lots_before = map.parking_lots.len();
+    let mut result_lots = Vec::new();
+    for mut lot in map.parking_lots.drain(..) {
+        if lot.polygon.points().iter().all(|pt| in_bounds(*pt)) {
+            result_lots.push(lot);
+            continue;
+        }
+        if !lot.polygon.get_bounds().intersects(&bbox) {
+            continue;
+        }
+        let clipped = boundary_polygon
+            .intersection(&lot.polygon)
+            .into_iter()
+            .max_by(|p1, p2| p1.area().partial_cmp(&p2.area()).unwrap());
+        match clipped {
+            Some(polygon) if polygon.area() >= MIN_CLIPPED_PARKING_LOT_AREA => {
+                lot.polygon = polygon;
+                result_lots.push(lot);
+            }
+            _ => {
+                timer.warn(format!(
+                    "Dropping parking lot {}; too little of it is inside the boundary",
+                    lot.osm_id
+                ));
+            }
+        }
+    }
+    map.parking_lots = result_lots;
+    stats.parking_lots_dropped = num_lots_before - map.parking_lots.len();
+    map.parking_aisles = map
+        .parking_aisles
+        .drain(..)
+        .filter_map(|pts| boundary_polygon.clip_polyline(&PolyLine::unchecked_new(pts)))
+        .filter(|pts| pts.len() >= 2)
+        .collect();
 
     if map.roads.is_empty() {
         panic!("There are no roads inside the clipping polygon");
     }
+    stats.borders_created = count_borders(map) - num_borders_before;
 
     timer.stop("clipping map to boundary");
+    stats
+}
+
+fn count_borders(map: &RawMap) -> usize {
+    map.intersections
+        .values()
+        .filter(|i| i.intersection_type == IntersectionType::Border)
+        .count()
+}
+
+// Turns the out-of-bounds intersection of a road crossing the boundary into a border, returning
+// the intersection to move. If the intersection happens to have another connected road, then we
+// need a new intersection before trimming the road. This effectively disconnects two roads in the
+// map that would be connected if we left in some partly-out-of-bounds road. Either way, nothing
+// describing the old out-of-bounds place carries over to the border.
+fn make_border(
+    map: &mut RawMap,
+    road: OriginalRoad,
+    i: OriginalIntersection,
+) -> OriginalIntersection {
+    let pt = map.intersections[&i].point;
+    if map
+        .roads
+        .keys()
+        .filter(|r2| r2.i1 == i || r2.i2 == i)
+        .count()
+        > 1
+    {
+        println!("Disconnecting {} from some other stuff", road);
+        // We don't need to mark the existing intersection as a border and make sure to split
+        // all other roads up too. That'll happen later in clip_map.
+        return new_border(map, pt);
+    }
+
+    map.intersections.insert(
+        i,
+        RawIntersection {
+            point: pt,
+            intersection_type: IntersectionType::Border,
+            elevation: Distance::ZERO,
+        },
+    );
+    i
+}
+
+// Replaces a road crossing the boundary with the pieces of it inside. Every place a piece meets the
+// boundary becomes a border intersection. Where possible, the road's original endpoints are moved
+// to the border instead of making new intersections. Returns the new roads.
+fn split_road_at_boundary(
+    map: &mut RawMap,
+    id: OriginalRoad,
+    boundary: &Polygon,
+    boundary_lines: &Vec<Line>,
+) -> Vec<OriginalRoad> {
+    let center = PolyLine::unchecked_new(map.roads[&id].center_points.clone());
+    let mut cuts = vec![Distance::ZERO];
+    cuts.extend(
+        border_crossings(&center, boundary_lines)
+            .into_iter()
+            .map(|(dist, _)| dist),
+    );
+    cuts.push(center.length());
+
+    let mut pieces: Vec<(Distance, Distance, PolyLine)> = Vec::new();
+    for pair in cuts.windows(2) {
+        if pair[1] - pair[0] <= EPSILON_DIST {
+            continue;
+        }
+        let piece = center.exact_slice(pair[0], pair[1]);
+        if boundary.contains_pt(piece.middle()) {
+            pieces.push((pair[0], pair[1], piece));
+        }
+    }
+
+    // Decide the endpoints of every piece before removing the road, since make_border needs to
+    // see it.
+    let num_pieces = pieces.len();
+    let mut endpoints = Vec::new();
+    for (idx, (start, end, piece)) in pieces.iter().enumerate() {
+        let i1 = if *start == Distance::ZERO {
+            id.i1
+        } else if idx == 0 {
+            let i = make_border(map, id, id.i1);
+            map.intersections.get_mut(&i).unwrap().point = piece.first_pt();
+            i
+        } else {
+            new_border(map, piece.first_pt())
+        };
+        // Loops start and end at the same intersection, so each end needs its own border.
+        let i2 = if *end == center.length() {
+            id.i2
+        } else if idx == num_pieces - 1 && id.i1 != id.i2 {
+            let i = make_border(map, id, id.i2);
+            map.intersections.get_mut(&i).unwrap().point = piece.last_pt();
+            i
+        } else {
+            new_border(map, piece.last_pt())
+        };
+        endpoints.push((i1, i2));
+    }
+
+    let orig = map.roads.remove(&id).unwrap();
+    let mut results = Vec::new();
+    for ((_, _, piece), (i1, i2)) in pieces.into_iter().zip(endpoints) {
+        let new_id = OriginalRoad {
+            osm_way_id: id.osm_way_id,
+            i1,
+            i2,
+        };
+        let mut r = orig.clone();
+        r.center_points = piece.points().clone();
+        map.roads.insert(new_id, r);
+        results.push(new_id);
+    }
+    fix_turn_restrictions(map, id, &results);
+    results
+}
+
+// Once a road is split into pieces, turn restrictions mentioning it have to point to the piece
+// still meeting the other road involved. Each piece only keeps its own restrictions that start
+// at one of its ends. If no piece fits, the restriction doesn't apply anymore.
+fn fix_turn_restrictions(map: &mut RawMap, orig: OriginalRoad, pieces: &Vec<OriginalRoad>) {
+    let touches = |r1: &OriginalRoad, r2: &OriginalRoad| {
+        r1.i1 == r2.i1 || r1.i1 == r2.i2 || r1.i2 == r2.i1 || r1.i2 == r2.i2
+    };
+    let rename = |other: &OriginalRoad, r: OriginalRoad| {
+        if r == orig {
+            pieces.iter().find(|p| touches(p, other)).cloned()
+        } else {
+            Some(r)
+        }
+    };
+    for (id, r) in map.roads.iter_mut() {
+        let is_piece = pieces.contains(id);
+        r.turn_restrictions = r
+            .turn_restrictions
+            .drain(..)
+            .filter_map(|(tr, to)| {
+                let to = rename(id, to)?;
+                if is_piece && !touches(id, &to) {
+                    return None;
+                }
+                Some((tr, to))
+            })
+            .collect();
+        r.complicated_turn_restrictions = r
+            .complicated_turn_restrictions
+            .drain(..)
+            .filter_map(|(via, to)| {
+                let via = rename(id, via)?;
+                let to = rename(&via, to)?;
+                if is_piece && !touches(id, &via) {
+                    return None;
+                }
+                Some((via, to))
+            })
+            .collect();
+    }
+}
+
+// The boundary shrunk by buffer on every side. Each edge moves inwards, and each corner goes where
+// the two moved edges next to it meet. The buffer should be small compared to the boundary, so
+// that no edge shrinks away entirely.
+fn inset_boundary(boundary: &Polygon, buffer: Distance) -> Polygon {
+    if buffer == Distance::ZERO {
+        return boundary.clone();
+    }
+    let mut pts = boundary.points().clone();
+    pts.dedup();
+    if pts[0] == *pts.last().unwrap() {
+        pts.pop();
+    }
+    let n = pts.len();
+    let first = Line::new(pts[0], pts[1]);
+    let right_is_inside = boundary.contains_pt(first.shift_right(buffer).middle());
+    let edges: Vec<Line> = (0..n)
+        .map(|idx| {
+            let l = Line::new(pts[idx], pts[(idx + 1) % n]);
+            if right_is_inside {
+                l.shift_right(buffer)
+            } else {
+                l.shift_left(buffer)
+            }
+        })
+        .collect();
+    let mut corners: Vec<Pt2D> = (0..n)
+        .map(|idx| {
+            let prev = &edges[(idx + n - 1) % n];
+            let next = &edges[idx];
+            // Edges in a straight line just meet end to end
+            prev.infinite()
+                .intersection(&next.infinite())
+                .unwrap_or_else(|| next.pt1())
+        })
+        .collect();
+    corners.push(corners[0]);
+    Polygon::new(&corners)
+}
+
+fn new_border(map: &mut RawMap, pt: Pt2D) -> OriginalIntersection {
+    // Start low, so we don't conflict with IDs generated by map_editor.
+    let id = OriginalIntersection {
+        osm_node_id: map.new_osm_node_id(-1),
+    };
+    map.intersections.insert(
+        id,
+        RawIntersection {
+            point: pt,
+            intersection_type: IntersectionType::Border,
+            elevation: Distance::ZERO,
+        },
+    );
+    id
+}
+
+// Where a road crosses the boundary, sorted by distance along the road. The road may cross the
+// same edge of the boundary more than once.
+fn border_crossings(center: &PolyLine, boundary_lines: &Vec<Line>) -> Vec<(Distance, Pt2D)> {
+    let mut crossings = Vec::new();
+    let mut dist_so_far = Distance::ZERO;
+    for l in center.lines() {
+        for edge in boundary_lines {
+            if let Some(pt) = l.intersection(edge) {
+                crossings.push((dist_so_far + l.pt1().dist_to(pt), pt));
+            }
+        }
+        dist_so_far += l.length();
+    }
+    crossings.sort_by_key(|(dist, _)| *dist);
+    // Hitting a corner of the boundary or a vertex of the road counts twice
+    crossings.dedup_by(|(dist2, _), (dist1, _)| *dist2 - *dist1 <= EPSILON_DIST);
+    crossings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map_model::raw::{RawBuilding, RawParkingLot, RawRoad, RestrictionType};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    // A 100x100 boundary with one road inside it, and one passing straight through with both ends
+    // outside.
+    fn test_map() -> RawMap {
+        let mut map = RawMap::blank("test", "test");
+        map.boundary_polygon = Polygon::rectangle(100.0, 100.0);
+        add_road(
+            &mut map,
+            1,
+            vec![Pt2D::new(-50.0, 50.0), Pt2D::new(150.0, 50.0)],
+        );
+        add_road(
+            &mut map,
+            2,
+            vec![Pt2D::new(10.0, 10.0), Pt2D::new(20.0, 10.0)],
+        );
+        map
+    }
+
+    // Endpoints get intersections 2*id and 2*id+1, unless the road loops back to where it started.
+    fn add_road(map: &mut RawMap, id: i64, center_points: Vec<Pt2D>) {
+        let first = center_points[0];
+        let last = *center_points.last().unwrap();
+        let i1 = OriginalIntersection {
+            osm_node_id: 2 * id,
+        };
+        let i2 = if first == last {
+            i1
+        } else {
+            OriginalIntersection {
+                osm_node_id: 2 * id + 1,
+            }
+        };
+        for (i, pt) in vec![(i1, first), (i2, last)] {
+            map.intersections.insert(
+                i,
+                RawIntersection {
+                    point: pt,
+                    intersection_type: IntersectionType::StopSign,
+                    elevation: Distance::ZERO,
+                },
+            );
+        }
+        map.roads.insert(
+            OriginalRoad {
+                osm_way_id: id,
+                i1,
+                i2,
+            },
+            RawRoad {
+                center_points,
+                osm_tags: BTreeMap::new(),
+                turn_restrictions: Vec::new(),
+                complicated_turn_restrictions: Vec::new(),
+                grades: Vec::new(),
+            },
+        );
+    }
+
+    fn assert_endpoints(map: &RawMap, id: i64, first: Pt2D, last: Pt2D) {
+        let r = map
+            .roads
+            .iter()
+            .find(|(r, _)| r.osm_way_id == id)
+            .unwrap()
+            .1;
+        assert!(r.center_points[0].approx_eq(first, EPSILON_DIST));
+        assert!(r
+            .center_points
+            .last()
+            .unwrap()
+            .approx_eq(last, EPSILON_DIST));
+    }
+
+    #[test]
+    fn test_either_endpoint_drops_pass_through() {
+        let mut map = test_map();
+        clip_map(
+            &mut map,
+            ClipMode::EitherEndpoint,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(
+            vec![2],
+            map.roads.keys().map(|r| r.osm_way_id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_pass_through() {
+        let mut map = test_map();
+        clip_map(
+            &mut map,
+            ClipMode::PassThrough,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(2, map.roads.len());
+
+        let (id, r) = map.roads.iter().find(|(r, _)| r.osm_way_id == 1).unwrap();
+        assert!(r.center_points[0].approx_eq(Pt2D::new(0.0, 50.0), EPSILON_DIST));
+        assert!(r
+            .center_points
+            .last()
+            .unwrap()
+            .approx_eq(Pt2D::new(100.0, 50.0), EPSILON_DIST));
+        for i in vec![id.i1, id.i2] {
+            assert_eq!(
+                IntersectionType::Border,
+                map.intersections[&i].intersection_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_clip_buildings() {
+        let mut map = test_map();
+        // Inside, straddling the boundary, and outside
+        for (id, x, y) in vec![(1, 40.0, 40.0), (2, 90.0, 40.0), (3, 200.0, 40.0)] {
+            map.buildings.insert(
+                OriginalBuilding { osm_way_id: id },
+                RawBuilding {
+                    polygon: Polygon::rectangle(20.0, 20.0).translate(x, y),
+                    osm_tags: BTreeMap::new(),
+                    public_garage_name: None,
+                    num_parking_spots: 0,
+                    amenities: BTreeSet::new(),
+                },
+            );
+        }
+        clip_map(
+            &mut map,
+            ClipMode::EitherEndpoint,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+
+        assert_eq!(
+            vec![1, 2],
+            map.buildings
+                .keys()
+                .map(|b| b.osm_way_id)
+                .collect::<Vec<_>>()
+        );
+        let b1 = &map.buildings[&OriginalBuilding { osm_way_id: 1 }];
+        assert!((b1.polygon.area() - 400.0).abs() < 0.1);
+        let b2 = &map.buildings[&OriginalBuilding { osm_way_id: 2 }];
+        assert!((b2.polygon.area() - 200.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_crossing_one_edge() {
+        let mut map = test_map();
+        add_road(
+            &mut map,
+            3,
+            vec![Pt2D::new(50.0, 20.0), Pt2D::new(150.0, 20.0)],
+        );
+        clip_map(
+            &mut map,
+            ClipMode::EitherEndpoint,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+
+        assert_endpoints(&map, 3, Pt2D::new(50.0, 20.0), Pt2D::new(100.0, 20.0));
+        let id = map.roads.keys().find(|r| r.osm_way_id == 3).unwrap();
+        assert_eq!(
+            IntersectionType::StopSign,
+            map.intersections[&id.i1].intersection_type
+        );
+        assert_eq!(
+            IntersectionType::Border,
+            map.intersections[&id.i2].intersection_type
+        );
+    }
+
+    #[test]
+    fn test_crossing_two_edges() {
+        let mut map = test_map();
+        // Enters through one edge and leaves through an adjacent one
+        add_road(
+            &mut map,
+            3,
+            vec![
+                Pt2D::new(-20.0, 80.0),
+                Pt2D::new(30.0, 80.0),
+                Pt2D::new(30.0, 150.0),
+            ],
+        );
+        clip_map(
+            &mut map,
+            ClipMode::PassThrough,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+
+        assert_endpoints(&map, 3, Pt2D::new(0.0, 80.0), Pt2D::new(30.0, 100.0));
+    }
+
+    #[test]
+    fn test_loop_road() {
+        let mut map = test_map();
+        // Starts and ends at the same point outside, dipping in through the left edge twice
+        add_road(
+            &mut map,
+            3,
+            vec![
+                Pt2D::new(-20.0, 20.0),
+                Pt2D::new(40.0, 20.0),
+                Pt2D::new(40.0, 40.0),
+                Pt2D::new(-20.0, 40.0),
+                Pt2D::new(-20.0, 20.0),
+            ],
+        );
+        clip_map(
+            &mut map,
+            ClipMode::PassThrough,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+
+        assert_endpoints(&map, 3, Pt2D::new(0.0, 20.0), Pt2D::new(0.0, 40.0));
+        let id = map.roads.keys().find(|r| r.osm_way_id == 3).unwrap();
+        assert_ne!(id.i1, id.i2);
+        for i in vec![id.i1, id.i2] {
+            assert_eq!(
+                IntersectionType::Border,
+                map.intersections[&i].intersection_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_u_shaped_road() {
+        let mut map = test_map();
+        // Both ends are inside, but the middle pokes out of the right edge
+        add_road(
+            &mut map,
+            3,
+            vec![
+                Pt2D::new(60.0, 20.0),
+                Pt2D::new(150.0, 20.0),
+                Pt2D::new(150.0, 60.0),
+                Pt2D::new(60.0, 60.0),
+            ],
+        );
+        let boundary_lines: Vec<Line> = map
+            .boundary_polygon
+            .points()
+            .windows(2)
+            .map(|pair| Line::new(pair[0], pair[1]))
+            .collect();
+        let id = *map.roads.keys().find(|r| r.osm_way_id == 3).unwrap();
+        let boundary = map.boundary_polygon.clone();
+        let pieces = split_road_at_boundary(&mut map, id, &boundary, &boundary_lines);
+
+        assert_eq!(2, pieces.len());
+        assert_eq!(id.i1, pieces[0].i1);
+        assert_eq!(id.i2, pieces[1].i2);
+        let r1 = &map.roads[&pieces[0]];
+        assert!(r1.center_points[0].approx_eq(Pt2D::new(60.0, 20.0), EPSILON_DIST));
+        assert!(r1
+            .center_points
+            .last()
+            .unwrap()
+            .approx_eq(Pt2D::new(100.0, 20.0), EPSILON_DIST));
+        let r2 = &map.roads[&pieces[1]];
+        assert!(r2.center_points[0].approx_eq(Pt2D::new(100.0, 60.0), EPSILON_DIST));
+        for i in vec![pieces[0].i2, pieces[1].i1] {
+            assert_eq!(
+                IntersectionType::Border,
+                map.intersections[&i].intersection_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_turn_restrictions_follow_split() {
+        let mut map = test_map();
+        // The same U-shaped road, plus a road meeting its second end. Turns between the two are
+        // banned both ways.
+        add_road(
+            &mut map,
+            3,
+            vec![
+                Pt2D::new(60.0, 20.0),
+                Pt2D::new(150.0, 20.0),
+                Pt2D::new(150.0, 60.0),
+                Pt2D::new(60.0, 60.0),
+            ],
+        );
+        let u_road = *map.roads.keys().find(|r| r.osm_way_id == 3).unwrap();
+        add_road(
+            &mut map,
+            4,
+            vec![Pt2D::new(20.0, 60.0), Pt2D::new(50.0, 60.0)],
+        );
+        let other = *map.roads.keys().find(|r| r.osm_way_id == 4).unwrap();
+        let mut r = map.roads.remove(&other).unwrap();
+        r.center_points.push(Pt2D::new(60.0, 60.0));
+        r.turn_restrictions
+            .push((RestrictionType::BanTurns, u_road));
+        let other = OriginalRoad {
+            osm_way_id: 4,
+            i1: other.i1,
+            i2: u_road.i2,
+        };
+        map.roads.insert(other, r);
+        map.roads
+            .get_mut(&u_road)
+            .unwrap()
+            .turn_restrictions
+            .push((RestrictionType::BanTurns, other));
+
+        clip_map(
+            &mut map,
+            ClipMode::EitherEndpoint,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+
+        let pieces: Vec<OriginalRoad> = map
+            .roads
+            .keys()
+            .filter(|r| r.osm_way_id == 3)
+            .cloned()
+            .collect();
+        assert_eq!(2, pieces.len());
+        let near_piece = *pieces.iter().find(|r| r.i2 == u_road.i2).unwrap();
+        let far_piece = *pieces.iter().find(|r| r.i1 == u_road.i1).unwrap();
+        assert_eq!(
+            vec![(RestrictionType::BanTurns, near_piece)],
+            map.roads[&other].turn_restrictions
+        );
+        assert_eq!(
+            vec![(RestrictionType::BanTurns, other)],
+            map.roads[&near_piece].turn_restrictions
+        );
+        assert!(map.roads[&far_piece].turn_restrictions.is_empty());
+    }
+
+    #[test]
+    fn test_keep_inside_intersection() {
+        let mut map = test_map();
+        // Two roads meet at a signal near the edge. One leaves the boundary, and both share an
+        // out-of-bounds intersection with a third road that never enters.
+        add_road(
+            &mut map,
+            3,
+            vec![Pt2D::new(90.0, 20.0), Pt2D::new(150.0, 20.0)],
+        );
+        let signal = map.roads.keys().find(|r| r.osm_way_id == 3).unwrap().i1;
+        let outside = map.roads.keys().find(|r| r.osm_way_id == 3).unwrap().i2;
+        map.intersections
+            .get_mut(&signal)
+            .unwrap()
+            .intersection_type = IntersectionType::TrafficSignal;
+        map.intersections.get_mut(&outside).unwrap().elevation = Distance::meters(30.0);
+        let other = OriginalRoad {
+            osm_way_id: 4,
+            i1: outside,
+            i2: signal,
+        };
+        map.roads.insert(
+            other,
+            RawRoad {
+                center_points: vec![
+                    Pt2D::new(150.0, 20.0),
+                    Pt2D::new(150.0, 40.0),
+                    Pt2D::new(90.0, 40.0),
+                ],
+                osm_tags: BTreeMap::new(),
+                turn_restrictions: Vec::new(),
+                complicated_turn_restrictions: Vec::new(),
+                grades: Vec::new(),
+            },
+        );
+        clip_map(
+            &mut map,
+            ClipMode::EitherEndpoint,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+
+        assert_eq!(
+            IntersectionType::TrafficSignal,
+            map.intersections[&signal].intersection_type
+        );
+        for r in map
+            .roads
+            .keys()
+            .filter(|r| r.osm_way_id == 3 || r.osm_way_id == 4)
+        {
+            let border = if r.i1 == signal { r.i2 } else { r.i1 };
+            assert_eq!(
+                IntersectionType::Border,
+                map.intersections[&border].intersection_type
+            );
+            assert_eq!(Distance::ZERO, map.intersections[&border].elevation);
+        }
+    }
+
+    #[test]
+    fn test_buffer() {
+        let mut map = test_map();
+        clip_map(
+            &mut map,
+            ClipMode::PassThrough,
+            Distance::meters(5.0),
+            &mut Timer::throwaway(),
+        );
+        assert_endpoints(&map, 1, Pt2D::new(5.0, 50.0), Pt2D::new(95.0, 50.0));
+        // Roads entirely inside aren't touched
+        assert_endpoints(&map, 2, Pt2D::new(10.0, 10.0), Pt2D::new(20.0, 10.0));
+    }
+
+    #[test]
+    fn test_buffer_at_an_angle() {
+        let mut map = test_map();
+        // Leaves through the right edge diagonally. It should end buffer away from the edge, not
+        // buffer back along the road.
+        add_road(
+            &mut map,
+            3,
+            vec![Pt2D::new(50.0, 20.0), Pt2D::new(150.0, 120.0)],
+        );
+        clip_map(
+            &mut map,
+            ClipMode::EitherEndpoint,
+            Distance::meters(5.0),
+            &mut Timer::throwaway(),
+        );
+        assert_endpoints(&map, 3, Pt2D::new(50.0, 20.0), Pt2D::new(95.0, 65.0));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut map = test_map();
+        let stats = clip_map(
+            &mut map,
+            ClipMode::PassThrough,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+        assert_eq!(
+            ClipStats {
+                roads_removed: 0,
+                roads_trimmed: 1,
+                buildings_dropped: 0,
+                parking_lots_dropped: 0,
+                borders_created: 2,
+            },
+            stats
+        );
+    }
+
+    #[test]
+    fn test_clip_parking_lots() {
+        let mut map = test_map();
+        // Overlapping the boundary, barely overlapping it, and outside
+        for (id, x, y) in vec![(1, 80.0, 40.0), (2, 98.0, 40.0), (3, 200.0, 40.0)] {
+            map.parking_lots.push(RawParkingLot {
+                polygon: Polygon::rectangle(40.0, 20.0).translate(x, y),
+                osm_id: id,
+                num_disabled_spots: 0,
+                cost_per_hour: None,
+            });
+        }
+        let stats = clip_map(
+            &mut map,
+            ClipMode::EitherEndpoint,
+            Distance::ZERO,
+            &mut Timer::throwaway(),
+        );
+
+        assert_eq!(2, stats.parking_lots_dropped);
+        assert_eq!(1, map.parking_lots.len());
+        assert_eq!(1, map.parking_lots[0].osm_id);
+        assert!((map.parking_lots[0].polygon.area() - 400.0).abs() < 0.1);
+        assert!(map.parking_lots[0]
+            .polygon
+            .points()
+            .iter()
+            .all(|pt| pt.x() <= 100.0 + EPSILON_DIST.inner_meters()));
+    }
 }
@@ -9,6 +9,8 @@ use kml::ExtraShapes;
 use map_model::osm;
 use map_model::raw::{DrivingSide, OriginalBuilding, OriginalRoad, RawMap};
 
+pub use self::clip::{ClipMode, ClipStats};
+
 // Just used for matching hints to different sides of a road.
 const DIRECTED_ROAD_THICKNESS: Distance = Distance::const_meters(2.5);
 
@@ -24,6 +26,9 @@ pub struct Options {
     pub gtfs: Option<String>,
     pub elevation: Option<String>,
     pub clip: Option<String>,
+    pub clip_mode: ClipMode,
+    // How far inside the clipping boundary to trim roads crossing it
+    pub clip_buffer: Distance,
     pub drive_on_right: bool,
 }
 
@@ -45,7 +50,8 @@ pub fn convert(opts: Options, timer: &mut abstutil::Timer) -> RawMap {
         ),
         timer,
     );
-    clip::clip_map(&mut map, timer);
+    let clip_stats = clip::clip_map(&mut map, opts.clip_mode, opts.clip_buffer, timer);
+    timer.note(format!("Clipping {}", clip_stats));
     map.driving_side = if opts.drive_on_right {
         DrivingSide::Right
     } else {
@@ -344,4 +350,24 @@ fn use_elevation(map: &mut RawMap, path: &str, timer: &mut Timer) {
         i.elevation = elevation.get(i.point.forcibly_to_gps(&map.gps_bounds));
     }
     timer.stop("apply elevation data to intersections");
+
+    timer.start("apply elevation data to roads");
+    for r in map.roads.values_mut() {
+        let heights: Vec<Distance> = r
+            .center_points
+            .iter()
+            .map(|pt| elevation.get(pt.forcibly_to_gps(&map.gps_bounds)))
+            .collect();
+        let mut grades = Vec::new();
+        for (pair, heights) in r.center_points.windows(2).zip(heights.windows(2)) {
+            let run = pair[0].dist_to(pair[1]);
+            if run == Distance::ZERO {
+                grades.push(0.0);
+            } else {
+                grades.push(100.0 * ((heights[1] - heights[0]) / run));
+            }
+        }
+        r.set_grade(grades);
+    }
+    timer.stop("apply elevation data to roads");
 }
@@ -166,6 +166,7 @@ pub fn extract_osm(
                     osm_tags: tags,
                     turn_restrictions: Vec::new(),
                     complicated_turn_restrictions: Vec::new(),
+                    grades: Vec::new(),
                 },
             ));
         } else if is_bldg(&tags) {
@@ -219,6 +220,11 @@ pub fn extract_osm(
             map.parking_lots.push(RawParkingLot {
                 polygon: Polygon::new(&pts),
                 osm_id: way.id,
+                num_disabled_spots: tags
+                    .get(osm::CAPACITY_DISABLED)
+                    .and_then(|x| x.parse::<usize>().ok())
+                    .unwrap_or(0),
+                cost_per_hour: tags.get("charge").and_then(|x| osm::parse_hourly_charge(x)),
             });
         } else if tags.get("highway") == Some(&"service".to_string()) {
             map.parking_aisles.push(pts);
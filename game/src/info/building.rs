@@ -22,7 +22,7 @@ pub fn info(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BuildingID
     }
 
     if let Some(ref p) = b.parking {
-        let free = app.primary.sim.get_free_offstreet_spots(b.id).len();
+        let free = app.primary.sim.count_free_offstreet_spots(b.id);
         if let Some(ref n) = p.public_garage_name {
             kv.push((
                 "Parking",
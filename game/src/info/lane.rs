@@ -2,14 +2,13 @@ use crate::app::App;
 use crate::info::{header_btns, make_table, make_tabs, throughput, DataOptions, Details, Tab};
 use abstutil::prettyprint_usize;
 use ezgui::{Btn, EventCtx, Line, LinePlot, PlotOptions, Series, Text, TextExt, Widget};
-use map_model::{LaneID, OriginalLane};
+use map_model::{LaneID, OriginalLane, Traversable};
 use std::collections::HashSet;
 
 pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Vec<Widget> {
     let mut rows = header(ctx, app, details, id, Tab::LaneInfo(id));
     let map = &app.primary.map;
     let l = map.get_l(id);
-    let r = map.get_r(l.parent);
 
     let mut kv = Vec::new();
 
@@ -22,12 +21,15 @@ pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Vec
             "Parking",
             format!(
                 "{} / {} spots available",
-                app.primary.sim.get_free_onstreet_spots(l.id).len(),
-                l.number_parking_spots()
+                app.primary.sim.count_free_onstreet_spots(l.id),
+                app.primary.sim.count_onstreet_spots(l.id)
             ),
         ));
     } else {
-        kv.push(("Speed limit", r.speed_limit.to_string()));
+        kv.push((
+            "Speed limit",
+            Traversable::Lane(l.id).speed_limit(map).to_string(),
+        ));
     }
 
     kv.push(("Length", l.length().describe_rounded()));
@@ -35,7 +37,7 @@ pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Vec
     rows.extend(make_table(ctx, kv));
 
     if l.is_parking() {
-        let capacity = l.number_parking_spots();
+        let capacity = app.primary.sim.count_onstreet_spots(l.id);
         let mut series = vec![Series {
             label: format!("After \"{}\"", app.primary.map.get_edits().edits_name),
             color: app.cs.after_changes,
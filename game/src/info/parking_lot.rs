@@ -13,7 +13,7 @@ pub fn info(ctx: &mut EventCtx, app: &App, details: &mut Details, id: ParkingLot
     rows.push(
         format!(
             "{} / {} spots available",
-            prettyprint_usize(app.primary.sim.get_free_lot_spots(pl.id).len()),
+            prettyprint_usize(app.primary.sim.count_free_lot_spots(pl.id)),
             prettyprint_usize(capacity)
         )
         .draw_text(ctx),
@@ -509,7 +509,7 @@ fn header(
                         Some("../data/system/assets/meters/pedestrian.svg"),
                     ),
                     AgentID::Car(c) => match c.1 {
-                        VehicleType::Car => {
+                        VehicleType::Car | VehicleType::Motorcycle => {
                             ("driving", Some("../data/system/assets/meters/car.svg"))
                         }
                         VehicleType::Bike => {
@@ -59,7 +59,7 @@ pub fn ongoing(
     let activity = match agent {
         AgentID::Pedestrian(_) => "walking",
         AgentID::Car(c) => match c.1 {
-            VehicleType::Car => "driving",
+            VehicleType::Car | VehicleType::Motorcycle => "driving",
             VehicleType::Bike => "biking",
             VehicleType::Bus => unreachable!(),
         },
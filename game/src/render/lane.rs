@@ -217,10 +217,15 @@ fn calculate_parking_lines(map: &Map, lane: &Lane) -> Vec<Polygon> {
     let leg_length = Distance::meters(1.0);
 
     let mut result = Vec::new();
-    let num_spots = lane.number_parking_spots();
-    if num_spots > 0 {
-        for idx in 0..=num_spots {
-            let (pt, lane_angle) = lane.dist_along(PARKING_SPOT_LENGTH * (1.0 + idx as f64));
+    let lengths = lane.parking_spot_lengths();
+    if !lengths.is_empty() {
+        // The boundaries between spots
+        let mut dists = vec![PARKING_SPOT_LENGTH];
+        for length in lengths {
+            dists.push(*dists.last().unwrap() + length);
+        }
+        for dist in dists {
+            let (pt, lane_angle) = lane.dist_along(dist);
             let perp_angle = map.driving_side_angle(lane_angle.rotate_degs(270.0));
             // Find the outside of the lane. Actually, shift inside a little bit, since the line
             // will have thickness, but shouldn't really intersect the adjacent line
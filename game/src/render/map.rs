@@ -475,7 +475,7 @@ impl AgentColorScheme {
 
     fn color(&self, agent: &UnzoomedAgent) -> Option<Color> {
         let category = match agent.vehicle_type {
-            Some(VehicleType::Car) => "Car".to_string(),
+            Some(VehicleType::Car) | Some(VehicleType::Motorcycle) => "Car".to_string(),
             Some(VehicleType::Bike) => "Bike".to_string(),
             Some(VehicleType::Bus) => "Bus".to_string(),
             None => "Pedestrian".to_string(),
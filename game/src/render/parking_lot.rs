@@ -5,8 +5,8 @@ use crate::render::{DrawOptions, Renderable, OUTLINE_THICKNESS};
 use ezgui::{Drawable, GeomBatch, GfxCtx, Prerender};
 use geom::{Distance, Line, PolyLine, Polygon, Pt2D};
 use map_model::{
-    Map, ParkingLot, ParkingLotID, NORMAL_LANE_THICKNESS, PARKING_LOT_SPOT_LENGTH,
-    SIDEWALK_THICKNESS,
+    Map, ParkingLot, ParkingLotID, SpotOrientation, NORMAL_LANE_THICKNESS, PARKING_LOT_SPOT_LENGTH,
+    PARKING_LOT_SPOT_WIDTH, SIDEWALK_THICKNESS,
 };
 
 pub struct DrawParkingLot {
@@ -62,9 +62,11 @@ impl DrawParkingLot {
                 PolyLine::unchecked_new(aisle.clone()).make_polygons(aisle_thickness),
             );
         }
-        let width = NORMAL_LANE_THICKNESS;
-        let height = PARKING_LOT_SPOT_LENGTH;
-        for (pt, angle) in &lot.spots {
+        for (pt, angle, orientation) in &lot.spots {
+            let (width, height) = match orientation {
+                SpotOrientation::Perpendicular => (PARKING_LOT_SPOT_WIDTH, PARKING_LOT_SPOT_LENGTH),
+                SpotOrientation::Parallel => (PARKING_LOT_SPOT_LENGTH, PARKING_LOT_SPOT_WIDTH),
+            };
             let left = pt.project_away(width / 2.0, angle.rotate_degs(90.0));
             let right = pt.project_away(width / 2.0, angle.rotate_degs(-90.0));
 
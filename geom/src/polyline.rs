@@ -350,6 +350,16 @@ impl PolyLine {
         self.shift_with_corrections(-width)
     }
 
+    // Like shift_right, but fails instead of panicking when the shifted points run into each
+    // other. Angle changes aren't reported; callers that care have to check.
+    pub fn maybe_shift_right(&self, width: Distance) -> Result<PolyLine, String> {
+        self.maybe_shift_with_corrections(width)
+    }
+
+    pub fn maybe_shift_left(&self, width: Distance) -> Result<PolyLine, String> {
+        self.maybe_shift_with_corrections(-width)
+    }
+
     // Things to remember about shifting polylines:
     // - the length before and after probably don't match up
     // - the number of points will match
@@ -357,12 +367,23 @@ impl PolyLine {
         let mut raw = self.shift_with_sharp_angles(width, MITER_THRESHOLD);
         raw.dedup();
         let result = PolyLine::new(raw);
-        let fixed = if result.pts.len() == self.pts.len() {
+        check_angles(self, self.fix_shifted_angles(result))
+    }
+
+    fn maybe_shift_with_corrections(&self, width: Distance) -> Result<PolyLine, String> {
+        let mut raw = self.shift_with_sharp_angles(width, MITER_THRESHOLD);
+        raw.dedup();
+        let result = PolyLine::maybe_new(raw)
+            .ok_or_else(|| format!("shifting by {} makes points collapse or repeat", width))?;
+        Ok(self.fix_shifted_angles(result))
+    }
+
+    fn fix_shifted_angles(&self, result: PolyLine) -> PolyLine {
+        if result.pts.len() == self.pts.len() {
             fix_angles(self, result)
         } else {
             result
-        };
-        check_angles(self, fixed)
+        }
     }
 
     fn shift_with_sharp_angles(&self, width: Distance, miter_threshold: f64) -> Vec<Pt2D> {
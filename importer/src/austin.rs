@@ -29,6 +29,8 @@ pub fn osm_to_raw(name: &str) {
             gtfs: None,
             elevation: None,
             clip: Some(format!("../data/input/austin/polygons/{}.poly", name)),
+            clip_mode: convert_osm::ClipMode::EitherEndpoint,
+            clip_buffer: geom::Distance::ZERO,
             drive_on_right: true,
         },
         &mut abstutil::Timer::throwaway(),
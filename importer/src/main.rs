@@ -164,6 +164,8 @@ fn oneshot(osm_path: String, clip: Option<String>) {
             gtfs: None,
             elevation: None,
             clip,
+            clip_mode: convert_osm::ClipMode::EitherEndpoint,
+            clip_buffer: geom::Distance::ZERO,
             drive_on_right: true,
         },
         &mut timer,
@@ -68,6 +68,8 @@ pub fn osm_to_raw(name: &str) {
             gtfs: Some("../data/input/seattle/google_transit".to_string()),
             elevation: Some("../data/input/seattle/N47W122.hgt".to_string()),
             clip: Some(format!("../data/input/seattle/polygons/{}.poly", name)),
+            clip_mode: convert_osm::ClipMode::EitherEndpoint,
+            clip_buffer: geom::Distance::ZERO,
             drive_on_right: true,
         },
         &mut abstutil::Timer::throwaway(),
@@ -7,18 +7,21 @@ use ezgui::{
     GfxCtx, HorizontalAlignment, Key, Line, Outcome, ScreenPt, Text, VerticalAlignment, Widget,
     Wizard, GUI,
 };
-use geom::{Distance, Line, Polygon, Pt2D};
+use geom::{ArrowCap, Circle, Distance, Line, PolyLine, Polygon, Pt2D};
 use map_model::raw::{OriginalBuilding, OriginalIntersection, OriginalRoad, RestrictionType};
-use map_model::{osm, NORMAL_LANE_THICKNESS};
-use model::{Model, ID};
+use map_model::{osm, LaneType, RoadSpec, NORMAL_LANE_THICKNESS};
+use model::{Model, ID, MIN_ROAD_PT_SPACING};
 use std::collections::HashSet;
 
+const GRID_SIZE: Distance = Distance::const_meters(10.0);
+
 struct UI {
     model: Model,
     state: State,
     composite: Composite,
     popup: Option<Drawable>,
     info_key_held: bool,
+    show_scale_bar: bool,
 
     last_id: Option<ID>,
 }
@@ -27,19 +30,33 @@ enum State {
     Viewing { short_roads: HashSet<OriginalRoad> },
     MovingIntersection(OriginalIntersection),
     MovingBuilding(OriginalBuilding),
+    ResizingBuilding(OriginalBuilding, Wizard),
+    EditingBuildingParking(OriginalBuilding, Wizard),
+    DrawingBuilding(Vec<Pt2D>),
+    Measuring(Vec<Pt2D>),
     MovingRoadPoint(OriginalRoad, usize),
+    // Where the cursor was last
+    MovingSelection(Pt2D),
     CreatingRoad(OriginalIntersection),
     EditingLanes(OriginalRoad, Wizard),
     EditingRoadAttribs(OriginalRoad, Wizard),
+    EditingLaneSpeed(OriginalRoad, Wizard),
+    EditingDirectionalSpeed(OriginalRoad, Wizard),
     SavingModel(Wizard),
+    ImportingGeoJSON(Wizard),
     // bool is if key is down
     SelectingRectangle(Pt2D, Pt2D, bool),
+    SelectingLasso(Vec<Pt2D>),
+    SettingSpeedInArea(Polygon, Wizard),
     CreatingTurnRestrictionPt1(OriginalRoad),
     CreatingTurnRestrictionPt2(OriginalRoad, OriginalRoad, Wizard),
     // bool is show_tooltip
     PreviewIntersection(Drawable, bool),
     EnteringWarp(Wizard),
+    // All matches and the current one
+    BrowsingRoads(Vec<OriginalRoad>, usize),
     StampingRoads(String, String, String, String),
+    StampingLanes(RoadSpec),
 }
 
 impl State {
@@ -79,10 +96,23 @@ impl UI {
                         vec![
                             (hotkey(Key::Escape), "quit"),
                             (None, "save raw map"),
+                            (None, "export to GeoJSON"),
+                            (None, "export to OSM XML"),
+                            (None, "import roads from GeoJSON"),
                             (hotkey(Key::J), "warp to something"),
                             (hotkey(Key::G), "preview all intersections"),
                             (None, "find overlapping intersections"),
+                            (None, "find roads crossing buildings"),
+                            (None, "find overlapping roads"),
+                            (None, "find disconnected roads"),
+                            (None, "repair road endpoints"),
+                            (None, "find degenerate roads"),
                             (hotkey(Key::Z), "find/clear short roads"),
+                            (None, "show/hide scale bar"),
+                            (None, "toggle grid snapping"),
+                            (None, "toggle speed limit colors"),
+                            (None, "toggle intersection degree colors"),
+                            (None, "generate a grid"),
                         ]
                         .into_iter()
                         .map(|(key, action)| Btn::text_fg(action).build_def(ctx, key))
@@ -96,12 +126,57 @@ impl UI {
             .build(ctx),
             popup: None,
             info_key_held: false,
+            show_scale_bar: false,
 
             last_id: None,
         }
     }
 }
 
+impl UI {
+    // Highlights everything that deleting the area would remove
+    fn draw_delete_preview(&self, g: &mut GfxCtx, area: &Polygon) {
+        let (roads, intersections, bldgs) = self.model.preview_delete_inside(area);
+        let ids = roads
+            .into_iter()
+            .map(ID::Road)
+            .chain(intersections.into_iter().map(ID::Intersection))
+            .chain(bldgs.into_iter().map(ID::Building));
+        for id in ids {
+            if let Some(p) = self.model.world.get_unioned_polygon(id) {
+                g.draw_polygon(Color::RED.alpha(0.5), p);
+            }
+        }
+    }
+
+    // In the bottom-left corner, with a north arrow above it
+    fn draw_scale_bar(&self, g: &mut GfxCtx) {
+        let zoom = g.canvas.cam_zoom;
+        let (dist, label) = self.model.scale_bar_length(200.0, zoom);
+        let width = dist.inner_meters() * zoom;
+        let (x, y) = (20.0, g.canvas.window_height - 50.0);
+
+        let mut batch = GeomBatch::new();
+        batch.push(Color::BLACK, Polygon::rectangle(width, 5.0).translate(x, y));
+        batch.push(
+            Color::BLACK,
+            PolyLine::new(vec![Pt2D::new(x, y - 20.0), Pt2D::new(x, y - 70.0)])
+                .make_arrow(Distance::meters(3.0), ArrowCap::Triangle)
+                .unwrap(),
+        );
+        batch.append(Text::from(Line(label)).render_g(g).translate(x, y + 10.0));
+        batch.append(
+            Text::from(Line("N"))
+                .render_g(g)
+                .translate(x + 10.0, y - 60.0),
+        );
+
+        g.fork_screenspace();
+        batch.draw(g);
+        g.unfork();
+    }
+}
+
 impl GUI for UI {
     fn event(&mut self, ctx: &mut EventCtx) -> EventLoopMode {
         if self.info_key_held {
@@ -160,6 +235,9 @@ impl GUI for UI {
                         } else if ctx.input.key_pressed(Key::Backspace, "delete building") {
                             self.model.delete_i(i);
                             self.model.world.handle_mouseover(ctx);
+                        } else if ctx.input.key_pressed(Key::X, "dissolve intersection") {
+                            self.model.dissolve_i(i, ctx.prerender);
+                            self.model.world.handle_mouseover(ctx);
                         } else if ctx.input.key_pressed(Key::T, "toggle intersection type") {
                             self.model.toggle_i_type(i, ctx.prerender);
                         } else if !self.model.intersection_geom
@@ -169,6 +247,14 @@ impl GUI for UI {
                         {
                             let draw = preview_intersection(i, &self.model, ctx);
                             self.state = State::PreviewIntersection(draw, false);
+                        } else if ctx.input.key_pressed(Key::M, "show turn movements") {
+                            let draw = preview_turns(i, &self.model, ctx);
+                            self.state = State::PreviewIntersection(draw, false);
+                        } else if ctx.input.key_pressed(Key::Space, "add to selection") {
+                            self.model.add_to_selection(ID::Intersection(i));
+                        } else if ctx.input.key_pressed(Key::A, "check reachability by car") {
+                            let draw = find_unreachable_intersections(i, &self.model, ctx);
+                            self.state = State::PreviewIntersection(draw, false);
                         }
                     }
                     Some(ID::Building(b)) => {
@@ -177,12 +263,22 @@ impl GUI for UI {
                         } else if ctx.input.key_pressed(Key::Backspace, "delete building") {
                             self.model.delete_b(b);
                             self.model.world.handle_mouseover(ctx);
+                        } else if ctx.input.key_pressed(Key::Space, "add to selection") {
+                            self.model.add_to_selection(ID::Building(b));
+                        } else if ctx.input.key_pressed(Key::P, "set parking") {
+                            self.state = State::EditingBuildingParking(b, Wizard::new());
+                        } else if ctx.input.key_pressed(Key::E, "resize building") {
+                            self.state = State::ResizingBuilding(b, Wizard::new());
+                        } else if ctx.input.key_pressed(Key::LeftBracket, "rotate left") {
+                            self.model.rotate_b(b, -15.0, ctx.prerender);
+                        } else if ctx.input.key_pressed(Key::RightBracket, "rotate right") {
+                            self.model.rotate_b(b, 15.0, ctx.prerender);
                         }
                     }
                     Some(ID::Road(r)) => {
                         let could_swap = {
                             let lanes = self.model.map.roads[&r].get_spec();
-                            lanes.fwd != lanes.back
+                            lanes.fwd != lanes.back && !self.model.is_oneway(r)
                         };
 
                         if ctx.input.key_pressed(Key::Backspace, "delete road") {
@@ -192,9 +288,21 @@ impl GUI for UI {
                             self.state = State::EditingLanes(r, Wizard::new());
                         } else if ctx.input.key_pressed(Key::N, "edit name/speed") {
                             self.state = State::EditingRoadAttribs(r, Wizard::new());
+                        } else if ctx.input.key_pressed(Key::L, "edit speed of one lane") {
+                            self.state = State::EditingLaneSpeed(r, Wizard::new());
+                        } else if ctx.input.key_pressed(Key::V, "edit speed in one direction") {
+                            self.state = State::EditingDirectionalSpeed(r, Wizard::new());
                         } else if could_swap && ctx.input.key_pressed(Key::S, "swap lanes") {
                             self.model.swap_lanes(r, ctx.prerender);
                             self.model.world.handle_mouseover(ctx);
+                        } else if ctx.input.key_pressed(Key::U, "reverse road direction") {
+                            self.model.reverse_r(r, ctx.prerender);
+                            self.model.world.handle_mouseover(ctx);
+                        } else if !self.model.is_oneway(r)
+                            && ctx.input.key_pressed(Key::O, "make one-way")
+                        {
+                            self.model.make_oneway(r, ctx.prerender);
+                            self.model.world.handle_mouseover(ctx);
                         } else if ctx.input.key_pressed(Key::F, "toggle sidewalks") {
                             self.model.toggle_r_sidewalks(r, ctx.prerender);
                             self.model.world.handle_mouseover(ctx);
@@ -223,6 +331,14 @@ impl GUI for UI {
                                     .cloned()
                                     .unwrap_or_else(|| "residential".to_string()),
                             );
+                        } else if ctx
+                            .input
+                            .key_pressed(Key::W, "use these lanes for new roads")
+                        {
+                            let spec = self.model.copy_road_config(r);
+                            self.model.set_default_road_spec(spec);
+                        } else if ctx.input.key_pressed(Key::Y, "copy lanes to other roads") {
+                            self.state = State::StampingLanes(self.model.copy_road_config(r));
                         } else if cursor.is_some()
                             && ctx.input.key_pressed(Key::P, "create new point")
                         {
@@ -233,6 +349,14 @@ impl GUI for UI {
                             }
                         } else if ctx.input.key_pressed(Key::X, "clear interior points") {
                             self.model.clear_r_pts(r, ctx.prerender);
+                        } else if cursor.is_some()
+                            && ctx.input.key_pressed(Key::D, "split road here")
+                        {
+                            if let Some((i, _)) =
+                                self.model.split_r(r, cursor.unwrap(), ctx.prerender)
+                            {
+                                self.model.world.force_set_selection(ID::Intersection(i));
+                            }
                         }
                     }
                     Some(ID::RoadPoint(r, idx)) => {
@@ -267,6 +391,25 @@ impl GUI for UI {
                                         self.state = State::SavingModel(Wizard::new());
                                     }
                                 }
+                                "export to GeoJSON" => {
+                                    let name = if self.model.map.name != "" {
+                                        self.model.map.name.clone()
+                                    } else {
+                                        "untitled".to_string()
+                                    };
+                                    self.model.export_geojson(&format!("{}.geojson", name));
+                                }
+                                "export to OSM XML" => {
+                                    let name = if self.model.map.name != "" {
+                                        self.model.map.name.clone()
+                                    } else {
+                                        "untitled".to_string()
+                                    };
+                                    self.model.export_osm_xml(&format!("{}.osm", name));
+                                }
+                                "import roads from GeoJSON" => {
+                                    self.state = State::ImportingGeoJSON(Wizard::new());
+                                }
                                 "warp to something" => {
                                     self.state = State::EnteringWarp(Wizard::new());
                                 }
@@ -280,6 +423,56 @@ impl GUI for UI {
                                     let draw = find_overlapping_intersections(&self.model, ctx);
                                     self.state = State::PreviewIntersection(draw, false);
                                 }
+                                "find roads crossing buildings" => {
+                                    let draw = find_roads_crossing_buildings(&self.model, ctx);
+                                    self.state = State::PreviewIntersection(draw, false);
+                                }
+                                "find overlapping roads" => {
+                                    let draw = find_overlapping_roads(&self.model, ctx);
+                                    self.state = State::PreviewIntersection(draw, false);
+                                }
+                                "find disconnected roads" => {
+                                    let draw = find_disconnected_roads(&self.model, ctx);
+                                    self.state = State::PreviewIntersection(draw, false);
+                                }
+                                "repair road endpoints" => {
+                                    let fixed = self.model.repair_endpoints(ctx.prerender);
+                                    println!("Fixed endpoints of {} roads", fixed);
+                                }
+                                "show/hide scale bar" => {
+                                    self.show_scale_bar = !self.show_scale_bar;
+                                }
+                                "generate a grid" => {
+                                    self.model.generate_grid(
+                                        5,
+                                        5,
+                                        Distance::meters(100.0),
+                                        Pt2D::new(0.0, 0.0),
+                                        ctx.prerender,
+                                    );
+                                }
+                                "toggle intersection degree colors" => {
+                                    self.model.toggle_degree_colors(ctx.prerender);
+                                }
+                                "toggle speed limit colors" => {
+                                    self.model.toggle_speed_colors(ctx.prerender);
+                                }
+                                "toggle grid snapping" => {
+                                    if self.model.get_grid_snap().is_some() {
+                                        self.model.set_grid_snap(None);
+                                    } else {
+                                        self.model.set_grid_snap(Some(GRID_SIZE));
+                                    }
+                                }
+                                "find degenerate roads" => {
+                                    let roads =
+                                        self.model.find_degenerate_roads(MIN_ROAD_PT_SPACING);
+                                    println!("{} degenerate roads", roads.len());
+                                    for r in &roads {
+                                        println!("- {}", r);
+                                    }
+                                    *short_roads = roads.into_iter().collect();
+                                }
                                 "find/clear short roads" => {
                                     if short_roads.is_empty() {
                                         *short_roads = find_short_roads(&self.model);
@@ -303,10 +496,25 @@ impl GUI for UI {
                                         let id = self.model.create_b(pt, ctx.prerender);
                                         self.model.world.force_set_selection(id);
                                     }
+                                } else if ctx.input.key_pressed(Key::O, "draw a building footprint")
+                                {
+                                    self.state = State::DrawingBuilding(Vec::new());
+                                } else if ctx.input.key_pressed(Key::Q, "measure distance") {
+                                    self.state = State::Measuring(Vec::new());
                                 } else if ctx.input.key_pressed(Key::LeftShift, "select area") {
                                     if let Some(pt) = cursor {
                                         self.state = State::SelectingRectangle(pt, pt, true);
                                     }
+                                } else if ctx.input.key_pressed(Key::L, "lasso an area to delete") {
+                                    self.state = State::SelectingLasso(Vec::new());
+                                } else if !self.model.get_selection().is_empty() {
+                                    if ctx.input.key_pressed(Key::K, "clear selection") {
+                                        self.model.clear_selection();
+                                    } else if cursor.is_some()
+                                        && ctx.input.key_pressed(Key::LeftControl, "move selection")
+                                    {
+                                        self.state = State::MovingSelection(cursor.unwrap());
+                                    }
                                 }
                             }
                         }
@@ -317,6 +525,9 @@ impl GUI for UI {
                 if let Some(pt) = cursor {
                     self.model.move_i(id, pt, ctx.prerender);
                     if ctx.input.key_released(Key::LeftControl) {
+                        if self.model.snap_i_to_road(id, ctx.prerender) {
+                            self.model.world.handle_mouseover(ctx);
+                        }
                         self.state = State::viewing();
                     }
                 }
@@ -329,6 +540,19 @@ impl GUI for UI {
                     }
                 }
             }
+            State::MovingSelection(ref mut last_pt) => {
+                if let Some(pt) = cursor {
+                    self.model.move_selection(
+                        Distance::meters(pt.x() - last_pt.x()),
+                        Distance::meters(pt.y() - last_pt.y()),
+                        ctx.prerender,
+                    );
+                    *last_pt = pt;
+                    if ctx.input.key_released(Key::LeftControl) {
+                        self.state = State::viewing();
+                    }
+                }
+            }
             State::MovingRoadPoint(r, idx) => {
                 if let Some(pt) = cursor {
                     self.model.move_r_pt(r, idx, pt, ctx.prerender);
@@ -363,19 +587,7 @@ impl GUI for UI {
                 }
             }
             State::EditingRoadAttribs(id, ref mut wizard) => {
-                let (orig_name, orig_speed) = {
-                    let r = &self.model.map.roads[&id];
-                    (
-                        r.osm_tags
-                            .get(osm::NAME)
-                            .cloned()
-                            .unwrap_or_else(String::new),
-                        r.osm_tags
-                            .get(osm::MAXSPEED)
-                            .cloned()
-                            .unwrap_or_else(String::new),
-                    )
-                };
+                let (orig_name, orig_speed) = self.model.get_r_name_and_speed(id);
 
                 let mut wiz = wizard.wrap(ctx);
                 let mut done = false;
@@ -396,6 +608,141 @@ impl GUI for UI {
                     self.model.world.handle_mouseover(ctx);
                 }
             }
+            State::Measuring(ref mut pts) => {
+                if ctx.input.key_pressed(Key::Escape, "stop measuring") {
+                    self.state = State::viewing();
+                } else if cursor.is_some() && ctx.input.key_pressed(Key::Space, "add a point") {
+                    pts.push(cursor.unwrap());
+                }
+            }
+            State::DrawingBuilding(ref mut pts) => {
+                if ctx.input.key_pressed(Key::Escape, "stop drawing building") {
+                    self.state = State::viewing();
+                } else if ctx.input.key_pressed(Key::Enter, "finish building") {
+                    let pts = pts.clone();
+                    if let Some(id) = self.model.create_b_from_points(pts, ctx.prerender) {
+                        self.model.world.force_set_selection(id);
+                    }
+                    self.state = State::viewing();
+                } else if cursor.is_some() && ctx.input.key_pressed(Key::Space, "add a corner") {
+                    pts.push(cursor.unwrap());
+                }
+            }
+            State::EditingBuildingParking(id, ref mut wizard) => {
+                let mut wiz = wizard.wrap(ctx);
+                let mut done = false;
+                if let Some(num_spots) = wiz.input_usize("How many parking spots?") {
+                    if let Some(name) = wiz.input_string_prefilled(
+                        "Name of the public garage (leave empty if private)",
+                        String::new(),
+                    ) {
+                        self.model.set_b_parking(
+                            id,
+                            num_spots,
+                            if name.is_empty() { None } else { Some(name) },
+                            ctx.prerender,
+                        );
+                        done = true;
+                    }
+                }
+                if done || wizard.aborted() {
+                    self.state = State::viewing();
+                    self.model.world.handle_mouseover(ctx);
+                }
+            }
+            State::ResizingBuilding(id, ref mut wizard) => {
+                let mut wiz = wizard.wrap(ctx);
+                let mut done = false;
+                if let Some(width) = wiz.input_usize("Width in meters?") {
+                    if let Some(height) = wiz.input_usize("Height in meters?") {
+                        if width > 0 && height > 0 {
+                            self.model.resize_b(
+                                id,
+                                Distance::meters(width as f64),
+                                Distance::meters(height as f64),
+                                ctx.prerender,
+                            );
+                        }
+                        done = true;
+                    }
+                }
+                if done || wizard.aborted() {
+                    self.state = State::viewing();
+                    self.model.world.handle_mouseover(ctx);
+                }
+            }
+            State::EditingDirectionalSpeed(id, ref mut wizard) => {
+                let mut wiz = wizard.wrap(ctx);
+                let mut done = false;
+                if let Some(dir) =
+                    wiz.choose_string("Which direction?", || vec!["forwards", "backwards"])
+                {
+                    let fwd = dir == "forwards";
+                    let orig_speed = self
+                        .model
+                        .get_r_directional_speed(id, fwd)
+                        .unwrap_or_else(String::new);
+                    if let Some(speed) = wiz.input_string_prefilled("What speed limit?", orig_speed)
+                    {
+                        self.model
+                            .set_r_directional_speed(id, fwd, speed, ctx.prerender);
+                        done = true;
+                    }
+                }
+                if done || wizard.aborted() {
+                    self.state = State::viewing();
+                    self.model.world.handle_mouseover(ctx);
+                }
+            }
+            State::EditingLaneSpeed(id, ref mut wizard) => {
+                let lanes: Vec<(bool, usize, LaneType)> = {
+                    let spec = self.model.map.roads[&id].get_spec();
+                    let mut lanes = Vec::new();
+                    for (idx, lt) in spec.fwd.into_iter().enumerate() {
+                        lanes.push((true, idx, lt));
+                    }
+                    for (idx, lt) in spec.back.into_iter().enumerate() {
+                        lanes.push((false, idx, lt));
+                    }
+                    lanes
+                };
+
+                let mut wiz = wizard.wrap(ctx);
+                let mut done = false;
+                if let Some((_, choice)) = wiz.choose("Which lane?", || {
+                    lanes
+                        .iter()
+                        .enumerate()
+                        .map(|(choice, (fwd, idx, lt))| {
+                            Choice::new(
+                                format!(
+                                    "{} {} lane #{}",
+                                    if *fwd { "forwards" } else { "backwards" },
+                                    lt.describe(),
+                                    idx
+                                ),
+                                choice,
+                            )
+                        })
+                        .collect()
+                }) {
+                    let (fwd, idx, _) = lanes[choice];
+                    if let Some(speed) = wiz.input_string_prefilled(
+                        "What speed limit?",
+                        self.model
+                            .get_lane_speed(id, fwd, idx)
+                            .unwrap_or_else(String::new),
+                    ) {
+                        self.model
+                            .set_lane_speed(id, fwd, idx, speed, ctx.prerender);
+                        done = true;
+                    }
+                }
+                if done || wizard.aborted() {
+                    self.state = State::viewing();
+                    self.model.world.handle_mouseover(ctx);
+                }
+            }
             State::SavingModel(ref mut wizard) => {
                 if let Some(name) = wizard.wrap(ctx).input_string("Name the synthetic map") {
                     self.model.map.name = name;
@@ -405,6 +752,15 @@ impl GUI for UI {
                     self.state = State::viewing();
                 }
             }
+            State::ImportingGeoJSON(ref mut wizard) => {
+                if let Some(path) = wizard.wrap(ctx).input_string("Path to the GeoJSON file") {
+                    self.model.import_geojson(&path, ctx.prerender);
+                    self.state = State::viewing();
+                    self.model.world.handle_mouseover(ctx);
+                } else if wizard.aborted() {
+                    self.state = State::viewing();
+                }
+            }
             State::SelectingRectangle(pt1, ref mut pt2, ref mut keydown) => {
                 if ctx.input.key_pressed(Key::LeftShift, "select area") {
                     *keydown = true;
@@ -428,6 +784,46 @@ impl GUI for UI {
                         self.model.world.handle_mouseover(ctx);
                     }
                     self.state = State::viewing();
+                } else if ctx
+                    .input
+                    .key_pressed(Key::S, "set speed limit of roads in area")
+                {
+                    if let Some(rect) = Polygon::rectangle_two_corners(pt1, *pt2) {
+                        self.state = State::SettingSpeedInArea(rect, Wizard::new());
+                    } else {
+                        self.state = State::viewing();
+                    }
+                }
+            }
+            State::SelectingLasso(ref mut pts) => {
+                if ctx.input.key_pressed(Key::Escape, "stop drawing lasso") {
+                    self.state = State::viewing();
+                } else if ctx
+                    .input
+                    .key_pressed(Key::Backspace, "delete everything in lasso")
+                {
+                    let pts = pts.clone();
+                    if self.model.delete_everything_in_lasso(pts) {
+                        self.model.world.handle_mouseover(ctx);
+                    }
+                    self.state = State::viewing();
+                } else if cursor.is_some() && ctx.input.key_pressed(Key::Space, "add a point") {
+                    pts.push(cursor.unwrap());
+                }
+            }
+            State::SettingSpeedInArea(ref area, ref mut wizard) => {
+                if let Some(speed) = wizard
+                    .wrap(ctx)
+                    .input_string_prefilled("What speed limit?", "20 mph".to_string())
+                {
+                    let count = self
+                        .model
+                        .set_speed_in_area(area.clone(), speed, ctx.prerender);
+                    println!("Changed the speed limit of {} roads", count);
+                    self.state = State::viewing();
+                    self.model.world.handle_mouseover(ctx);
+                } else if wizard.aborted() {
+                    self.state = State::viewing();
                 }
             }
             State::CreatingTurnRestrictionPt1(from) => {
@@ -497,16 +893,50 @@ impl GUI for UI {
                             ok = true;
                         }
                     }
+                    self.state = State::viewing();
                     if !ok {
-                        println!("Sorry, don't understand {}", line);
+                        let roads = self.model.find_roads_by_name(&line);
+                        if roads.is_empty() {
+                            println!("Sorry, don't understand {}", line);
+                        } else {
+                            ctx.canvas
+                                .center_on_map_pt(self.model.get_r_center(roads[0]));
+                            self.state = State::BrowsingRoads(roads, 0);
+                        }
                     }
-                    self.state = State::viewing();
                     self.model.world.handle_mouseover(ctx);
                 } else if wizard.aborted() {
                     self.state = State::viewing();
                     self.model.world.handle_mouseover(ctx);
                 }
             }
+            State::BrowsingRoads(ref roads, ref mut idx) => {
+                if ctx.input.key_pressed(Key::Escape, "stop browsing roads") {
+                    self.state = State::viewing();
+                } else if ctx
+                    .input
+                    .key_pressed(Key::Tab, &format!("next of {} matching roads", roads.len()))
+                {
+                    *idx = (*idx + 1) % roads.len();
+                    ctx.canvas
+                        .center_on_map_pt(self.model.get_r_center(roads[*idx]));
+                    self.model.world.handle_mouseover(ctx);
+                }
+            }
+            State::StampingLanes(ref spec) => {
+                if ctx.input.key_pressed(Key::Escape, "stop copying lanes") {
+                    self.state = State::viewing();
+                    self.model.world.handle_mouseover(ctx);
+                } else if let Some(ID::Road(id)) = self.model.world.get_selection() {
+                    if ctx
+                        .input
+                        .key_pressed(Key::Y, &format!("set lanes={}", spec))
+                    {
+                        self.model
+                            .paste_road_config(id, spec.clone(), None, ctx.prerender);
+                    }
+                }
+            }
             State::StampingRoads(ref lanespec, ref name, ref speed, ref highway) => {
                 if ctx
                     .input
@@ -566,6 +996,11 @@ impl GUI for UI {
             }),
             _ => self.model.world.draw(g, |_| true),
         }
+        for id in self.model.get_selection() {
+            if let Some(p) = self.model.world.get_unioned_polygon(*id) {
+                g.draw_polygon(Color::CYAN.alpha(0.5), &p);
+            }
+        }
 
         match self.state {
             State::CreatingRoad(i1) => {
@@ -579,7 +1014,13 @@ impl GUI for UI {
             }
             State::EditingLanes(_, ref wizard)
             | State::EditingRoadAttribs(_, ref wizard)
+            | State::EditingLaneSpeed(_, ref wizard)
+            | State::EditingDirectionalSpeed(_, ref wizard)
+            | State::ResizingBuilding(_, ref wizard)
+            | State::EditingBuildingParking(_, ref wizard)
+            | State::SettingSpeedInArea(_, ref wizard)
             | State::SavingModel(ref wizard)
+            | State::ImportingGeoJSON(ref wizard)
             | State::EnteringWarp(ref wizard) => {
                 wizard.draw(g);
             }
@@ -589,14 +1030,78 @@ impl GUI for UI {
                         g.draw_polygon(Color::CYAN, p);
                     }
                 }
+                // Highlight the rest of the street along with the hovered road
+                if let Some(ID::Road(hovered)) = self.model.world.get_selection() {
+                    for r in self.model.street_group_members(hovered) {
+                        if r == hovered {
+                            continue;
+                        }
+                        if let Some(p) = self.model.world.get_unioned_polygon(ID::Road(r)) {
+                            g.draw_polygon(Color::CYAN.alpha(0.5), p);
+                        }
+                    }
+                }
             }
             State::MovingIntersection(_)
             | State::MovingBuilding(_)
             | State::MovingRoadPoint(_, _)
-            | State::StampingRoads(_, _, _, _) => {}
+            | State::MovingSelection(_)
+            | State::StampingRoads(_, _, _, _)
+            | State::StampingLanes(_) => {}
+            State::BrowsingRoads(ref roads, idx) => {
+                if let Some(p) = self.model.world.get_unioned_polygon(ID::Road(roads[idx])) {
+                    g.draw_polygon(Color::CYAN.alpha(0.5), p);
+                }
+            }
+            State::Measuring(ref pts) => {
+                let mut pts = pts.clone();
+                if let Some(cursor) = g.get_cursor_in_map_space() {
+                    pts.push(cursor);
+                }
+                for pair in pts.windows(2) {
+                    if let Some(l) = Line::maybe_new(pair[0], pair[1]) {
+                        g.draw_line(Color::RED, Distance::meters(1.0), &l);
+                    }
+                }
+                if pts.len() >= 2 {
+                    g.draw_mouse_tooltip(Text::from(Line(
+                        self.model.measure_path(&pts).to_string(),
+                    )));
+                }
+            }
+            State::DrawingBuilding(ref pts) => {
+                let mut pts = pts.clone();
+                if let Some(cursor) = g.get_cursor_in_map_space() {
+                    pts.push(cursor);
+                }
+                for pair in pts.windows(2) {
+                    if let Some(l) = Line::maybe_new(pair[0], pair[1]) {
+                        g.draw_line(Color::BLUE, Distance::meters(1.0), &l);
+                    }
+                }
+            }
+            State::SelectingLasso(ref pts) => {
+                let mut pts = pts.clone();
+                if let Some(cursor) = g.get_cursor_in_map_space() {
+                    pts.push(cursor);
+                }
+                // Show how the lasso will close
+                if pts.len() >= 3 {
+                    pts.push(pts[0]);
+                }
+                if pts.len() >= 4 {
+                    self.draw_delete_preview(g, &Polygon::new(&pts));
+                }
+                for pair in pts.windows(2) {
+                    if let Some(l) = Line::maybe_new(pair[0], pair[1]) {
+                        g.draw_line(Color::RED, Distance::meters(1.0), &l);
+                    }
+                }
+            }
             State::SelectingRectangle(pt1, pt2, _) => {
                 if let Some(rect) = Polygon::rectangle_two_corners(pt1, pt2) {
                     g.draw_polygon(Color::BLUE.alpha(0.5), &rect);
+                    self.draw_delete_preview(g, &rect);
                 }
             }
             State::CreatingTurnRestrictionPt1(from) => {
@@ -626,6 +1131,10 @@ impl GUI for UI {
             }
         };
 
+        if self.show_scale_bar {
+            self.draw_scale_bar(g);
+        }
+
         self.composite.draw(g);
         if let Some(ref popup) = self.popup {
             g.redraw_at(ScreenPt::new(0.0, 0.0), popup);
@@ -668,6 +1177,26 @@ fn preview_intersection(i: OriginalIntersection, model: &Model, ctx: &EventCtx)
     batch.upload(ctx)
 }
 
+fn preview_turns(i: OriginalIntersection, model: &Model, ctx: &EventCtx) -> Drawable {
+    let center = model.map.intersections[&i].point;
+    let mut batch = GeomBatch::new();
+    for (from, to) in model.turns_at(i, false) {
+        let pts = vec![
+            model.get_r_pt_near(from, i),
+            center,
+            model.get_r_pt_near(to, i),
+        ];
+        if let Some(pl) = PolyLine::maybe_new(pts) {
+            batch.push(
+                Color::PURPLE.alpha(0.8),
+                pl.make_arrow(NORMAL_LANE_THICKNESS / 2.0, ArrowCap::Triangle)
+                    .unwrap(),
+            );
+        }
+    }
+    batch.upload(ctx)
+}
+
 fn preview_all_intersections(model: &Model, ctx: &EventCtx) -> Drawable {
     let mut batch = GeomBatch::new();
     let mut timer = Timer::new("preview all intersections");
@@ -718,6 +1247,70 @@ fn find_overlapping_intersections(model: &Model, ctx: &EventCtx) -> Drawable {
     batch.upload(ctx)
 }
 
+fn find_roads_crossing_buildings(model: &Model, ctx: &EventCtx) -> Drawable {
+    let mut batch = GeomBatch::new();
+    for (r, b) in model.roads_crossing_buildings() {
+        println!("WARNING: {} crosses {}", r, b);
+        batch.push(
+            Color::RED.alpha(0.5),
+            model.map.buildings[&b].polygon.clone(),
+        );
+    }
+    batch.upload(ctx)
+}
+
+fn find_overlapping_roads(model: &Model, ctx: &EventCtx) -> Drawable {
+    let mut batch = GeomBatch::new();
+    for (r1, r2) in model.find_overlapping_roads() {
+        println!("WARNING: {} overlaps {}", r1, r2);
+        for r in vec![r1, r2] {
+            if let Some(p) = model.world.get_unioned_polygon(ID::Road(r)) {
+                batch.push(Color::PURPLE.alpha(0.5), p.clone());
+            }
+        }
+    }
+    batch.upload(ctx)
+}
+
+fn find_disconnected_roads(model: &Model, ctx: &EventCtx) -> Drawable {
+    let mut batch = GeomBatch::new();
+    for component in model.find_disconnected_components() {
+        println!("WARNING: {} roads are disconnected", component.len());
+        for r in component {
+            if let Some(p) = model.world.get_unioned_polygon(ID::Road(r)) {
+                batch.push(Color::RED.alpha(0.5), p.clone());
+            }
+        }
+    }
+    for i in model.find_dead_ends() {
+        println!("WARNING: {} is a dead end", i);
+        batch.push(
+            Color::ORANGE.alpha(0.5),
+            Circle::new(model.map.intersections[&i].point, Distance::meters(10.0)).to_polygon(),
+        );
+    }
+    batch.upload(ctx)
+}
+
+fn find_unreachable_intersections(
+    from: OriginalIntersection,
+    model: &Model,
+    ctx: &EventCtx,
+) -> Drawable {
+    let reachable = model.reachable_from(from);
+    let mut batch = GeomBatch::new();
+    for (id, i) in &model.map.intersections {
+        if !reachable.contains(id) {
+            println!("WARNING: can't drive from {} to {}", from, id);
+            batch.push(
+                Color::RED.alpha(0.5),
+                Circle::new(i.point, Distance::meters(10.0)).to_polygon(),
+            );
+        }
+    }
+    batch.upload(ctx)
+}
+
 // TODO OriginalRoad is dangerous, as this map changes. :\
 fn find_short_roads(model: &Model) -> HashSet<OriginalRoad> {
     // Assume the full map has been built. We really care about short lanes there.
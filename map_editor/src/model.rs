@@ -1,7 +1,10 @@
 use crate::world::{Object, ObjectID, World};
 use abstutil::Timer;
 use ezgui::{Color, Line, Prerender, Text};
-use geom::{ArrowCap, Bounds, Circle, Distance, FindClosest, GPSBounds, PolyLine, Polygon, Pt2D};
+use geom::{
+    Angle, ArrowCap, Bounds, Circle, Distance, FindClosest, GPSBounds, LonLat, PolyLine, Polygon,
+    Pt2D,
+};
 use map_model::raw::{
     OriginalBuilding, OriginalIntersection, OriginalRoad, RawBuilding, RawIntersection, RawMap,
     RawRoad, RestrictionType, TurnRestriction,
@@ -15,6 +18,10 @@ use std::mem;
 const INTERSECTION_RADIUS: Distance = Distance::const_meters(5.0);
 const BUILDING_LENGTH: Distance = Distance::const_meters(30.0);
 const CENTER_LINE_THICKNESS: Distance = Distance::const_meters(0.5);
+// Road points any closer than this make lane geometry blow up when it's shifted.
+pub const MIN_ROAD_PT_SPACING: Distance = Distance::const_meters(1.0);
+// Roads sharing less area than this (in square meters) are just touching, not overlapping.
+const MIN_OVERLAP_AREA: f64 = 10.0;
 
 pub struct Model {
     // map and world are pub. The main crate should use them directly for simple stuff, to avoid
@@ -22,9 +29,19 @@ pub struct Model {
     pub map: RawMap,
     showing_pts: Option<OriginalRoad>,
     pub world: World<ID>,
+    // Intersections and buildings to move together
+    selection: BTreeSet<ID>,
 
     include_bldgs: bool,
     pub intersection_geom: bool,
+    // If set, intersections are placed on a grid of this size
+    grid_snap: Option<Distance>,
+    // Color lanes by their speed limit instead of their type
+    speed_colors: bool,
+    // Color intersections by how many roads they have instead of their type
+    degree_colors: bool,
+    // Lanes for newly created roads
+    default_road_spec: RoadSpec,
 }
 
 // Construction
@@ -36,7 +53,15 @@ impl Model {
 
             include_bldgs: false,
             world: World::new(),
+            selection: BTreeSet::new(),
             intersection_geom: false,
+            grid_snap: None,
+            speed_colors: false,
+            degree_colors: false,
+            default_road_spec: RoadSpec {
+                fwd: vec![LaneType::Driving, LaneType::Parking, LaneType::Sidewalk],
+                back: vec![LaneType::Driving, LaneType::Parking, LaneType::Sidewalk],
+            },
         }
     }
 
@@ -122,6 +147,191 @@ impl Model {
         abstutil::write_json(abstutil::path_synthetic_map(&self.map.name), &self.map);
     }
 
+    // Renumber everything contiguously from 0. This throws away ID stability, so only do it right
+    // before a final export.
+    pub fn compact_ids(&mut self, prerender: &Prerender) {
+        self.showing_pts = None;
+        self.selection.clear();
+        self.map.compact_ids();
+
+        self.world = World::new();
+        if self.include_bldgs {
+            for id in self.map.buildings.keys().cloned().collect::<Vec<_>>() {
+                self.bldg_added(id, prerender);
+            }
+        }
+        for id in self.map.intersections.keys().cloned().collect::<Vec<_>>() {
+            self.intersection_added(id, prerender);
+        }
+        for id in self.map.roads.keys().cloned().collect::<Vec<_>>() {
+            self.road_added(id, prerender);
+        }
+    }
+
+    // For inspecting the map in other tools. Doesn't change anything.
+    pub fn export_geojson(&self, path: &str) {
+        let gps = |pt: &Pt2D| {
+            let gps = pt.forcibly_to_gps(&self.map.gps_bounds);
+            vec![gps.x(), gps.y()]
+        };
+
+        let mut features = Vec::new();
+        for (id, r) in &self.map.roads {
+            features.push(serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "LineString",
+                    "coordinates": r.center_points.iter().map(gps).collect::<Vec<_>>(),
+                },
+                "properties": {
+                    "id": id.to_string(),
+                    "lanes": r.get_spec().to_string(),
+                    "osm_tags": r.osm_tags,
+                },
+            }));
+        }
+        for (id, i) in &self.map.intersections {
+            features.push(serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": gps(&i.point),
+                },
+                "properties": {
+                    "id": id.to_string(),
+                    "intersection_type": format!("{:?}", i.intersection_type),
+                },
+            }));
+        }
+        for (id, b) in &self.map.buildings {
+            let mut ring = b.polygon.points().iter().map(gps).collect::<Vec<_>>();
+            // GeoJSON rings have to be closed.
+            if b.polygon.points()[0] != *b.polygon.points().last().unwrap() {
+                ring.push(ring[0].clone());
+            }
+            features.push(serde_json::json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": vec![ring],
+                },
+                "properties": {
+                    "id": id.to_string(),
+                    "osm_tags": b.osm_tags,
+                },
+            }));
+        }
+
+        let collection = serde_json::json!({
+            "type": "FeatureCollection",
+            "features": features,
+        });
+        match std::fs::write(path, abstutil::to_json(&collection)) {
+            Ok(()) => println!("Wrote {}", path),
+            Err(err) => println!("Couldn't write {}: {}", path, err),
+        }
+    }
+
+    // For round-tripping into OSM tools. Road interior points and building corners become new
+    // nodes. They, and any ways that'd otherwise share an ID, get negative IDs, the same way OSM
+    // editors mark features that haven't been uploaded yet.
+    pub fn export_osm_xml(&self, path: &str) {
+        let mut nodes: Vec<(i64, Pt2D)> = self
+            .map
+            .intersections
+            .iter()
+            .map(|(id, i)| (id.osm_node_id, i.point))
+            .collect();
+        let mut next_node_id = nodes.iter().map(|(id, _)| *id).min().unwrap_or(0).min(0) - 1;
+        let mut next_way_id = self
+            .map
+            .roads
+            .keys()
+            .map(|r| r.osm_way_id)
+            .chain(self.map.buildings.keys().map(|b| b.osm_way_id))
+            .min()
+            .unwrap_or(0)
+            .min(0)
+            - 1;
+        let mut used_way_ids = BTreeSet::new();
+        let mut ways: Vec<(i64, Vec<i64>, &BTreeMap<String, String>)> = Vec::new();
+
+        for (id, r) in &self.map.roads {
+            let mut refs = vec![id.i1.osm_node_id];
+            for pt in &r.center_points[1..r.center_points.len() - 1] {
+                nodes.push((next_node_id, *pt));
+                refs.push(next_node_id);
+                next_node_id -= 1;
+            }
+            refs.push(id.i2.osm_node_id);
+            // Roads split from the same OSM way need their own IDs
+            let way_id = if used_way_ids.insert(id.osm_way_id) {
+                id.osm_way_id
+            } else {
+                let synthetic = next_way_id;
+                next_way_id -= 1;
+                synthetic
+            };
+            ways.push((way_id, refs, &r.osm_tags));
+        }
+        for (id, b) in &self.map.buildings {
+            let mut pts = b.polygon.points().clone();
+            if pts[0] == *pts.last().unwrap() {
+                pts.pop();
+            }
+            let mut refs = Vec::new();
+            for pt in pts {
+                nodes.push((next_node_id, pt));
+                refs.push(next_node_id);
+                next_node_id -= 1;
+            }
+            // OSM closes areas by repeating the first node
+            refs.push(refs[0]);
+            let way_id = if used_way_ids.insert(id.osm_way_id) {
+                id.osm_way_id
+            } else {
+                let synthetic = next_way_id;
+                next_way_id -= 1;
+                synthetic
+            };
+            ways.push((way_id, refs, &b.osm_tags));
+        }
+
+        let mut lines = vec![
+            "<?xml version='1.0' encoding='UTF-8'?>".to_string(),
+            "<osm version=\"0.6\" generator=\"abstreet map_editor\">".to_string(),
+        ];
+        for (id, pt) in nodes {
+            let gps = pt.forcibly_to_gps(&self.map.gps_bounds);
+            lines.push(format!(
+                "  <node id=\"{}\" lat=\"{}\" lon=\"{}\"/>",
+                id,
+                gps.y(),
+                gps.x()
+            ));
+        }
+        for (id, refs, tags) in ways {
+            lines.push(format!("  <way id=\"{}\">", id));
+            for node in refs {
+                lines.push(format!("    <nd ref=\"{}\"/>", node));
+            }
+            for (k, v) in tags {
+                lines.push(format!(
+                    "    <tag k=\"{}\" v=\"{}\"/>",
+                    escape_xml(k),
+                    escape_xml(v)
+                ));
+            }
+            lines.push("  </way>".to_string());
+        }
+        lines.push("</osm>".to_string());
+
+        match std::fs::write(path, lines.join("\n")) {
+            Ok(()) => println!("Wrote {}", path),
+            Err(err) => println!("Couldn't write {}: {}", path, err),
+        }
+    }
+
     fn compute_bounds(&self) -> Bounds {
         let mut bounds = Bounds::new();
         for b in self.map.buildings.values() {
@@ -140,30 +350,384 @@ impl Model {
         bounds
     }
 
+    // Everything that delete_everything_inside would remove, without changing anything.
+    // Intersections with a road leading outside the area are kept.
+    pub fn preview_delete_inside(
+        &self,
+        area: &Polygon,
+    ) -> (
+        Vec<OriginalRoad>,
+        Vec<OriginalIntersection>,
+        Vec<OriginalBuilding>,
+    ) {
+        let roads: BTreeSet<OriginalRoad> = self
+            .map
+            .roads
+            .iter()
+            .filter(|(_, r)| r.center_points.iter().any(|pt| area.contains_pt(*pt)))
+            .map(|(id, _)| *id)
+            .collect();
+        let intersections = self
+            .map
+            .intersections
+            .iter()
+            .filter(|(id, i)| {
+                area.contains_pt(i.point)
+                    && self
+                        .map
+                        .roads_per_intersection(**id)
+                        .iter()
+                        .all(|r| roads.contains(r))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        let bldgs = if self.include_bldgs {
+            self.map
+                .buildings
+                .iter()
+                .filter(|(_, b)| area.contains_pt(b.polygon.center()))
+                .map(|(id, _)| *id)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        (roads.into_iter().collect(), intersections, bldgs)
+    }
+
     pub fn delete_everything_inside(&mut self, area: Polygon) {
-        if self.include_bldgs {
-            for id in self.map.buildings.keys().cloned().collect::<Vec<_>>() {
-                if area.contains_pt(self.map.buildings[&id].polygon.center()) {
-                    self.delete_b(id);
-                }
+        let (roads, intersections, bldgs) = self.preview_delete_inside(&area);
+        for id in bldgs {
+            self.delete_b(id);
+        }
+        for id in roads {
+            self.delete_r(id);
+        }
+        for id in intersections {
+            self.delete_i(id);
+        }
+    }
+
+    // Like delete_everything_inside, but for a freeform area drawn point by point. Returns false
+    // if the points don't form a valid area.
+    pub fn delete_everything_in_lasso(&mut self, pts: Vec<Pt2D>) -> bool {
+        match simple_polygon(pts) {
+            Ok(area) => {
+                self.delete_everything_inside(area);
+                true
+            }
+            Err(err) => {
+                println!("The lasso {}", err);
+                false
             }
         }
+    }
+
+    // Sets the speed limit of every road with a point inside the area. Returns how many roads
+    // changed.
+    pub fn set_speed_in_area(
+        &mut self,
+        area: Polygon,
+        speed: String,
+        prerender: &Prerender,
+    ) -> usize {
+        if osm::parse_maxspeed(&speed).is_none() {
+            println!("Bad speed {}; use something like \"25 mph\"", speed);
+            return 0;
+        }
 
+        let mut count = 0;
         for id in self.map.roads.keys().cloned().collect::<Vec<_>>() {
             if self.map.roads[&id]
                 .center_points
                 .iter()
                 .any(|pt| area.contains_pt(*pt))
             {
-                self.delete_r(id);
+                self.road_deleted(id);
+                self.map
+                    .roads
+                    .get_mut(&id)
+                    .unwrap()
+                    .osm_tags
+                    .insert(osm::MAXSPEED.to_string(), speed.clone());
+                self.road_added(id, prerender);
+                count += 1;
             }
         }
+        count
+    }
 
-        for id in self.map.intersections.keys().cloned().collect::<Vec<_>>() {
-            if area.contains_pt(self.map.intersections[&id].point) {
-                self.delete_i(id);
+    // Roads whose first or last point doesn't match the intersection it's connected to
+    pub fn validate(&self) -> Vec<OriginalRoad> {
+        let threshold = Distance::meters(0.01);
+        self.map
+            .roads
+            .iter()
+            .filter(|(id, r)| {
+                !r.center_points[0].approx_eq(self.map.intersections[&id.i1].point, threshold)
+                    || !r
+                        .center_points
+                        .last()
+                        .unwrap()
+                        .approx_eq(self.map.intersections[&id.i2].point, threshold)
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // Snaps every road found by validate back to its intersections. Returns the number of roads
+    // fixed. Roads are identified by their intersections, so no IDs change.
+    pub fn repair_endpoints(&mut self, prerender: &Prerender) -> usize {
+        let broken = self.validate();
+        let mut intersections = BTreeSet::new();
+        for id in &broken {
+            self.road_deleted(*id);
+            intersections.insert(id.i1);
+            intersections.insert(id.i2);
+
+            let pt1 = self.map.intersections[&id.i1].point;
+            let pt2 = self.map.intersections[&id.i2].point;
+            let pts = &mut self.map.roads.get_mut(id).unwrap().center_points;
+            pts[0] = pt1;
+            *pts.last_mut().unwrap() = pt2;
+
+            self.road_added(*id, prerender);
+        }
+        // Intersection geometry depends on the roads
+        for i in intersections {
+            self.world.delete(ID::Intersection(i));
+            self.intersection_added(i, prerender);
+        }
+        broken.len()
+    }
+
+    pub fn measure(&self, a: Pt2D, b: Pt2D) -> Distance {
+        a.dist_to(b)
+    }
+
+    // The total length of a path through all of the points
+    pub fn measure_path(&self, pts: &Vec<Pt2D>) -> Distance {
+        pts.windows(2)
+            .map(|pair| self.measure(pair[0], pair[1]))
+            .sum()
+    }
+
+    // For a scale bar about target_pixels long at the given zoom, returns a round distance (1, 2,
+    // or 5 times a power of 10) that's no longer than that, and a label for it.
+    pub fn scale_bar_length(&self, target_pixels: f64, zoom: f64) -> (Distance, String) {
+        // Map-space is in meters, so this is how many meters fit in the target
+        let max_meters = target_pixels / zoom;
+        let magnitude = 10.0_f64.powi(max_meters.log10().floor() as i32);
+        let meters = vec![5.0, 2.0, 1.0]
+            .into_iter()
+            .map(|x| x * magnitude)
+            .find(|m| *m <= max_meters)
+            .unwrap_or(magnitude);
+
+        let label = if meters >= 1000.0 {
+            format!("{}km", meters / 1000.0)
+        } else if meters >= 1.0 {
+            format!("{}m", meters)
+        } else {
+            format!("{}cm", (meters * 100.0).round())
+        };
+        (Distance::meters(meters), label)
+    }
+
+    // The closest intersection, building center, or point along a road center-line within
+    // max_dist of pt.
+    pub fn nearest_object(&self, pt: Pt2D, max_dist: Distance) -> Option<(ID, Distance)> {
+        // Cheaply skip anything whose bounding box isn't close enough
+        let near = |bounds: Bounds| {
+            pt.x() >= bounds.min_x - max_dist.inner_meters()
+                && pt.x() <= bounds.max_x + max_dist.inner_meters()
+                && pt.y() >= bounds.min_y - max_dist.inner_meters()
+                && pt.y() <= bounds.max_y + max_dist.inner_meters()
+        };
+
+        let mut candidates: Vec<(ID, Distance)> = Vec::new();
+        for (id, i) in &self.map.intersections {
+            candidates.push((ID::Intersection(*id), i.point.dist_to(pt)));
+        }
+        if self.include_bldgs {
+            for (id, b) in &self.map.buildings {
+                if near(b.polygon.get_bounds()) {
+                    candidates.push((ID::Building(*id), b.polygon.center().dist_to(pt)));
+                }
+            }
+        }
+        for (id, r) in &self.map.roads {
+            if !near(Bounds::from(&r.center_points)) {
+                continue;
+            }
+            for pair in r.center_points.windows(2) {
+                if let Some(line) = geom::Line::maybe_new(pair[0], pair[1]) {
+                    candidates.push((ID::Road(*id), line.project_pt(pt).dist_to(pt)));
+                }
+            }
+        }
+
+        candidates
+            .into_iter()
+            .filter(|(_, dist)| *dist <= max_dist)
+            .min_by_key(|(_, dist)| *dist)
+    }
+
+    // Roads with any lane overlapping a building. Nothing is changed; this is just to catch
+    // mistakes before exporting.
+    pub fn roads_crossing_buildings(&self) -> Vec<(OriginalRoad, OriginalBuilding)> {
+        let mut results = Vec::new();
+        for id in self.map.roads.keys() {
+            let lanes = self.lane_polygons(*id);
+            let mut bounds = Bounds::new();
+            for (_, p) in &lanes {
+                bounds.union(p.get_bounds());
+            }
+
+            for (b_id, b) in &self.map.buildings {
+                let b_bounds = b.polygon.get_bounds();
+                if b_bounds.max_x < bounds.min_x
+                    || b_bounds.min_x > bounds.max_x
+                    || b_bounds.max_y < bounds.min_y
+                    || b_bounds.min_y > bounds.max_y
+                {
+                    continue;
+                }
+                if lanes
+                    .iter()
+                    .any(|(_, p)| !p.intersection(&b.polygon).is_empty())
+                {
+                    results.push((*id, *b_id));
+                }
+            }
+        }
+        results
+    }
+
+    // Pairs of roads not meeting at an intersection whose lanes overlap substantially. These
+    // usually come from importing or duplicating roads and break the simulation.
+    pub fn find_overlapping_roads(&self) -> Vec<(OriginalRoad, OriginalRoad)> {
+        let all: Vec<(OriginalRoad, Vec<Polygon>, Bounds)> = self
+            .map
+            .roads
+            .keys()
+            .map(|id| {
+                let polygons: Vec<Polygon> = self
+                    .lane_polygons(*id)
+                    .into_iter()
+                    .map(|(_, p)| p)
+                    .collect();
+                let mut bounds = Bounds::new();
+                for p in &polygons {
+                    bounds.union(p.get_bounds());
+                }
+                (*id, polygons, bounds)
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        for (idx, (r1, polys1, b1)) in all.iter().enumerate() {
+            for (r2, polys2, b2) in &all[idx + 1..] {
+                if r1.i1 == r2.i1 || r1.i1 == r2.i2 || r1.i2 == r2.i1 || r1.i2 == r2.i2 {
+                    continue;
+                }
+                if b1.max_x < b2.min_x
+                    || b1.min_x > b2.max_x
+                    || b1.max_y < b2.min_y
+                    || b1.min_y > b2.max_y
+                {
+                    continue;
+                }
+                let mut area = 0.0;
+                for p1 in polys1 {
+                    for p2 in polys2 {
+                        for p in p1.intersection(p2) {
+                            area += p.area();
+                        }
+                    }
+                }
+                if area >= MIN_OVERLAP_AREA {
+                    results.push((*r1, *r2));
+                }
+            }
+        }
+        results
+    }
+
+    // Where a car starting at this intersection could get to, respecting one-way roads.
+    pub fn reachable_from(&self, start: OriginalIntersection) -> BTreeSet<OriginalIntersection> {
+        self.map.reachable_from(start)
+    }
+
+    // Groups of roads that can't be reached from the largest group
+    pub fn find_disconnected_components(&self) -> Vec<BTreeSet<OriginalRoad>> {
+        self.map.disconnected_components()
+    }
+
+    pub fn find_dead_ends(&self) -> Vec<OriginalIntersection> {
+        self.map.dead_ends()
+    }
+
+    pub fn intersections_of_type(&self, t: IntersectionType) -> Vec<OriginalIntersection> {
+        self.map
+            .intersections
+            .iter()
+            .filter(|(_, i)| i.intersection_type == t)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // How many intersections there are of each type. Types with none are omitted.
+    pub fn count_intersections_per_type(&self) -> Vec<(IntersectionType, usize)> {
+        let mut counts: Vec<(IntersectionType, usize)> = Vec::new();
+        for i in self.map.intersections.values() {
+            if let Some(pair) = counts.iter_mut().find(|(t, _)| *t == i.intersection_type) {
+                pair.1 += 1;
+            } else {
+                counts.push((i.intersection_type, 1));
+            }
+        }
+        counts
+    }
+
+    // Case-insensitive substring match against road names
+    pub fn find_roads_by_name(&self, query: &str) -> Vec<OriginalRoad> {
+        let query = query.to_lowercase();
+        self.map
+            .roads
+            .iter()
+            .filter(|(_, r)| {
+                r.osm_tags
+                    .get(osm::NAME)
+                    .map(|name| name.to_lowercase().contains(&query))
+                    .unwrap_or(false)
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // Roads with this tag. If value is specified, the tag must also match it.
+    pub fn roads_with_tag(&self, key: &str, value: Option<&str>) -> Vec<OriginalRoad> {
+        self.map
+            .roads
+            .iter()
+            .filter(|(_, r)| match (r.osm_tags.get(key), value) {
+                (Some(v1), Some(v2)) => v1 == v2,
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // How many roads have each value of this tag
+    pub fn tag_value_histogram(&self, key: &str) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for r in self.map.roads.values() {
+            if let Some(v) = r.osm_tags.get(key) {
+                *counts.entry(v.clone()).or_insert(0) += 1;
             }
         }
+        counts
     }
 
     pub fn describe_obj(&self, id: ID) -> Text {
@@ -171,7 +735,19 @@ impl Model {
         match id {
             ID::Building(b) => {
                 txt.add_highlighted(Line(b.to_string()), Color::BLUE);
-                for (k, v) in &self.map.buildings[&b].osm_tags {
+                let bldg = &self.map.buildings[&b];
+                if bldg.num_parking_spots > 0 {
+                    txt.add(Line(format!(
+                        "{} parking spots{}",
+                        bldg.num_parking_spots,
+                        if let Some(ref name) = bldg.public_garage_name {
+                            format!(" in public garage {}", name)
+                        } else {
+                            String::new()
+                        }
+                    )));
+                }
+                for (k, v) in &bldg.osm_tags {
                     txt.add_appended(vec![
                         Line(k).fg(Color::RED),
                         Line(" = "),
@@ -204,6 +780,23 @@ impl Model {
                         Line(v).fg(Color::CYAN),
                     ]);
                 }
+                let spec = road.get_spec();
+                for (fwd, lanes) in vec![(true, &spec.fwd), (false, &spec.back)] {
+                    for (idx, lt) in lanes.iter().enumerate() {
+                        if let Some(speed) = road.osm_tags.get(&osm::lane_maxspeed_key(fwd, idx)) {
+                            txt.add(Line(format!(
+                                "{} {} lane #{} has speed limit {}",
+                                if fwd { "Forwards" } else { "Backwards" },
+                                lt.describe(),
+                                idx,
+                                speed
+                            )));
+                        }
+                    }
+                }
+                if !road.grades.is_empty() {
+                    txt.add(Line(format!("Max grade {:.1}%", road.max_grade())));
+                }
                 for (restriction, dst) in &road.turn_restrictions {
                     txt.add_appended(vec![
                         Line("Restriction: "),
@@ -239,11 +832,24 @@ impl Model {
 impl Model {
     fn intersection_added(&mut self, id: OriginalIntersection, prerender: &Prerender) {
         let i = &self.map.intersections[&id];
-        let color = match i.intersection_type {
-            IntersectionType::TrafficSignal => Color::GREEN,
-            IntersectionType::StopSign => Color::RED,
-            IntersectionType::Border => Color::BLUE,
-            IntersectionType::Construction => Color::ORANGE,
+        let color = if self.degree_colors {
+            // Dead ends that aren't borders will break routing
+            match (
+                i.intersection_type,
+                self.map.roads_per_intersection(id).len(),
+            ) {
+                (IntersectionType::Border, _) => Color::BLUE,
+                (_, 0) | (_, 1) => Color::PURPLE,
+                (_, 2) | (_, 3) | (_, 4) => Color::grey(0.5),
+                _ => Color::ORANGE,
+            }
+        } else {
+            match i.intersection_type {
+                IntersectionType::TrafficSignal => Color::GREEN,
+                IntersectionType::StopSign => Color::RED,
+                IntersectionType::Border => Color::BLUE,
+                IntersectionType::Construction => Color::ORANGE,
+            }
         };
 
         let poly = if self.intersection_geom && !self.map.roads_per_intersection(id).is_empty() {
@@ -257,7 +863,58 @@ impl Model {
             .add(prerender, Object::new(ID::Intersection(id), color, poly));
     }
 
-    pub fn create_i(&mut self, point: Pt2D, prerender: &Prerender) {
+    pub fn toggle_degree_colors(&mut self, prerender: &Prerender) {
+        self.degree_colors = !self.degree_colors;
+        for id in self.map.intersections.keys().cloned().collect::<Vec<_>>() {
+            self.world.delete(ID::Intersection(id));
+            self.intersection_added(id, prerender);
+        }
+    }
+
+    pub fn set_grid_snap(&mut self, grid_snap: Option<Distance>) {
+        self.grid_snap = grid_snap;
+    }
+
+    pub fn get_grid_snap(&self) -> Option<Distance> {
+        self.grid_snap
+    }
+
+    pub fn set_default_road_spec(&mut self, spec: RoadSpec) {
+        self.default_road_spec = spec;
+    }
+
+    pub fn toggle_speed_colors(&mut self, prerender: &Prerender) {
+        self.speed_colors = !self.speed_colors;
+        for id in self.map.roads.keys().cloned().collect::<Vec<_>>() {
+            self.road_deleted(id);
+            self.road_added(id, prerender);
+        }
+    }
+
+    fn snap_to_grid(&self, pt: Pt2D) -> Pt2D {
+        if let Some(size) = self.grid_snap {
+            let size = size.inner_meters();
+            Pt2D::new(
+                (pt.x() / size).round() * size,
+                (pt.y() / size).round() * size,
+            )
+        } else {
+            pt
+        }
+    }
+
+    // Snaps to the grid, so only use this for points the user placed.
+    pub fn create_i(&mut self, point: Pt2D, prerender: &Prerender) -> OriginalIntersection {
+        let point = self.snap_to_grid(point);
+        self.insert_i(point, IntersectionType::StopSign, prerender)
+    }
+
+    fn insert_i(
+        &mut self,
+        point: Pt2D,
+        intersection_type: IntersectionType,
+        prerender: &Prerender,
+    ) -> OriginalIntersection {
         let id = OriginalIntersection {
             osm_node_id: self.map.new_osm_node_id(time_to_id()),
         };
@@ -265,16 +922,95 @@ impl Model {
             id,
             RawIntersection {
                 point,
-                intersection_type: IntersectionType::StopSign,
+                intersection_type,
                 // TODO If this isn't a synthetic map, load the elevation data and grab a real
                 // value.
                 elevation: Distance::ZERO,
             },
         );
         self.intersection_added(id, prerender);
+        id
+    }
+
+    // Creates a lattice of intersections with roads between neighbors. Each intersection on the
+    // edge also gets a short road leading out to a border, so the grid is routable. origin is the
+    // top-left corner, including those border roads.
+    pub fn generate_grid(
+        &mut self,
+        rows: usize,
+        cols: usize,
+        spacing: Distance,
+        origin: Pt2D,
+        prerender: &Prerender,
+    ) {
+        let stub = spacing / 2.0;
+        let pt = |row: f64, col: f64| {
+            Pt2D::new(
+                origin.x() + (stub + spacing * col).inner_meters(),
+                origin.y() + (stub + spacing * row).inner_meters(),
+            )
+        };
+
+        let mut lattice: Vec<Vec<OriginalIntersection>> = Vec::new();
+        for row in 0..rows {
+            let mut ids = Vec::new();
+            for col in 0..cols {
+                ids.push(self.insert_i(
+                    pt(row as f64, col as f64),
+                    IntersectionType::StopSign,
+                    prerender,
+                ));
+            }
+            lattice.push(ids);
+        }
+
+        let mut all = Vec::new();
+        for row in 0..rows {
+            for col in 0..cols {
+                let i = lattice[row][col];
+                all.push(i);
+                if col + 1 < cols {
+                    self.create_r(i, lattice[row][col + 1], prerender);
+                }
+                if row + 1 < rows {
+                    self.create_r(i, lattice[row + 1][col], prerender);
+                }
+
+                // Offsets to a border, in units of rows and columns
+                let mut borders = Vec::new();
+                if row == 0 {
+                    borders.push((-0.5, 0.0));
+                }
+                if row == rows - 1 {
+                    borders.push((0.5, 0.0));
+                }
+                if col == 0 {
+                    borders.push((0.0, -0.5));
+                }
+                if col == cols - 1 {
+                    borders.push((0.0, 0.5));
+                }
+                for (dr, dc) in borders {
+                    let border = self.insert_i(
+                        pt(row as f64 + dr, col as f64 + dc),
+                        IntersectionType::Border,
+                        prerender,
+                    );
+                    self.create_r(i, border, prerender);
+                    all.push(border);
+                }
+            }
+        }
+
+        // Intersection geometry depends on the roads, which didn't exist yet.
+        for i in all {
+            self.world.delete(ID::Intersection(i));
+            self.intersection_added(i, prerender);
+        }
     }
 
     pub fn move_i(&mut self, id: OriginalIntersection, point: Pt2D, prerender: &Prerender) {
+        let point = self.snap_to_grid(point);
         self.world.delete(ID::Intersection(id));
         for r in self.map.move_intersection(id, point).unwrap() {
             self.road_deleted(r);
@@ -313,121 +1049,1050 @@ impl Model {
         }
         self.map.delete_intersection(id);
         self.world.delete(ID::Intersection(id));
+        self.selection.remove(&ID::Intersection(id));
     }
-}
 
-// Roads
-impl Model {
-    fn road_added(&mut self, id: OriginalRoad, prerender: &Prerender) {
-        for obj in self.road_objects(id) {
-            self.world.add(prerender, obj);
+    // Removes an intersection with exactly two roads, joining them into one road between their
+    // far ends. The merged road keeps the first road's tags and direction. Any other intersection
+    // is just deleted like normal, which fails if roads use it.
+    pub fn dissolve_i(&mut self, id: OriginalIntersection, prerender: &Prerender) {
+        let roads = self.map.roads_per_intersection(id);
+        if roads.len() != 2 {
+            self.delete_i(id);
+            return;
         }
-    }
-
-    fn road_deleted(&mut self, id: OriginalRoad) {
-        for obj in self.road_objects(id) {
-            self.world.delete(obj.get_id());
+        let (r1, r2) = (roads[0], roads[1]);
+        let far1 = if r1.i1 == id { r1.i2 } else { r1.i1 };
+        let far2 = if r2.i1 == id { r2.i2 } else { r2.i1 };
+        if far1 == id || far2 == id || far1 == far2 {
+            println!("Can't dissolve {}; its roads form a loop", id);
+            return;
+        }
+        // Keep r1 pointing the same way
+        let new_id = if r1.i2 == id {
+            OriginalRoad {
+                osm_way_id: r1.osm_way_id,
+                i1: far1,
+                i2: far2,
+            }
+        } else {
+            OriginalRoad {
+                osm_way_id: r1.osm_way_id,
+                i1: far2,
+                i2: far1,
+            }
+        };
+        if self.map.roads.contains_key(&new_id) {
+            println!("Can't dissolve {}; {} already exists", id, new_id);
+            return;
         }
-    }
 
-    pub fn create_r(
-        &mut self,
-        i1: OriginalIntersection,
-        i2: OriginalIntersection,
-        prerender: &Prerender,
-    ) {
-        // Ban cul-de-sacs, since they get stripped out later anyway.
-        if self
+        // Other roads with turn restrictions leading to these need to be redrawn.
+        let others: Vec<OriginalRoad> = self
             .map
             .roads
-            .keys()
-            .any(|r| (r.i1 == i1 && r.i2 == i2) || (r.i1 == i2 && r.i2 == i1))
+            .iter()
+            .filter(|(r, road)| {
+                **r != r1
+                    && **r != r2
+                    && road
+                        .turn_restrictions
+                        .iter()
+                        .any(|(_, to)| *to == r1 || *to == r2)
+            })
+            .map(|(r, _)| *r)
+            .collect();
+        for r in vec![r1, r2] {
+            self.stop_showing_pts(r);
+            self.road_deleted(r);
+        }
+        for r in &others {
+            self.road_deleted(*r);
+        }
+        for i in vec![id, far1, far2] {
+            self.world.delete(ID::Intersection(i));
+        }
+        self.selection.remove(&ID::Intersection(id));
+
+        let first = self.map.roads[&r1].clone();
+        let second = self.map.roads[&r2].clone();
+        let mut restrictions = self.map.delete_road(r1);
+        restrictions.extend(self.map.delete_road(r2));
+        self.map.delete_intersection(id);
+
+        // Orient both halves from new_id.i1 to new_id.i2
+        let mut pts1 = first.center_points.clone();
+        if r1.i1 == id {
+            pts1.reverse();
+        }
+        let mut pts2 = second.center_points.clone();
+        if r2.i2 == id {
+            pts2.reverse();
+        }
+        let (mut pts, rest) = if r1.i2 == id {
+            (pts1, pts2)
+        } else {
+            (
+                pts2.into_iter().rev().collect(),
+                pts1.into_iter().rev().collect(),
+            )
+        };
+        pts.pop();
+        pts.extend(rest);
+
+        // Grades flip sign when a road is reversed. If either half is missing elevation data,
+        // so is the merged road.
+        let flip = |grades: &Vec<f64>| grades.iter().rev().map(|g| -g).collect::<Vec<f64>>();
+        let grades = if first.grades.is_empty() || second.grades.is_empty() {
+            Vec::new()
+        } else {
+            let mut grades = if r1.i1 == id {
+                flip(&first.grades)
+            } else {
+                first.grades.clone()
+            };
+            grades.extend(if r2.i2 == id {
+                flip(&second.grades)
+            } else {
+                second.grades.clone()
+            });
+            if r1.i2 == id {
+                grades
+            } else {
+                flip(&grades)
+            }
+        };
+
+        let mut merged = first;
+        merged.center_points = pts;
+        merged.grades = grades;
+        // The far end of the second road decides whether the new end is an original endpoint
+        let (key, second_key) = if r1.i2 == id {
+            (
+                osm::ENDPT_FWD,
+                if r2.i2 == far2 {
+                    osm::ENDPT_FWD
+                } else {
+                    osm::ENDPT_BACK
+                },
+            )
+        } else {
+            (
+                osm::ENDPT_BACK,
+                if r2.i1 == far2 {
+                    osm::ENDPT_BACK
+                } else {
+                    osm::ENDPT_FWD
+                },
+            )
+        };
+        merged.osm_tags.remove(key);
+        if let Some(value) = second.osm_tags.get(second_key) {
+            merged.osm_tags.insert(key.to_string(), value.clone());
+        }
+        // Restored below with the new ID
+        merged.turn_restrictions.clear();
+        self.map.roads.insert(new_id, merged);
+
+        let rename = |r: OriginalRoad| if r == r1 || r == r2 { new_id } else { r };
+        for TurnRestriction(from, restriction, to) in restrictions {
+            let (from, to) = (rename(from), rename(to));
+            if from != to && self.map.roads.contains_key(&from) && self.map.roads.contains_key(&to)
+            {
+                self.map
+                    .roads
+                    .get_mut(&from)
+                    .unwrap()
+                    .turn_restrictions
+                    .push((restriction, to));
+            }
+        }
+        // Restrictions via either half can't be expressed anymore
+        for road in self.map.roads.values_mut() {
+            road.complicated_turn_restrictions
+                .retain(|(via, _)| *via != r1 && *via != r2);
+            for (_, to) in &mut road.complicated_turn_restrictions {
+                *to = rename(*to);
+            }
+        }
+
+        self.road_added(new_id, prerender);
+        for r in others {
+            self.road_added(r, prerender);
+        }
+        for i in vec![far1, far2] {
+            self.intersection_added(i, prerender);
+        }
+    }
+
+    // Every (from, to) pair of roads that a vehicle could plausibly move between at this
+    // intersection, only based on the direction of lanes. U-turns are excluded unless requested.
+    pub fn turns_at(
+        &self,
+        id: OriginalIntersection,
+        include_u_turns: bool,
+    ) -> Vec<(OriginalRoad, OriginalRoad)> {
+        let roads = self.map.roads_per_intersection(id);
+        let mut turns = Vec::new();
+        for from in &roads {
+            if !self.r_has_vehicle_lanes(*from, id, true) {
+                continue;
+            }
+            for to in &roads {
+                if from == to && !include_u_turns {
+                    continue;
+                }
+                if self.r_has_vehicle_lanes(*to, id, false) {
+                    turns.push((*from, *to));
+                }
+            }
+        }
+        turns
+    }
+
+    // Can something travel along this road towards (or away from) the intersection?
+    fn r_has_vehicle_lanes(
+        &self,
+        r: OriginalRoad,
+        i: OriginalIntersection,
+        towards_i: bool,
+    ) -> bool {
+        let spec = self.map.roads[&r].get_spec();
+        // Forwards lanes go from i1 to i2.
+        let lanes = if (r.i2 == i) == towards_i {
+            spec.fwd
+        } else {
+            spec.back
+        };
+        lanes.into_iter().any(|lt| lt.is_for_moving_vehicles())
+    }
+}
+
+// Roads
+impl Model {
+    fn road_added(&mut self, id: OriginalRoad, prerender: &Prerender) {
+        for obj in self.road_objects(id) {
+            self.world.add(prerender, obj);
+        }
+    }
+
+    fn road_deleted(&mut self, id: OriginalRoad) {
+        for obj in self.road_objects(id) {
+            self.world.delete(obj.get_id());
+        }
+    }
+
+    pub fn create_r(
+        &mut self,
+        i1: OriginalIntersection,
+        i2: OriginalIntersection,
+        prerender: &Prerender,
+    ) {
+        // Ban cul-de-sacs, since they get stripped out later anyway.
+        if self
+            .map
+            .roads
+            .keys()
+            .any(|r| (r.i1 == i1 && r.i2 == i2) || (r.i1 == i2 && r.i2 == i1))
         {
             println!("Road already exists");
             return;
         }
+        if self.map.intersections[&i1]
+            .point
+            .dist_to(self.map.intersections[&i2].point)
+            < MIN_ROAD_PT_SPACING
+        {
+            println!("Can't create a road between intersections on top of each other");
+            return;
+        }
 
         let id = OriginalRoad {
             osm_way_id: self.map.new_osm_way_id(time_to_id()),
             i1,
             i2,
         };
-        let mut osm_tags = BTreeMap::new();
+        self.map.roads.insert(
+            id,
+            RawRoad {
+                center_points: vec![
+                    self.map.intersections[&i1].point,
+                    self.map.intersections[&i2].point,
+                ],
+                osm_tags: synthetic_road_tags(id, &self.default_road_spec),
+                turn_restrictions: Vec::new(),
+                complicated_turn_restrictions: Vec::new(),
+                grades: Vec::new(),
+            },
+        );
+        self.road_added(id, prerender);
+    }
+
+    // Roads shorter than min_len, which may crash when their lanes are shifted. The length is
+    // measured directly from the points, since PolyLine would reject the worst cases.
+    pub fn find_degenerate_roads(&self, min_len: Distance) -> Vec<OriginalRoad> {
+        self.map
+            .roads
+            .iter()
+            .filter(|(_, r)| {
+                let len: Distance = r
+                    .center_points
+                    .windows(2)
+                    .map(|pair| pair[0].dist_to(pair[1]))
+                    .sum();
+                len < min_len
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // Splits a road in two at the point on it closest to pt, with a new intersection in between.
+    // Both halves keep the original tags. Returns the new intersection and the second half.
+    pub fn split_r(
+        &mut self,
+        id: OriginalRoad,
+        pt: Pt2D,
+        prerender: &Prerender,
+    ) -> Option<(OriginalIntersection, OriginalRoad)> {
+        self.split_r_at(id, pt, None, prerender)
+    }
+
+    // If an intersection was dropped onto the middle of another road, split that road there and
+    // connect them, forming a T. Returns true if that happened.
+    pub fn snap_i_to_road(&mut self, id: OriginalIntersection, prerender: &Prerender) -> bool {
+        let pt = self.map.intersections[&id].point;
+        let mut closest = FindClosest::new(&self.compute_bounds());
+        for (r, road) in &self.map.roads {
+            // Don't snap onto a road the intersection is already part of
+            if r.i1 != id && r.i2 != id {
+                closest.add(*r, &road.center_points);
+            }
+        }
+        match closest.closest_pt(pt, INTERSECTION_RADIUS) {
+            Some((r, _)) => self.split_r_at(r, pt, Some(id), prerender).is_some(),
+            None => false,
+        }
+    }
+
+    // Like split_r, but if existing is set, that intersection moves to the split point and joins
+    // both halves, instead of creating a new intersection.
+    fn split_r_at(
+        &mut self,
+        id: OriginalRoad,
+        pt: Pt2D,
+        existing: Option<OriginalIntersection>,
+        prerender: &Prerender,
+    ) -> Option<(OriginalIntersection, OriginalRoad)> {
+        let pl = PolyLine::new(self.map.roads[&id].center_points.clone());
+        let mut closest = FindClosest::new(&self.compute_bounds());
+        closest.add((), pl.points());
+        let (_, split_pt) = closest.closest_pt(pt, Distance::meters(5.0))?;
+        let (dist, _) = pl.dist_along_of_point(split_pt)?;
+        if dist < MIN_ROAD_PT_SPACING || pl.length() - dist < MIN_ROAD_PT_SPACING {
+            println!("Can't split {} so close to an end", id);
+            return None;
+        }
+        if let Some(i) = existing {
+            for (i1, i2) in vec![(id.i1, i), (i, id.i2)] {
+                let half = OriginalRoad {
+                    osm_way_id: id.osm_way_id,
+                    i1,
+                    i2,
+                };
+                if i1 == i2 || self.map.roads.contains_key(&half) {
+                    println!("Can't split {} at {}; {} already exists", id, i, half);
+                    return None;
+                }
+            }
+        }
+
+        // Other roads with turn restrictions leading to this one need to be redrawn.
+        let others: Vec<OriginalRoad> = self
+            .map
+            .roads
+            .iter()
+            .filter(|(r, road)| **r != id && road.turn_restrictions.iter().any(|(_, to)| *to == id))
+            .map(|(r, _)| *r)
+            .collect();
+        self.stop_showing_pts(id);
+        self.road_deleted(id);
+        for r in &others {
+            self.road_deleted(*r);
+        }
+        self.world.delete(ID::Intersection(id.i1));
+        self.world.delete(ID::Intersection(id.i2));
+        let orig = self.map.roads[&id].clone();
+        let restrictions = self.map.delete_road(id);
+
+        // Roads already attached to an existing intersection move along with it
+        let mut moved = Vec::new();
+        let new_i = match existing {
+            Some(i) => {
+                self.world.delete(ID::Intersection(i));
+                for r in self.map.move_intersection(i, split_pt).unwrap() {
+                    if !others.contains(&r) {
+                        self.road_deleted(r);
+                        moved.push(r);
+                    }
+                }
+                i
+            }
+            None => {
+                let i = self.insert_i(split_pt, IntersectionType::StopSign, prerender);
+                self.world.delete(ID::Intersection(i));
+                i
+            }
+        };
+        let r1 = OriginalRoad {
+            osm_way_id: id.osm_way_id,
+            i1: id.i1,
+            i2: new_i,
+        };
+        let r2 = OriginalRoad {
+            osm_way_id: id.osm_way_id,
+            i1: new_i,
+            i2: id.i2,
+        };
+        // Whichever half is connected to some other road
+        let half = |other: OriginalRoad| {
+            if other.i1 == id.i2 || other.i2 == id.i2 {
+                r2
+            } else {
+                r1
+            }
+        };
+
+        let mut first = orig.clone();
+        first.center_points = pl.exact_slice(Distance::ZERO, dist).into_points();
+        first.osm_tags.remove(osm::ENDPT_FWD);
+        first.turn_restrictions.clear();
+        first.complicated_turn_restrictions.clear();
+        first.grades.clear();
+        let mut second = first.clone();
+        second.center_points = pl.exact_slice(dist, pl.length()).into_points();
+        second.osm_tags = orig.osm_tags.clone();
+        second.osm_tags.remove(osm::ENDPT_BACK);
+        for (via, to) in orig.complicated_turn_restrictions {
+            if half(via) == r1 {
+                first.complicated_turn_restrictions.push((via, to));
+            } else {
+                second.complicated_turn_restrictions.push((via, to));
+            }
+        }
+        self.map.roads.insert(r1, first);
+        self.map.roads.insert(r2, second);
+
+        for TurnRestriction(from, restriction, to) in restrictions {
+            if from == id {
+                self.map
+                    .roads
+                    .get_mut(&half(to))
+                    .unwrap()
+                    .turn_restrictions
+                    .push((restriction, to));
+            } else {
+                self.map
+                    .roads
+                    .get_mut(&from)
+                    .unwrap()
+                    .turn_restrictions
+                    .push((restriction, half(from)));
+            }
+        }
+        // Restrictions via the original road would now have to go through both halves, which
+        // can't be expressed.
+        for road in self.map.roads.values_mut() {
+            road.complicated_turn_restrictions
+                .retain(|(via, _)| *via != id);
+            for (via, to) in &mut road.complicated_turn_restrictions {
+                if *to == id {
+                    *to = half(*via);
+                }
+            }
+        }
+
+        self.road_added(r1, prerender);
+        self.road_added(r2, prerender);
+        for r in others.into_iter().chain(moved) {
+            self.road_added(r, prerender);
+        }
+        for i in vec![id.i1, id.i2, new_i] {
+            self.intersection_added(i, prerender);
+        }
+        Some((new_i, r2))
+    }
+
+    // Creates roads following every LineString in a GeoJSON file, with intersections at their
+    // endpoints. Endpoints close together share one intersection. If the map is empty, its
+    // bounds are set from the file; otherwise anything outside the current bounds is skipped.
+    pub fn import_geojson(&mut self, path: &str, prerender: &Prerender) {
+        let value: serde_json::Value = match abstutil::slurp_file(path)
+            .and_then(|raw| serde_json::from_slice(&raw).map_err(|err| err.into()))
+        {
+            Ok(v) => v,
+            Err(err) => {
+                println!("Couldn't read {}: {}", path, err);
+                return;
+            }
+        };
+        let mut lines: Vec<Vec<LonLat>> = Vec::new();
+        for feature in value["features"]
+            .as_array()
+            .cloned()
+            .unwrap_or_else(Vec::new)
+        {
+            if feature["geometry"]["type"] != "LineString" {
+                continue;
+            }
+            let pts = feature["geometry"]["coordinates"]
+                .as_array()
+                .cloned()
+                .unwrap_or_else(Vec::new)
+                .into_iter()
+                .filter_map(|pair| Some(LonLat::new(pair[0].as_f64()?, pair[1].as_f64()?)))
+                .collect::<Vec<_>>();
+            if pts.len() >= 2 {
+                lines.push(pts);
+            }
+        }
+
+        if self.map.intersections.is_empty() {
+            self.map.gps_bounds = GPSBounds::new();
+            for pt in lines.iter().flatten() {
+                self.map.gps_bounds.update(*pt);
+            }
+            self.map.boundary_polygon = self.map.gps_bounds.to_bounds().get_rectangle();
+        }
+
+        let mut touched = BTreeSet::new();
+        let mut num_roads = 0;
+        for line in lines {
+            let mut pts = match self.map.gps_bounds.try_convert(&line) {
+                Some(pts) => pts,
+                None => {
+                    println!("Skipping a LineString outside the map");
+                    continue;
+                }
+            };
+            let i1 = self.find_or_create_i(pts[0], prerender);
+            let i2 = self.find_or_create_i(*pts.last().unwrap(), prerender);
+            if i1 == i2
+                || self
+                    .map
+                    .roads
+                    .keys()
+                    .any(|r| (r.i1 == i1 && r.i2 == i2) || (r.i1 == i2 && r.i2 == i1))
+            {
+                println!("Skipping a LineString that'd be a loop or a duplicate road");
+                continue;
+            }
+            pts[0] = self.map.intersections[&i1].point;
+            *pts.last_mut().unwrap() = self.map.intersections[&i2].point;
+            let mut center_points = vec![pts[0]];
+            for pt in pts.into_iter().skip(1) {
+                if center_points.last().unwrap().dist_to(pt) >= MIN_ROAD_PT_SPACING {
+                    center_points.push(pt);
+                }
+            }
+            // The last point might've been dropped for being too close to the previous one.
+            *center_points.last_mut().unwrap() = self.map.intersections[&i2].point;
+            if center_points.len() < 2 {
+                continue;
+            }
+
+            let id = OriginalRoad {
+                osm_way_id: self.map.new_osm_way_id(time_to_id()),
+                i1,
+                i2,
+            };
+            self.map.roads.insert(
+                id,
+                RawRoad {
+                    center_points,
+                    osm_tags: synthetic_road_tags(id, &self.default_road_spec),
+                    turn_restrictions: Vec::new(),
+                    complicated_turn_restrictions: Vec::new(),
+                    grades: Vec::new(),
+                },
+            );
+            self.road_added(id, prerender);
+            touched.insert(i1);
+            touched.insert(i2);
+            num_roads += 1;
+        }
+
+        for i in touched {
+            self.world.delete(ID::Intersection(i));
+            self.intersection_added(i, prerender);
+        }
+        println!("Imported {} roads from {}", num_roads, path);
+    }
+
+    fn find_or_create_i(&mut self, pt: Pt2D, prerender: &Prerender) -> OriginalIntersection {
+        for (id, i) in &self.map.intersections {
+            if i.point.dist_to(pt) < MIN_ROAD_PT_SPACING {
+                return *id;
+            }
+        }
+        self.insert_i(pt, IntersectionType::StopSign, prerender)
+    }
+
+    // Creates a parallel copy of a road, shifted to the right by offset (or left, if it's
+    // negative), with the lanes reversed. Useful for building the other half of a divided road.
+    // Each end connects to a nearby intersection if there is one; otherwise a new one is made.
+    // Returns None if the road can't be shifted that far or the shifted geometry would cross
+    // itself.
+    pub fn duplicate_r_offset(
+        &mut self,
+        id: OriginalRoad,
+        offset: Distance,
+        prerender: &Prerender,
+    ) -> Option<OriginalRoad> {
+        let orig = &self.map.roads[&id];
+        let center = PolyLine::new(orig.center_points.clone());
+        let shifted = if offset >= Distance::ZERO {
+            center.maybe_shift_right(offset)
+        } else {
+            center.maybe_shift_left(-offset)
+        };
+        let shifted = match shifted {
+            Ok(pl) => pl,
+            Err(err) => {
+                println!("Can't shift {} by {}: {}", id, offset, err);
+                return None;
+            }
+        };
+        let mut pts = shifted.points().clone();
+
+        // Shifting too far around a tight curve can reverse segments or make them cross.
+        let lines = shifted.lines();
+        if center
+            .lines()
+            .iter()
+            .zip(lines.iter())
+            .any(|(l1, l2)| !l1.angle().approx_eq(l2.angle(), 90.0))
+        {
+            println!("Shifting {} by {} reverses part of it", id, offset);
+            return None;
+        }
+        for (idx1, l1) in lines.iter().enumerate() {
+            for l2 in lines.iter().skip(idx1 + 2) {
+                if l1.intersection(l2).is_some() {
+                    println!("Shifting {} by {} makes it cross itself", id, offset);
+                    return None;
+                }
+            }
+        }
+
+        let mut osm_tags = orig.osm_tags.clone();
+        let mut spec = orig.get_spec();
+        mem::swap(&mut spec.fwd, &mut spec.back);
         osm_tags.insert(osm::SYNTHETIC.to_string(), "true".to_string());
-        osm_tags.insert(
+        osm_tags.insert(osm::SYNTHETIC_LANES.to_string(), spec.to_string());
+        // The copy has its own endpoints and isn't part of the original's street group
+        osm_tags.remove(osm::ENDPT_FWD);
+        osm_tags.remove(osm::ENDPT_BACK);
+        osm_tags.remove(osm::STREET_GROUP);
+        // Per-lane overrides refer to the old lane order
+        abstutil::retain_btreemap(&mut osm_tags, |k, _| {
+            !k.starts_with(osm::SYNTHETIC_LANE_MAXSPEED)
+        });
+
+        // Don't connect back to the original road's own intersections.
+        let find_or_create_i = |model: &mut Model, pt: Pt2D| {
+            if let Some((i, _)) = model
+                .map
+                .intersections
+                .iter()
+                .filter(|(i, _)| **i != id.i1 && **i != id.i2)
+                .map(|(i, intersection)| (*i, intersection.point.dist_to(pt)))
+                .filter(|(_, dist)| *dist <= INTERSECTION_RADIUS)
+                .min_by_key(|(_, dist)| *dist)
+            {
+                i
+            } else {
+                model.insert_i(pt, IntersectionType::StopSign, prerender)
+            }
+        };
+        let i1 = find_or_create_i(self, pts[0]);
+        let i2 = find_or_create_i(self, *pts.last().unwrap());
+        if i1 == i2
+            || self
+                .map
+                .roads
+                .keys()
+                .any(|r| (r.i1 == i1 && r.i2 == i2) || (r.i1 == i2 && r.i2 == i1))
+        {
+            println!("Duplicating {} would connect the same intersections", id);
+            return None;
+        }
+        pts[0] = self.map.intersections[&i1].point;
+        *pts.last_mut().unwrap() = self.map.intersections[&i2].point;
+
+        let new_id = OriginalRoad {
+            osm_way_id: self.map.new_osm_way_id(time_to_id()),
+            i1,
+            i2,
+        };
+        osm_tags.insert(osm::OSM_WAY_ID.to_string(), new_id.osm_way_id.to_string());
+        self.map.roads.insert(
+            new_id,
+            RawRoad {
+                center_points: pts,
+                osm_tags,
+                turn_restrictions: Vec::new(),
+                complicated_turn_restrictions: Vec::new(),
+                grades: Vec::new(),
+            },
+        );
+        self.road_added(new_id, prerender);
+        for i in vec![i1, i2] {
+            self.world.delete(ID::Intersection(i));
+            self.intersection_added(i, prerender);
+        }
+        Some(new_id)
+    }
+
+    pub fn edit_lanes(&mut self, id: OriginalRoad, spec: String, prerender: &Prerender) {
+        self.road_deleted(id);
+
+        if let Some(s) = RoadSpec::parse(spec.clone()) {
+            let old_spec = self.map.roads[&id].get_spec();
+            let osm_tags = &mut self.map.roads.get_mut(&id).unwrap().osm_tags;
+            if !s.back.is_empty() {
+                osm_tags.remove("oneway");
+            }
+            retain_lane_speeds(osm_tags, &old_spec, &s);
+            osm_tags.insert(osm::SYNTHETIC_LANES.to_string(), s.to_string());
+        } else {
+            println!("Bad RoadSpec: {}", spec);
+        }
+
+        self.road_added(id, prerender);
+    }
+
+    pub fn copy_road_config(&self, id: OriginalRoad) -> RoadSpec {
+        self.map.roads[&id].get_spec()
+    }
+
+    // Optionally also sets the (name, speed limit).
+    pub fn paste_road_config(
+        &mut self,
+        id: OriginalRoad,
+        spec: RoadSpec,
+        name_and_speed: Option<(String, String)>,
+        prerender: &Prerender,
+    ) {
+        self.road_deleted(id);
+
+        let old_spec = self.map.roads[&id].get_spec();
+        let osm_tags = &mut self.map.roads.get_mut(&id).unwrap().osm_tags;
+        if !spec.back.is_empty() {
+            osm_tags.remove("oneway");
+        }
+        retain_lane_speeds(osm_tags, &old_spec, &spec);
+        osm_tags.insert(osm::SYNTHETIC_LANES.to_string(), spec.to_string());
+        if let Some((name, speed)) = name_and_speed {
+            osm_tags.insert(osm::NAME.to_string(), name);
+            osm_tags.insert(osm::MAXSPEED.to_string(), speed);
+            osm_tags.remove(osm::MAXSPEED_FWD);
+            osm_tags.remove(osm::MAXSPEED_BACK);
+        }
+
+        self.road_added(id, prerender);
+    }
+
+    pub fn swap_lanes(&mut self, id: OriginalRoad, prerender: &Prerender) {
+        if self.is_oneway(id) {
+            println!("Not swapping lanes of one-way {}", id);
+            return;
+        }
+        self.road_deleted(id);
+
+        let (mut lanes, osm_tags) = {
+            let r = self.map.roads.get_mut(&id).unwrap();
+            (r.get_spec(), &mut r.osm_tags)
+        };
+        mem::swap(&mut lanes.fwd, &mut lanes.back);
+        osm_tags.insert(osm::SYNTHETIC_LANES.to_string(), lanes.to_string());
+        swap_lane_speeds(osm_tags);
+
+        self.road_added(id, prerender);
+    }
+
+    // Flips which end of the road is the start, keeping the same physical lanes. Unlike
+    // swap_lanes, the geometry and ID change too.
+    pub fn reverse_r(&mut self, id: OriginalRoad, prerender: &Prerender) {
+        let new_id = OriginalRoad {
+            osm_way_id: id.osm_way_id,
+            i1: id.i2,
+            i2: id.i1,
+        };
+        if self.map.roads.contains_key(&new_id) {
+            println!("Can't reverse {}; {} already exists", id, new_id);
+            return;
+        }
+
+        // Other roads with turn restrictions leading to this one need to be redrawn.
+        let others: Vec<OriginalRoad> = self
+            .map
+            .roads
+            .iter()
+            .filter(|(r, road)| **r != id && road.turn_restrictions.iter().any(|(_, to)| *to == id))
+            .map(|(r, _)| *r)
+            .collect();
+        self.stop_showing_pts(id);
+        self.road_deleted(id);
+        for r in &others {
+            self.road_deleted(*r);
+        }
+        let mut road = self.map.roads[&id].clone();
+        let restrictions = self.map.delete_road(id);
+
+        road.center_points.reverse();
+        road.grades = road.grades.iter().rev().map(|g| -g).collect();
+        let mut lanes = road.get_spec();
+        mem::swap(&mut lanes.fwd, &mut lanes.back);
+        road.osm_tags
+            .insert(osm::SYNTHETIC_LANES.to_string(), lanes.to_string());
+        swap_lane_speeds(&mut road.osm_tags);
+        // One-ways keep flowing the same physical direction
+        match road.osm_tags.get("oneway").map(|s| s.as_str()) {
+            Some("yes") => {
+                road.osm_tags.insert("oneway".to_string(), "-1".to_string());
+            }
+            Some("-1") => {
+                road.osm_tags
+                    .insert("oneway".to_string(), "yes".to_string());
+            }
+            _ => {}
+        }
+        let fwd = road.osm_tags.remove(osm::ENDPT_FWD);
+        let back = road.osm_tags.remove(osm::ENDPT_BACK);
+        if let Some(value) = fwd {
+            road.osm_tags.insert(osm::ENDPT_BACK.to_string(), value);
+        }
+        if let Some(value) = back {
+            road.osm_tags.insert(osm::ENDPT_FWD.to_string(), value);
+        }
+        // Restored below with the new ID
+        road.turn_restrictions.clear();
+        self.map.roads.insert(new_id, road);
+
+        let rename = |r: OriginalRoad| if r == id { new_id } else { r };
+        for TurnRestriction(from, restriction, to) in restrictions {
+            self.map
+                .roads
+                .get_mut(&rename(from))
+                .unwrap()
+                .turn_restrictions
+                .push((restriction, rename(to)));
+        }
+        for road in self.map.roads.values_mut() {
+            for (via, to) in &mut road.complicated_turn_restrictions {
+                *via = rename(*via);
+                *to = rename(*to);
+            }
+        }
+
+        self.road_added(new_id, prerender);
+        for r in others {
+            self.road_added(r, prerender);
+        }
+    }
+
+    // oneway=-1 means the road only flows backwards, but that's still one-way.
+    pub fn is_oneway(&self, id: OriginalRoad) -> bool {
+        matches!(
+            self.map.roads[&id]
+                .osm_tags
+                .get("oneway")
+                .map(|s| s.as_str()),
+            Some("yes") | Some("-1")
+        )
+    }
+
+    // Puts every lane on the forward side. Lanes are ordered from the center: driving lanes, then
+    // bike lanes, then parking, then one sidewalk.
+    pub fn make_oneway(&mut self, id: OriginalRoad, prerender: &Prerender) {
+        self.road_deleted(id);
+
+        let r = self.map.roads.get_mut(&id).unwrap();
+        let spec = r.get_spec();
+        let rank = |lt: &LaneType| match lt {
+            LaneType::Driving
+            | LaneType::Bus
+            | LaneType::SharedLeftTurn
+            | LaneType::Construction => 0,
+            LaneType::Biking => 1,
+            LaneType::Parking => 2,
+            LaneType::Sidewalk => 3,
+        };
+        let mut fwd: Vec<LaneType> = spec.fwd.into_iter().chain(spec.back.into_iter()).collect();
+        fwd.sort_by_key(rank);
+        let mut seen_sidewalk = false;
+        fwd.retain(|lt| {
+            if *lt != LaneType::Sidewalk {
+                return true;
+            }
+            let keep = !seen_sidewalk;
+            seen_sidewalk = true;
+            keep
+        });
+        r.osm_tags.insert(
             osm::SYNTHETIC_LANES.to_string(),
             RoadSpec {
-                fwd: vec![LaneType::Driving, LaneType::Parking, LaneType::Sidewalk],
-                back: vec![LaneType::Driving, LaneType::Parking, LaneType::Sidewalk],
+                fwd,
+                back: Vec::new(),
             }
             .to_string(),
         );
-        osm_tags.insert(osm::ENDPT_FWD.to_string(), "true".to_string());
-        osm_tags.insert(osm::ENDPT_BACK.to_string(), "true".to_string());
-        osm_tags.insert(osm::OSM_WAY_ID.to_string(), id.osm_way_id.to_string());
-        // Reasonable defaults.
-        osm_tags.insert(osm::NAME.to_string(), "Streety McStreetFace".to_string());
-        osm_tags.insert(osm::MAXSPEED.to_string(), "25 mph".to_string());
+        r.osm_tags.insert("oneway".to_string(), "yes".to_string());
+        // The lanes got shuffled around, so per-lane speed limits don't apply anymore.
+        take_lane_speeds(&mut r.osm_tags);
 
-        self.map.roads.insert(
-            id,
-            RawRoad {
-                center_points: vec![
-                    self.map.intersections[&i1].point,
-                    self.map.intersections[&i2].point,
-                ],
-                osm_tags,
-                turn_restrictions: Vec::new(),
-                complicated_turn_restrictions: Vec::new(),
-            },
-        );
         self.road_added(id, prerender);
     }
 
-    pub fn edit_lanes(&mut self, id: OriginalRoad, spec: String, prerender: &Prerender) {
+    pub fn set_r_name_and_speed(
+        &mut self,
+        id: OriginalRoad,
+        name: String,
+        speed: String,
+        highway: String,
+        prerender: &Prerender,
+    ) {
         self.road_deleted(id);
 
-        if let Some(s) = RoadSpec::parse(spec.clone()) {
+        let osm_tags = &mut self.map.roads.get_mut(&id).unwrap().osm_tags;
+        osm_tags.insert(osm::NAME.to_string(), name);
+        osm_tags.insert(osm::MAXSPEED.to_string(), speed);
+        // The new speed applies to both directions
+        osm_tags.remove(osm::MAXSPEED_FWD);
+        osm_tags.remove(osm::MAXSPEED_BACK);
+        osm_tags.insert(osm::HIGHWAY.to_string(), highway);
+
+        self.road_added(id, prerender);
+    }
+
+    // Replaces any group these roads were already in.
+    pub fn group_roads(&mut self, ids: &[OriginalRoad], name: String) {
+        for id in ids {
             self.map
                 .roads
-                .get_mut(&id)
+                .get_mut(id)
                 .unwrap()
                 .osm_tags
-                .insert(osm::SYNTHETIC_LANES.to_string(), s.to_string());
+                .insert(osm::STREET_GROUP.to_string(), name.clone());
+        }
+    }
+
+    pub fn ungroup_road(&mut self, id: OriginalRoad) {
+        self.map
+            .roads
+            .get_mut(&id)
+            .unwrap()
+            .osm_tags
+            .remove(osm::STREET_GROUP);
+    }
+
+    pub fn get_street_group(&self, id: OriginalRoad) -> Option<&String> {
+        self.map.roads[&id].osm_tags.get(osm::STREET_GROUP)
+    }
+
+    // Every road in the same group, including this one. Empty if the road isn't grouped.
+    pub fn street_group_members(&self, id: OriginalRoad) -> Vec<OriginalRoad> {
+        if let Some(name) = self.get_street_group(id) {
+            self.map
+                .roads
+                .iter()
+                .filter(|(_, r)| r.osm_tags.get(osm::STREET_GROUP) == Some(name))
+                .map(|(id, _)| *id)
+                .collect()
         } else {
-            println!("Bad RoadSpec: {}", spec);
+            Vec::new()
         }
+    }
 
-        self.road_added(id, prerender);
+    // If the road only has a speed limit per direction, uses the forwards one.
+    pub fn get_r_name_and_speed(&self, id: OriginalRoad) -> (String, String) {
+        let osm_tags = &self.map.roads[&id].osm_tags;
+        (
+            osm_tags.get(osm::NAME).cloned().unwrap_or_else(String::new),
+            osm_tags
+                .get(osm::MAXSPEED)
+                .or_else(|| osm_tags.get(osm::MAXSPEED_FWD))
+                .or_else(|| osm_tags.get(osm::MAXSPEED_BACK))
+                .cloned()
+                .unwrap_or_else(String::new),
+        )
     }
 
-    pub fn swap_lanes(&mut self, id: OriginalRoad, prerender: &Prerender) {
+    // Falls back to the speed for the whole road.
+    pub fn get_r_directional_speed(&self, id: OriginalRoad, fwd: bool) -> Option<String> {
+        let osm_tags = &self.map.roads[&id].osm_tags;
+        osm_tags
+            .get(if fwd {
+                osm::MAXSPEED_FWD
+            } else {
+                osm::MAXSPEED_BACK
+            })
+            .or_else(|| osm_tags.get(osm::MAXSPEED))
+            .cloned()
+    }
+
+    pub fn set_r_directional_speed(
+        &mut self,
+        id: OriginalRoad,
+        fwd: bool,
+        speed: String,
+        prerender: &Prerender,
+    ) {
+        if osm::parse_maxspeed(&speed).is_none() {
+            println!("Bad speed {}; use something like \"25 mph\"", speed);
+            return;
+        }
         self.road_deleted(id);
 
-        let (mut lanes, osm_tags) = {
-            let r = self.map.roads.get_mut(&id).unwrap();
-            (r.get_spec(), &mut r.osm_tags)
-        };
-        mem::swap(&mut lanes.fwd, &mut lanes.back);
-        osm_tags.insert(osm::SYNTHETIC_LANES.to_string(), lanes.to_string());
+        self.map.roads.get_mut(&id).unwrap().osm_tags.insert(
+            if fwd {
+                osm::MAXSPEED_FWD
+            } else {
+                osm::MAXSPEED_BACK
+            }
+            .to_string(),
+            speed,
+        );
 
         self.road_added(id, prerender);
     }
 
-    pub fn set_r_name_and_speed(
+    // Uses the road's speed limit if the lane doesn't override it.
+    pub fn get_lane_speed(&self, id: OriginalRoad, fwd: bool, idx: usize) -> Option<String> {
+        self.map.roads[&id]
+            .osm_tags
+            .get(&osm::lane_maxspeed_key(fwd, idx))
+            .cloned()
+            .or_else(|| self.get_r_directional_speed(id, fwd))
+    }
+
+    pub fn set_lane_speed(
         &mut self,
         id: OriginalRoad,
-        name: String,
+        fwd: bool,
+        idx: usize,
         speed: String,
-        highway: String,
         prerender: &Prerender,
     ) {
+        let spec = self.map.roads[&id].get_spec();
+        let mut osm_tags = self.map.roads[&id].osm_tags.clone();
+        if let Err(err) = set_lane_speed_tag(&mut osm_tags, &spec, fwd, idx, speed) {
+            println!("Can't set lane speed on {}: {}", id, err);
+            return;
+        }
         self.road_deleted(id);
 
-        let osm_tags = &mut self.map.roads.get_mut(&id).unwrap().osm_tags;
-        osm_tags.insert(osm::NAME.to_string(), name);
-        osm_tags.insert(osm::MAXSPEED.to_string(), speed);
-        osm_tags.insert(osm::HIGHWAY.to_string(), highway);
+        self.map.roads.get_mut(&id).unwrap().osm_tags = osm_tags;
 
         self.road_added(id, prerender);
     }
@@ -498,51 +2163,29 @@ impl Model {
         let unset =
             r.synthetic() && r.osm_tags.get(osm::NAME) == Some(&"Streety McStreetFace".to_string());
         let lanes_unknown = r.osm_tags.contains_key(osm::INFERRED_SIDEWALKS);
-        let spec = r.get_spec();
         let center_pts = PolyLine::new(r.center_points.clone());
 
         let mut obj = Object::blank(ID::Road(id));
-
-        let mut offset = Distance::ZERO;
-        for (idx, lt) in spec.fwd.iter().enumerate() {
-            let width = if *lt == LaneType::Sidewalk {
-                SIDEWALK_THICKNESS
+        let num_fwd = r.get_spec().fwd.len();
+        for (idx, (lt, polygon)) in self.lane_polygons(id).into_iter().enumerate() {
+            let color = if self.speed_colors {
+                let speed = if idx < num_fwd {
+                    self.get_lane_speed(id, true, idx)
+                } else {
+                    self.get_lane_speed(id, false, idx - num_fwd)
+                };
+                Model::speed_to_color(speed)
             } else {
-                NORMAL_LANE_THICKNESS
+                Model::lt_to_color(lt, unset, lanes_unknown)
             };
-            obj.push(
-                Model::lt_to_color(*lt, unset, lanes_unknown),
-                self.map
-                    .driving_side
-                    .right_shift(center_pts.clone(), offset + width / 2.0)
-                    .unwrap()
-                    .make_polygons(width),
-            );
-            offset += width;
-            if idx == 0 {
+            obj.push(color, polygon);
+            if idx == 0 && num_fwd > 0 {
                 obj.push(
                     Color::YELLOW,
                     center_pts.make_polygons(CENTER_LINE_THICKNESS),
                 );
             }
         }
-        offset = Distance::ZERO;
-        for lt in &spec.back {
-            let width = if *lt == LaneType::Sidewalk {
-                SIDEWALK_THICKNESS
-            } else {
-                NORMAL_LANE_THICKNESS
-            };
-            obj.push(
-                Model::lt_to_color(*lt, unset, lanes_unknown),
-                self.map
-                    .driving_side
-                    .right_shift(center_pts.reversed(), offset + width / 2.0)
-                    .unwrap()
-                    .make_polygons(width),
-            );
-            offset += width;
-        }
 
         let mut result = vec![obj];
         for (restriction, to) in &r.turn_restrictions {
@@ -574,6 +2217,38 @@ impl Model {
         result
     }
 
+    // Forwards lanes, then backwards, each from the center of the road outwards
+    fn lane_polygons(&self, id: OriginalRoad) -> Vec<(LaneType, Polygon)> {
+        let r = &self.map.roads[&id];
+        let spec = r.get_spec();
+        let center_pts = PolyLine::new(r.center_points.clone());
+
+        let mut polygons = Vec::new();
+        for (lanes, pl) in vec![
+            (&spec.fwd, center_pts.clone()),
+            (&spec.back, center_pts.reversed()),
+        ] {
+            let mut offset = Distance::ZERO;
+            for lt in lanes {
+                let width = if *lt == LaneType::Sidewalk {
+                    SIDEWALK_THICKNESS
+                } else {
+                    NORMAL_LANE_THICKNESS
+                };
+                polygons.push((
+                    *lt,
+                    self.map
+                        .driving_side
+                        .right_shift(pl.clone(), offset + width / 2.0)
+                        .unwrap()
+                        .make_polygons(width),
+                ));
+                offset += width;
+            }
+        }
+        polygons
+    }
+
     // Copied from render/lane.rs. :(
     fn lt_to_color(lt: LaneType, unset: bool, lanes_unknown: bool) -> Color {
         let color = match lt {
@@ -594,6 +2269,18 @@ impl Model {
         }
     }
 
+    // Green at 20mph or less, up to red at 60mph or more. Grey if the speed is missing or can't be
+    // parsed.
+    fn speed_to_color(speed: Option<String>) -> Color {
+        match speed.and_then(|s| osm::parse_maxspeed(&s)) {
+            Some(speed) => {
+                let mph = speed.inner_meters_per_second() / 0.44704;
+                Color::GREEN.lerp(Color::RED, ((mph - 20.0) / 40.0).max(0.0).min(1.0))
+            }
+            None => Color::grey(0.5),
+        }
+    }
+
     pub fn show_r_points(&mut self, id: OriginalRoad, prerender: &Prerender) {
         if self.showing_pts == Some(id) {
             return;
@@ -633,13 +2320,20 @@ impl Model {
     pub fn move_r_pt(&mut self, id: OriginalRoad, idx: usize, point: Pt2D, prerender: &Prerender) {
         assert_eq!(self.showing_pts, Some(id));
 
+        let mut pts = self.map.roads[&id].center_points.clone();
+        pts[idx] = point;
+        if pts_too_close(&pts) {
+            return;
+        }
+
         self.stop_showing_pts(id);
         self.road_deleted(id);
         self.world.delete(ID::Intersection(id.i1));
         self.world.delete(ID::Intersection(id.i2));
 
-        let pts = &mut self.map.roads.get_mut(&id).unwrap().center_points;
-        pts[idx] = point;
+        let r = self.map.roads.get_mut(&id).unwrap();
+        r.center_points = pts;
+        r.grades.clear();
 
         self.road_added(id, prerender);
         self.intersection_added(id.i1, prerender);
@@ -650,13 +2344,25 @@ impl Model {
     pub fn delete_r_pt(&mut self, id: OriginalRoad, idx: usize, prerender: &Prerender) {
         assert_eq!(self.showing_pts, Some(id));
 
+        let mut pts = self.map.roads[&id].center_points.clone();
+        if pts.len() == 2 {
+            println!("Can't delete an endpoint of {}", id);
+            return;
+        }
+        pts.remove(idx);
+        if pts_too_close(&pts) {
+            return;
+        }
+
         self.stop_showing_pts(id);
         self.road_deleted(id);
         self.world.delete(ID::Intersection(id.i1));
         self.world.delete(ID::Intersection(id.i2));
 
-        let pts = &mut self.map.roads.get_mut(&id).unwrap().center_points;
-        pts.remove(idx);
+        let r = self.map.roads.get_mut(&id).unwrap();
+        r.center_points = pts;
+        // There's no elevation data here to recompute the grade with.
+        r.grades.clear();
 
         self.road_added(id, prerender);
         self.intersection_added(id.i1, prerender);
@@ -673,13 +2379,23 @@ impl Model {
         self.world.delete(ID::Intersection(id.i2));
 
         let mut closest = FindClosest::new(&self.compute_bounds());
-        let pts = &mut self.map.roads.get_mut(&id).unwrap().center_points;
+        let r = self.map.roads.get_mut(&id).unwrap();
+        let pts = &mut r.center_points;
         for (idx, pair) in pts.windows(2).enumerate() {
             closest.add(idx + 1, &vec![pair[0], pair[1]]);
         }
         let new_id = if let Some((idx, _)) = closest.closest_pt(pt, Distance::meters(5.0)) {
-            pts.insert(idx, pt);
-            Some(ID::RoadPoint(id, idx))
+            if pt.dist_to(pts[idx - 1]) < MIN_ROAD_PT_SPACING
+                || pt.dist_to(pts[idx]) < MIN_ROAD_PT_SPACING
+            {
+                println!("New point is too close to an existing one");
+                None
+            } else {
+                pts.insert(idx, pt);
+                // The segments changed, so the old grades don't line up anymore
+                r.grades.clear();
+                Some(ID::RoadPoint(id, idx))
+            }
         } else {
             println!("Couldn't figure out where to insert new point");
             None
@@ -703,6 +2419,7 @@ impl Model {
 
         let r = &mut self.map.roads.get_mut(&id).unwrap();
         r.center_points = vec![r.center_points[0], *r.center_points.last().unwrap()];
+        r.grades.clear();
 
         self.road_added(id, prerender);
         self.intersection_added(id.i1, prerender);
@@ -713,6 +2430,16 @@ impl Model {
     pub fn get_r_center(&self, id: OriginalRoad) -> Pt2D {
         PolyLine::new(self.map.roads[&id].center_points.clone()).middle()
     }
+
+    // A point along the road a little bit away from one of its intersections
+    pub fn get_r_pt_near(&self, id: OriginalRoad, i: OriginalIntersection) -> Pt2D {
+        let mut pl = PolyLine::new(self.map.roads[&id].center_points.clone());
+        if id.i2 == i {
+            pl = pl.reversed();
+        }
+        let dist = std::cmp::min(Distance::meters(15.0), pl.length() / 2.0);
+        pl.dist_along(dist).0
+    }
 }
 
 // Turn restrictions
@@ -772,6 +2499,33 @@ impl Model {
         ID::Building(id)
     }
 
+    // The footprint is closed automatically, and must not cross itself.
+    pub fn create_b_from_points(&mut self, pts: Vec<Pt2D>, prerender: &Prerender) -> Option<ID> {
+        let polygon = match simple_polygon(pts) {
+            Ok(p) => p,
+            Err(err) => {
+                println!("A building {}", err);
+                return None;
+            }
+        };
+
+        let id = OriginalBuilding {
+            osm_way_id: self.map.new_osm_way_id(time_to_id()),
+        };
+        self.map.buildings.insert(
+            id,
+            RawBuilding {
+                polygon,
+                osm_tags: BTreeMap::new(),
+                public_garage_name: None,
+                num_parking_spots: 0,
+                amenities: BTreeSet::new(),
+            },
+        );
+        self.bldg_added(id, prerender);
+        Some(ID::Building(id))
+    }
+
     pub fn move_b(&mut self, id: OriginalBuilding, new_center: Pt2D, prerender: &Prerender) {
         self.world.delete(ID::Building(id));
 
@@ -785,13 +2539,134 @@ impl Model {
         self.bldg_added(id, prerender);
     }
 
+    // A public garage can be used by anybody; otherwise the spots are just for the building.
+    pub fn set_b_parking(
+        &mut self,
+        id: OriginalBuilding,
+        num_spots: usize,
+        public_garage_name: Option<String>,
+        prerender: &Prerender,
+    ) {
+        self.world.delete(ID::Building(id));
+
+        let b = self.map.buildings.get_mut(&id).unwrap();
+        b.num_parking_spots = num_spots;
+        b.public_garage_name = public_garage_name;
+
+        self.bldg_added(id, prerender);
+    }
+
+    // Replaces the building with an axis-aligned rectangle around the same center.
+    pub fn resize_b(
+        &mut self,
+        id: OriginalBuilding,
+        width: Distance,
+        height: Distance,
+        prerender: &Prerender,
+    ) {
+        self.world.delete(ID::Building(id));
+
+        let b = self.map.buildings.get_mut(&id).unwrap();
+        b.polygon = Polygon::rectangle_centered(b.polygon.center(), width, height);
+
+        self.bldg_added(id, prerender);
+    }
+
+    // Rotates clockwise about the building's center.
+    pub fn rotate_b(&mut self, id: OriginalBuilding, degrees: f64, prerender: &Prerender) {
+        self.world.delete(ID::Building(id));
+
+        let b = self.map.buildings.get_mut(&id).unwrap();
+        b.polygon = b.polygon.rotate(Angle::new_degs(degrees));
+
+        self.bldg_added(id, prerender);
+    }
+
     pub fn delete_b(&mut self, id: OriginalBuilding) {
         self.world.delete(ID::Building(id));
         self.map.buildings.remove(&id).unwrap();
+        self.selection.remove(&ID::Building(id));
+    }
+}
+
+// Selection
+impl Model {
+    // Only intersections and buildings can be selected.
+    pub fn add_to_selection(&mut self, id: ID) {
+        match id {
+            ID::Intersection(_) | ID::Building(_) => {
+                self.selection.insert(id);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+    }
+
+    pub fn get_selection(&self) -> &BTreeSet<ID> {
+        &self.selection
+    }
+
+    // Translates everything selected. Roads with both ends selected move entirely; other roads
+    // connected to a selected intersection just have that endpoint moved, like in move_i.
+    pub fn move_selection(&mut self, dx: Distance, dy: Distance, prerender: &Prerender) {
+        let (dx, dy) = (dx.inner_meters(), dy.inner_meters());
+        if let Some(r) = self.showing_pts {
+            self.stop_showing_pts(r);
+        }
+        let mut intersections = BTreeSet::new();
+        for id in self.selection.clone() {
+            match id {
+                ID::Intersection(i) => {
+                    intersections.insert(i);
+                }
+                ID::Building(b) => {
+                    self.world.delete(id);
+                    let bldg = self.map.buildings.get_mut(&b).unwrap();
+                    bldg.polygon = bldg.polygon.translate(dx, dy);
+                    self.bldg_added(b, prerender);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        let mut roads = BTreeSet::new();
+        for i in &intersections {
+            roads.extend(self.map.roads_per_intersection(*i));
+        }
+        for r in &roads {
+            self.road_deleted(*r);
+        }
+        for i in &intersections {
+            self.world.delete(ID::Intersection(*i));
+            let pt = &mut self.map.intersections.get_mut(i).unwrap().point;
+            *pt = pt.offset(dx, dy);
+        }
+        for r in &roads {
+            let road = self.map.roads.get_mut(r).unwrap();
+            if intersections.contains(&r.i1) && intersections.contains(&r.i2) {
+                for pt in &mut road.center_points {
+                    *pt = pt.offset(dx, dy);
+                }
+            } else if intersections.contains(&r.i1) {
+                road.center_points[0] = road.center_points[0].offset(dx, dy);
+            } else {
+                let last = road.center_points.last_mut().unwrap();
+                *last = last.offset(dx, dy);
+            }
+        }
+        for r in roads {
+            self.road_added(r, prerender);
+        }
+        for i in intersections {
+            self.intersection_added(i, prerender);
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 pub enum ID {
     Building(OriginalBuilding),
     Intersection(OriginalIntersection),
@@ -812,6 +2687,14 @@ impl ObjectID for ID {
     }
 }
 
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
 // Don't conflict with the synthetic IDs generated by map clipping.
 #[cfg(not(target_arch = "wasm32"))]
 fn time_to_id() -> i64 {
@@ -827,3 +2710,183 @@ fn time_to_id() -> i64 {
     // current time as seconds in wasm.
     -5000
 }
+
+// Synthetic roads start with these tags.
+fn synthetic_road_tags(id: OriginalRoad, spec: &RoadSpec) -> BTreeMap<String, String> {
+    let mut osm_tags = BTreeMap::new();
+    osm_tags.insert(osm::SYNTHETIC.to_string(), "true".to_string());
+    osm_tags.insert(osm::SYNTHETIC_LANES.to_string(), spec.to_string());
+    osm_tags.insert(osm::ENDPT_FWD.to_string(), "true".to_string());
+    osm_tags.insert(osm::ENDPT_BACK.to_string(), "true".to_string());
+    osm_tags.insert(osm::OSM_WAY_ID.to_string(), id.osm_way_id.to_string());
+    // Reasonable defaults.
+    osm_tags.insert(osm::NAME.to_string(), "Streety McStreetFace".to_string());
+    osm_tags.insert(osm::MAXSPEED.to_string(), "25 mph".to_string());
+    osm_tags
+}
+
+fn set_lane_speed_tag(
+    osm_tags: &mut BTreeMap<String, String>,
+    spec: &RoadSpec,
+    fwd: bool,
+    idx: usize,
+    speed: String,
+) -> Result<(), String> {
+    let num_lanes = if fwd { spec.fwd.len() } else { spec.back.len() };
+    if idx >= num_lanes {
+        return Err(format!(
+            "there's no {} lane #{}",
+            if fwd { "forwards" } else { "backwards" },
+            idx
+        ));
+    }
+    if osm::parse_maxspeed(&speed).is_none() {
+        return Err(format!(
+            "bad speed {}; use something like \"25 mph\"",
+            speed
+        ));
+    }
+    osm_tags.insert(osm::lane_maxspeed_key(fwd, idx), speed);
+    Ok(())
+}
+
+// Removes and returns the per-lane speed limits, keyed by (fwd, index from the center).
+fn take_lane_speeds(osm_tags: &mut BTreeMap<String, String>) -> BTreeMap<(bool, usize), String> {
+    let prefix = format!("{}:", osm::SYNTHETIC_LANE_MAXSPEED);
+    let keys: Vec<String> = osm_tags
+        .keys()
+        .filter(|k| k.starts_with(&prefix))
+        .cloned()
+        .collect();
+    let mut speeds = BTreeMap::new();
+    for key in keys {
+        let speed = osm_tags.remove(&key).unwrap();
+        let mut parts = key[prefix.len()..].split(':');
+        let fwd = match parts.next() {
+            Some("fwd") => true,
+            Some("back") => false,
+            _ => continue,
+        };
+        if let Some(idx) = parts.next().and_then(|x| x.parse::<usize>().ok()) {
+            speeds.insert((fwd, idx), speed);
+        }
+    }
+    speeds
+}
+
+// For when the forwards and backwards lanes trade places.
+fn swap_lane_speeds(osm_tags: &mut BTreeMap<String, String>) {
+    for ((fwd, idx), speed) in take_lane_speeds(osm_tags) {
+        osm_tags.insert(osm::lane_maxspeed_key(!fwd, idx), speed);
+    }
+}
+
+// Lanes that kept the same type and position keep their speed limit; the rest lose it.
+fn retain_lane_speeds(osm_tags: &mut BTreeMap<String, String>, old: &RoadSpec, new: &RoadSpec) {
+    for ((fwd, idx), speed) in take_lane_speeds(osm_tags) {
+        let (before, after) = if fwd {
+            (&old.fwd, &new.fwd)
+        } else {
+            (&old.back, &new.back)
+        };
+        if before.get(idx).is_some() && before.get(idx) == after.get(idx) {
+            osm_tags.insert(osm::lane_maxspeed_key(fwd, idx), speed);
+        }
+    }
+}
+
+// Closes the points into a polygon, unless there are too few, some repeat, or the edges cross
+// each other. The error finishes a sentence about the thing being drawn.
+fn simple_polygon(mut pts: Vec<Pt2D>) -> Result<Polygon, &'static str> {
+    if pts.len() > 1 && pts[0] == *pts.last().unwrap() {
+        pts.pop();
+    }
+    if pts.len() < 3 {
+        return Err("needs at least 3 points");
+    }
+    let mut lines = Vec::new();
+    for idx in 0..pts.len() {
+        if let Some(l) = geom::Line::maybe_new(pts[idx], pts[(idx + 1) % pts.len()]) {
+            lines.push(l);
+        } else {
+            return Err("can't have repeated points");
+        }
+    }
+    for (idx1, l1) in lines.iter().enumerate() {
+        for (idx2, l2) in lines.iter().enumerate().skip(idx1 + 2) {
+            // The first and last lines share a point
+            if idx1 == 0 && idx2 == lines.len() - 1 {
+                continue;
+            }
+            if l1.intersection(l2).is_some() {
+                return Err("can't cross itself");
+            }
+        }
+    }
+    Ok(Polygon::new(&pts))
+}
+
+fn pts_too_close(pts: &Vec<Pt2D>) -> bool {
+    if pts
+        .windows(2)
+        .any(|pair| pair[0].dist_to(pair[1]) < MIN_ROAD_PT_SPACING)
+    {
+        println!(
+            "Road points would be closer than {}; not changing anything",
+            MIN_ROAD_PT_SPACING
+        );
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(s: &str) -> RoadSpec {
+        RoadSpec::parse(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_lane_speed_round_trip() {
+        let mut osm_tags = BTreeMap::new();
+        let two_way = spec("dps/ds");
+        set_lane_speed_tag(&mut osm_tags, &two_way, false, 0, "15 mph".to_string()).unwrap();
+        assert!(
+            set_lane_speed_tag(&mut osm_tags, &two_way, false, 2, "15 mph".to_string()).is_err()
+        );
+        assert!(set_lane_speed_tag(&mut osm_tags, &two_way, true, 0, "fast".to_string()).is_err());
+        assert_eq!(
+            osm::parse_maxspeed(&osm_tags[&osm::lane_maxspeed_key(false, 0)]),
+            Some(geom::Speed::miles_per_hour(15.0))
+        );
+
+        swap_lane_speeds(&mut osm_tags);
+        swap_lane_speeds(&mut osm_tags);
+        assert_eq!(
+            take_lane_speeds(&mut osm_tags),
+            vec![((false, 0), "15 mph".to_string())]
+                .into_iter()
+                .collect()
+        );
+        assert!(osm_tags.is_empty());
+    }
+
+    #[test]
+    fn test_lane_speed_after_editing_lanes() {
+        let mut osm_tags = BTreeMap::new();
+        let before = spec("dps/ds");
+        set_lane_speed_tag(&mut osm_tags, &before, true, 0, "15 mph".to_string()).unwrap();
+        set_lane_speed_tag(&mut osm_tags, &before, false, 1, "10 mph".to_string()).unwrap();
+
+        // The forwards driving lane stays put, but the backwards sidewalk disappears.
+        retain_lane_speeds(&mut osm_tags, &before, &spec("dbs/d"));
+        assert_eq!(
+            take_lane_speeds(&mut osm_tags),
+            vec![((true, 0), "15 mph".to_string())]
+                .into_iter()
+                .collect()
+        );
+    }
+}
@@ -3,7 +3,7 @@ use crate::{
     osm, BuildingID, BusStopID, DirectedRoadID, IntersectionID, Map, PathConstraints, Road, RoadID,
     TurnType,
 };
-use geom::{Angle, Distance, Line, PolyLine, Pt2D};
+use geom::{Angle, Distance, Duration, Line, PolyLine, Pt2D, Speed};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
 use std::fmt;
@@ -13,6 +13,19 @@ pub const PARKING_SPOT_LENGTH: Distance = Distance::const_meters(8.0);
 // The full PARKING_SPOT_LENGTH used for on-street is looking too conservative for some manually
 // audited cases in Seattle. This is 0.8 of above
 pub const PARKING_LOT_SPOT_LENGTH: Distance = Distance::const_meters(6.4);
+// Same as NORMAL_LANE_THICKNESS
+pub const PARKING_LOT_SPOT_WIDTH: Distance = Distance::const_meters(2.5);
+
+// How cars line up in a parking lane
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OnstreetOrientation {
+    // Bumper to bumper along the curb
+    Parallel,
+    // Pulled in at 45 degrees, facing the direction of travel
+    Angled,
+    // Pulled straight in towards the curb
+    Perpendicular,
+}
 
 // TODO reconsider pub usize. maybe outside world shouldnt know.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -103,6 +116,24 @@ pub struct Lane {
     // If set, cars trying to park near here should actually start their search at this other lane.
     // Only populated for driving lanes inevitably leading to borders.
     pub parking_blackhole: Option<LaneID>,
+    // Only for parking lanes. (start, end) distances along the lane that cars can't park in.
+    pub no_parking: Vec<(Distance, Distance)>,
+    // Only for parking lanes. The first this many spots are reserved for accessible permits.
+    pub num_disabled_spots: usize,
+    // Only for parking lanes. How long a car may stay in any spot, like at a meter.
+    pub parking_time_limit: Option<Duration>,
+    // Only for parking lanes. If empty, every spot is PARKING_SPOT_LENGTH. Use
+    // parking_spot_lengths() instead, since not all of these might fit.
+    pub spot_lengths: Vec<Distance>,
+    // Only for parking lanes.
+    pub parking_orientation: OnstreetOrientation,
+    // Only for parking lanes. Hourly price in dollars, if it isn't free.
+    pub parking_cost_per_hour: Option<f64>,
+    // Only for parking lanes. Longer vehicles can't park here.
+    pub parking_max_vehicle_length: Option<Distance>,
+    // If set, overrides the parent road's speed limit. Use Traversable::speed_limit to get the
+    // limit that applies.
+    pub speed_limit: Option<Speed>,
 }
 
 impl Lane {
@@ -168,8 +199,12 @@ impl Lane {
 
     pub fn number_parking_spots(&self) -> usize {
         assert_eq!(self.lane_type, LaneType::Parking);
+        if !self.spot_lengths.is_empty() {
+            return self.parking_spot_lengths().len();
+        }
         // No spots next to intersections
-        let spots = (self.length() / PARKING_SPOT_LENGTH).floor() - 2.0;
+        let spots =
+            ((self.length() - PARKING_SPOT_LENGTH * 2.0) / self.parking_spot_spacing()).floor();
         if spots >= 1.0 {
             spots as usize
         } else {
@@ -177,6 +212,36 @@ impl Lane {
         }
     }
 
+    // The length of each spot, in order along the lane. The first spot starts
+    // PARKING_SPOT_LENGTH from the start of the lane, and spots stop at least that far from the
+    // end.
+    pub fn parking_spot_lengths(&self) -> Vec<Distance> {
+        assert_eq!(self.lane_type, LaneType::Parking);
+        if self.spot_lengths.is_empty() {
+            return vec![self.parking_spot_spacing(); self.number_parking_spots()];
+        }
+        let mut lengths = Vec::new();
+        let mut front = PARKING_SPOT_LENGTH;
+        for length in &self.spot_lengths {
+            front += *length;
+            if front > self.length() - PARKING_SPOT_LENGTH {
+                break;
+            }
+            lengths.push(*length);
+        }
+        lengths
+    }
+
+    // How much of the lane each spot takes up by default. Angled and perpendicular spots are only
+    // as wide as a car, measured along the curb.
+    pub fn parking_spot_spacing(&self) -> Distance {
+        match self.parking_orientation {
+            OnstreetOrientation::Parallel => PARKING_SPOT_LENGTH,
+            OnstreetOrientation::Angled => PARKING_LOT_SPOT_WIDTH * std::f64::consts::SQRT_2,
+            OnstreetOrientation::Perpendicular => PARKING_LOT_SPOT_WIDTH,
+        }
+    }
+
     pub fn is_driving(&self) -> bool {
         self.lane_type == LaneType::Driving
     }
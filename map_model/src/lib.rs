@@ -26,10 +26,13 @@ pub use crate::edits::{
     EditCmd, EditEffects, EditIntersection, MapEdits, OriginalLane, PermanentMapEdits,
 };
 pub use crate::intersection::{Intersection, IntersectionID, IntersectionType};
-pub use crate::lane::{Lane, LaneID, LaneType, PARKING_LOT_SPOT_LENGTH, PARKING_SPOT_LENGTH};
+pub use crate::lane::{
+    Lane, LaneID, LaneType, OnstreetOrientation, PARKING_LOT_SPOT_LENGTH, PARKING_LOT_SPOT_WIDTH,
+    PARKING_SPOT_LENGTH,
+};
 pub use crate::make::initial::lane_specs::RoadSpec;
 pub use crate::map::Map;
-pub use crate::parking_lot::{ParkingLot, ParkingLotID};
+pub use crate::parking_lot::{ParkingLot, ParkingLotID, SpotOrientation};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn, UberTurnGroup};
 pub use crate::pathfind::{Path, PathConstraints, PathRequest, PathStep};
 pub use crate::road::{DirectedRoadID, Road, RoadID};
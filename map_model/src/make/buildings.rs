@@ -2,7 +2,8 @@ use crate::make::sidewalk_finder::find_sidewalk_points;
 use crate::raw::{OriginalBuilding, RawBuilding, RawParkingLot};
 use crate::{
     osm, Building, BuildingID, FrontPath, LaneID, LaneType, Map, OffstreetParking, ParkingLot,
-    ParkingLotID, Position, NORMAL_LANE_THICKNESS, PARKING_LOT_SPOT_LENGTH,
+    ParkingLotID, Position, SpotOrientation, NORMAL_LANE_THICKNESS, PARKING_LOT_SPOT_LENGTH,
+    PARKING_LOT_SPOT_WIDTH,
 };
 use abstutil::Timer;
 use geom::{Angle, Distance, HashablePt2D, Line, PolyLine, Polygon, Pt2D, Ring};
@@ -182,6 +183,8 @@ pub fn make_all_parking_lots(
                     aisles: Vec::new(),
                     osm_id: orig.osm_id,
                     spots: Vec::new(),
+                    num_disabled_spots: orig.num_disabled_spots,
+                    cost_per_hour: orig.cost_per_hour,
 
                     driveway_line,
                     driving_pos,
@@ -257,7 +260,11 @@ fn get_address(tags: &BTreeMap<String, String>, sidewalk: LaneID, map: &Map) ->
     }
 }
 
-fn infer_spots(lot_polygon: &Polygon, aisles: &Vec<Vec<Pt2D>>) -> Vec<(Pt2D, Angle)> {
+// Only perpendicular stalls are inferred so far.
+fn infer_spots(
+    lot_polygon: &Polygon,
+    aisles: &Vec<Vec<Pt2D>>,
+) -> Vec<(Pt2D, Angle, SpotOrientation)> {
     let mut spots = Vec::new();
     let mut finalized_lines = Vec::new();
 
@@ -270,9 +277,9 @@ fn infer_spots(lot_polygon: &Polygon, aisles: &Vec<Vec<Pt2D>>) -> Vec<(Pt2D, Ang
             let lines = {
                 let mut lines = Vec::new();
                 let mut start = Distance::ZERO;
-                while start + NORMAL_LANE_THICKNESS < pl.length() {
+                while start + PARKING_LOT_SPOT_WIDTH < pl.length() {
                     let (pt, angle) = pl.dist_along(start);
-                    start += NORMAL_LANE_THICKNESS;
+                    start += PARKING_LOT_SPOT_WIDTH;
                     let theta = angle.rotate_degs(rotate);
                     lines.push(Line::new(
                         pt.project_away(aisle_thickness / 2.0, theta),
@@ -293,7 +300,11 @@ fn infer_spots(lot_polygon: &Polygon, aisles: &Vec<Vec<Pt2D>>) -> Vec<(Pt2D, Ang
                     && line_valid(lot_polygon, aisles, &back, &finalized_lines)
                 {
                     let avg_angle = (l1.angle() + l2.angle()) / 2.0;
-                    spots.push((back.middle(), avg_angle.opposite()));
+                    spots.push((
+                        back.middle(),
+                        avg_angle.opposite(),
+                        SpotOrientation::Perpendicular,
+                    ));
                     finalized_lines.push(l1.clone());
                     finalized_lines.push(l2.clone());
                     finalized_lines.push(back);
@@ -156,7 +156,7 @@ pub fn get_lane_types(osm_tags: &BTreeMap<String, String>) -> (Vec<LaneType>, Ve
 }
 
 // This is a convenient way for map_editor to plumb instructions here.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct RoadSpec {
     pub fwd: Vec<LaneType>,
     pub back: Vec<LaneType>,
@@ -3,12 +3,12 @@ use crate::raw::{DrivingSide, OriginalIntersection, OriginalRoad, RawMap};
 use crate::{
     connectivity, make, osm, Area, AreaID, Building, BuildingID, BusRoute, BusRouteID, BusStop,
     BusStopID, ControlStopSign, ControlTrafficSignal, EditCmd, EditEffects, EditIntersection,
-    Intersection, IntersectionID, IntersectionType, Lane, LaneID, LaneType, MapEdits, ParkingLot,
-    ParkingLotID, Path, PathConstraints, PathRequest, Position, Road, RoadID, Turn, TurnGroupID,
-    TurnID, TurnType, NORMAL_LANE_THICKNESS, SIDEWALK_THICKNESS,
+    Intersection, IntersectionID, IntersectionType, Lane, LaneID, LaneType, MapEdits,
+    OnstreetOrientation, ParkingLot, ParkingLotID, Path, PathConstraints, PathRequest, Position,
+    Road, RoadID, Turn, TurnGroupID, TurnID, TurnType, NORMAL_LANE_THICKNESS, SIDEWALK_THICKNESS,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Error, Timer, Warn};
-use geom::{Angle, Bounds, Distance, GPSBounds, Line, PolyLine, Polygon, Pt2D, Speed};
+use geom::{Angle, Bounds, Distance, Duration, GPSBounds, Line, PolyLine, Polygon, Pt2D, Speed};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
@@ -1016,6 +1016,12 @@ fn make_half_map(
             map.intersections[src_i.0].outgoing_lanes.push(id);
             map.intersections[dst_i.0].incoming_lanes.push(id);
 
+            // Index from the center of the road, like RoadSpec
+            let idx_from_center = if lane.reverse_pts {
+                road.children_backwards.len()
+            } else {
+                road.children_forwards.len()
+            };
             let (unshifted_pts, other_lanes_width): (PolyLine, Distance) = if lane.reverse_pts {
                 let w = road.width_back(&map);
                 road.children_backwards.push((id, lane.lane_type));
@@ -1047,6 +1053,68 @@ fn make_half_map(
                 building_paths: Vec::new(),
                 bus_stops: Vec::new(),
                 parking_blackhole: None,
+                no_parking: if lane.lane_type == LaneType::Parking {
+                    no_parking_intervals(&road.osm_tags, !lane.reverse_pts, timer)
+                } else {
+                    Vec::new()
+                },
+                num_disabled_spots: if lane.lane_type == LaneType::Parking {
+                    road.osm_tags
+                        .get(if lane.reverse_pts {
+                            osm::DISABLED_PARKING_BACK
+                        } else {
+                            osm::DISABLED_PARKING_FWD
+                        })
+                        .and_then(|x| x.parse::<usize>().ok())
+                        .unwrap_or(0)
+                } else {
+                    0
+                },
+                spot_lengths: if lane.lane_type == LaneType::Parking {
+                    parking_spot_lengths(&road.osm_tags, !lane.reverse_pts, timer)
+                } else {
+                    Vec::new()
+                },
+                parking_time_limit: if lane.lane_type == LaneType::Parking {
+                    road.osm_tags
+                        .get(if lane.reverse_pts {
+                            osm::PARKING_TIME_LIMIT_BACK
+                        } else {
+                            osm::PARKING_TIME_LIMIT_FWD
+                        })
+                        .and_then(|x| x.parse::<usize>().ok())
+                        .map(Duration::minutes)
+                } else {
+                    None
+                },
+                parking_orientation: if lane.lane_type == LaneType::Parking {
+                    parking_orientation(&road.osm_tags, !lane.reverse_pts, timer)
+                } else {
+                    OnstreetOrientation::Parallel
+                },
+                parking_cost_per_hour: if lane.lane_type == LaneType::Parking {
+                    parking_cost(&road.osm_tags, !lane.reverse_pts, timer)
+                } else {
+                    None
+                },
+                parking_max_vehicle_length: if lane.lane_type == LaneType::Parking {
+                    road.osm_tags
+                        .get(if lane.reverse_pts {
+                            osm::PARKING_MAX_LENGTH_BACK
+                        } else {
+                            osm::PARKING_MAX_LENGTH_FWD
+                        })
+                        .and_then(|x| x.parse::<f64>().ok())
+                        .map(Distance::meters)
+                } else {
+                    None
+                },
+                speed_limit: lane_speed_limit(
+                    &road.osm_tags,
+                    !lane.reverse_pts,
+                    idx_from_center,
+                    timer,
+                ),
             });
         }
         if road.get_name() == "???" {
@@ -1137,6 +1205,124 @@ fn make_half_map(
     map
 }
 
+fn no_parking_intervals(
+    tags: &BTreeMap<String, String>,
+    fwd: bool,
+    timer: &mut Timer,
+) -> Vec<(Distance, Distance)> {
+    let key = if fwd {
+        osm::NO_PARKING_FWD
+    } else {
+        osm::NO_PARKING_BACK
+    };
+    let mut intervals = Vec::new();
+    if let Some(value) = tags.get(key) {
+        for interval in value.split(';') {
+            let parts = interval
+                .split('-')
+                .map(|x| x.trim().parse::<f64>())
+                .collect::<Vec<_>>();
+            match parts.as_slice() {
+                [Ok(start), Ok(end)] if start < end => {
+                    intervals.push((Distance::meters(*start), Distance::meters(*end)));
+                }
+                _ => {
+                    timer.warn(format!("Bad {} interval {}", key, interval));
+                }
+            }
+        }
+    }
+    intervals
+}
+
+fn parking_spot_lengths(
+    tags: &BTreeMap<String, String>,
+    fwd: bool,
+    timer: &mut Timer,
+) -> Vec<Distance> {
+    let key = if fwd {
+        osm::PARKING_SPOT_LENGTHS_FWD
+    } else {
+        osm::PARKING_SPOT_LENGTHS_BACK
+    };
+    let mut lengths = Vec::new();
+    if let Some(value) = tags.get(key) {
+        for length in value.split(';') {
+            match length.trim().parse::<f64>() {
+                Ok(x) if x > 0.0 => {
+                    lengths.push(Distance::meters(x));
+                }
+                _ => {
+                    timer.warn(format!("Bad {} length {}, using normal spots", key, length));
+                    return Vec::new();
+                }
+            }
+        }
+    }
+    lengths
+}
+
+fn parking_orientation(
+    tags: &BTreeMap<String, String>,
+    fwd: bool,
+    timer: &mut Timer,
+) -> OnstreetOrientation {
+    let key = if fwd {
+        osm::PARKING_ORIENTATION_FWD
+    } else {
+        osm::PARKING_ORIENTATION_BACK
+    };
+    match tags.get(key).map(|x| x.as_str()) {
+        None | Some("parallel") => OnstreetOrientation::Parallel,
+        Some("angled") => OnstreetOrientation::Angled,
+        Some("perpendicular") => OnstreetOrientation::Perpendicular,
+        Some(x) => {
+            timer.warn(format!("Bad {} {}, using parallel parking", key, x));
+            OnstreetOrientation::Parallel
+        }
+    }
+}
+
+fn parking_cost(tags: &BTreeMap<String, String>, fwd: bool, timer: &mut Timer) -> Option<f64> {
+    let key = if fwd {
+        osm::PARKING_COST_FWD
+    } else {
+        osm::PARKING_COST_BACK
+    };
+    let value = tags.get(key)?;
+    match value.parse::<f64>() {
+        Ok(x) if x >= 0.0 => Some(x),
+        _ => {
+            timer.warn(format!("Bad {} {}, parking there is free", key, value));
+            None
+        }
+    }
+}
+
+// A lane's own override beats the limit for its direction. None means use the road's limit.
+fn lane_speed_limit(
+    tags: &BTreeMap<String, String>,
+    fwd: bool,
+    idx: usize,
+    timer: &mut Timer,
+) -> Option<Speed> {
+    let lane_key = osm::lane_maxspeed_key(fwd, idx);
+    let dir_key = if fwd {
+        osm::MAXSPEED_FWD
+    } else {
+        osm::MAXSPEED_BACK
+    };
+    for key in vec![lane_key.as_str(), dir_key] {
+        if let Some(value) = tags.get(key) {
+            if let Some(speed) = osm::parse_maxspeed(value) {
+                return Some(speed);
+            }
+            timer.warn(format!("Bad {} {}, ignoring it", key, value));
+        }
+    }
+    None
+}
+
 fn is_border(intersection: &Intersection, lanes: &Vec<Lane>) -> bool {
     // RawIntersection said it is.
     if intersection.is_border() {
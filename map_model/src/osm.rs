@@ -1,10 +1,15 @@
 // These are common OSM keys. Keys used in just one or two places don't really need to be defined
 // here.
 
+use geom::Speed;
+
 // These're normal OSM keys.
 pub const NAME: &str = "name";
+pub const CAPACITY_DISABLED: &str = "capacity:disabled";
 pub const HIGHWAY: &str = "highway";
 pub const MAXSPEED: &str = "maxspeed";
+pub const MAXSPEED_FWD: &str = "maxspeed:forward";
+pub const MAXSPEED_BACK: &str = "maxspeed:backward";
 pub const PARKING_RIGHT: &str = "parking:lane:right";
 pub const PARKING_LEFT: &str = "parking:lane:left";
 pub const PARKING_BOTH: &str = "parking:lane:both";
@@ -26,7 +31,72 @@ pub const ENDPT_BACK: &str = "abst:endpt_back";
 // Synthetic roads have (some of) these.
 pub const SYNTHETIC: &str = "abst:synthetic";
 pub const SYNTHETIC_LANES: &str = "abst:synthetic_lanes";
+// Overrides MAXSPEED for one lane. See lane_maxspeed_key.
+pub const SYNTHETIC_LANE_MAXSPEED: &str = "abst:synthetic_lane_maxspeed";
+
+// Roads that make up one street, like both carriageways of a divided road, share this value.
+pub const STREET_GROUP: &str = "abst:street_group";
+
+// Stretches of a parking lane where cars can't park, like driveways and bus stops. The value is a
+// list of intervals in meters along the lane, like "10-15;40-46.5".
+pub const NO_PARKING_FWD: &str = "abst:no_parking_fwd";
+pub const NO_PARKING_BACK: &str = "abst:no_parking_back";
+// How many spots at the start of a parking lane are reserved for accessible permits
+pub const DISABLED_PARKING_FWD: &str = "abst:disabled_parking_fwd";
+pub const DISABLED_PARKING_BACK: &str = "abst:disabled_parking_back";
+// The longest a car may stay in a parking lane, in minutes
+pub const PARKING_TIME_LIMIT_FWD: &str = "abst:parking_time_limit_fwd";
+pub const PARKING_TIME_LIMIT_BACK: &str = "abst:parking_time_limit_back";
+// Lengths in meters of each spot along a parking lane, like "5;5;6.5", for lanes mixing compact
+// and standard spots
+pub const PARKING_SPOT_LENGTHS_FWD: &str = "abst:parking_spot_lengths_fwd";
+pub const PARKING_SPOT_LENGTHS_BACK: &str = "abst:parking_spot_lengths_back";
+// How cars line up in a parking lane: "parallel", "angled", or "perpendicular"
+pub const PARKING_ORIENTATION_FWD: &str = "abst:parking_orientation_fwd";
+pub const PARKING_ORIENTATION_BACK: &str = "abst:parking_orientation_back";
+// Hourly price of a parking lane in dollars, like "2.5". Lanes without this are free.
+pub const PARKING_COST_FWD: &str = "abst:parking_cost_fwd";
+pub const PARKING_COST_BACK: &str = "abst:parking_cost_back";
+// The longest vehicle in meters that fits in a parking lane's spots, like "6"
+pub const PARKING_MAX_LENGTH_FWD: &str = "abst:parking_max_length_fwd";
+pub const PARKING_MAX_LENGTH_BACK: &str = "abst:parking_max_length_back";
 
 // Any roads might have these.
 pub const INFERRED_PARKING: &str = "abst:parking_inferred";
 pub const INFERRED_SIDEWALKS: &str = "abst:sidewalks_inferred";
+
+// Only understands values like "25 mph" so far.
+pub fn parse_maxspeed(value: &str) -> Option<Speed> {
+    // TODO handle other units
+    if value.ends_with(" mph") {
+        if let Ok(mph) = value[0..value.len() - 4].parse::<f64>() {
+            return Some(Speed::miles_per_hour(mph));
+        }
+    }
+    None
+}
+
+// Only understands hourly values like "2.50 USD/hour" or "$2/hour" so far. Returns dollars.
+pub fn parse_hourly_charge(value: &str) -> Option<f64> {
+    // TODO handle other periods
+    if value.ends_with("/hour") {
+        let amount: String = value[0..value.len() - 5]
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == '.')
+            .collect();
+        if let Ok(dollars) = amount.parse::<f64>() {
+            return Some(dollars);
+        }
+    }
+    None
+}
+
+// Lanes are indexed from the center of the road, the same way as the lists in RoadSpec.
+pub fn lane_maxspeed_key(fwd: bool, idx: usize) -> String {
+    format!(
+        "{}:{}:{}",
+        SYNTHETIC_LANE_MAXSPEED,
+        if fwd { "fwd" } else { "back" },
+        idx
+    )
+}
@@ -21,6 +21,15 @@ impl fmt::Display for ParkingLotID {
     }
 }
 
+// How a stall is laid out relative to its aisle
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SpotOrientation {
+    // The car pulls straight in, across the aisle
+    Perpendicular,
+    // The car lines up along the aisle
+    Parallel,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ParkingLot {
     pub id: ParkingLotID,
@@ -28,7 +37,11 @@ pub struct ParkingLot {
     pub aisles: Vec<Vec<Pt2D>>,
     pub osm_id: i64,
     // The middle of the "T", pointing towards the parking aisle
-    pub spots: Vec<(Pt2D, Angle)>,
+    pub spots: Vec<(Pt2D, Angle, SpotOrientation)>,
+    // The first this many spots are reserved for accessible permits
+    pub num_disabled_spots: usize,
+    // Hourly price in dollars, if it isn't free
+    pub cost_per_hour: Option<f64>,
 
     // Goes from the lot to the driving lane
     pub driveway_line: PolyLine,
@@ -1,6 +1,8 @@
 use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
 use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
-use crate::{Lane, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, Turn, TurnID};
+use crate::{
+    Lane, LaneID, Map, Path, PathConstraints, PathRequest, PathStep, Traversable, Turn, TurnID,
+};
 use abstutil::MultiMap;
 use fast_paths::{deserialize_32, serialize_32, FastGraph, InputGraph, PathCalculator};
 use serde::{Deserialize, Serialize};
@@ -211,8 +213,8 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
     match constraints {
         PathConstraints::Car => {
             // Prefer slightly longer route on faster roads
-            let t1 = lane.length() / map.get_r(lane.parent).speed_limit;
-            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).speed_limit;
+            let t1 = lane.length() / Traversable::Lane(lane.id).speed_limit(map);
+            let t2 = turn.geom.length() / Traversable::Turn(turn.id).speed_limit(map);
             (t1 + t2).inner_seconds().round() as usize
         }
         PathConstraints::Bike => {
@@ -238,8 +240,8 @@ pub fn cost(lane: &Lane, turn: &Turn, constraints: PathConstraints, map: &Map) -
         }
         PathConstraints::Bus => {
             // Like Car, but prefer bus lanes.
-            let t1 = lane.length() / map.get_r(lane.parent).speed_limit;
-            let t2 = turn.geom.length() / map.get_parent(turn.id.dst).speed_limit;
+            let t1 = lane.length() / Traversable::Lane(lane.id).speed_limit(map);
+            let t2 = turn.geom.length() / Traversable::Turn(turn.id).speed_limit(map);
             let lt_penalty = if lane.is_bus() {
                 1.0
             } else {
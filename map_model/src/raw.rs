@@ -1,11 +1,12 @@
 use crate::make::initial::lane_specs::get_lane_types;
-use crate::{osm, AreaType, IntersectionType, RoadSpec};
+use crate::{osm, AreaType, IntersectionType, LaneType, RoadSpec};
 use abstutil::{deserialize_btreemap, serialize_btreemap, Timer, Warn};
 use geom::{Angle, Distance, GPSBounds, Line, PolyLine, Polygon, Pt2D};
 use gtfs::Route;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
+use std::mem;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RawMap {
@@ -149,6 +150,165 @@ impl RawMap {
         }
     }
 
+    // Every intersection a car could drive to from start, respecting one-way roads.
+    pub fn reachable_from(&self, start: OriginalIntersection) -> BTreeSet<OriginalIntersection> {
+        let mut next: BTreeMap<OriginalIntersection, Vec<OriginalIntersection>> = BTreeMap::new();
+        for (id, r) in &self.roads {
+            let spec = r.get_spec();
+            if spec.fwd.contains(&LaneType::Driving) {
+                next.entry(id.i1).or_insert_with(Vec::new).push(id.i2);
+            }
+            if spec.back.contains(&LaneType::Driving) {
+                next.entry(id.i2).or_insert_with(Vec::new).push(id.i1);
+            }
+        }
+
+        let mut visited = BTreeSet::new();
+        let mut queue = vec![start];
+        while let Some(i) = queue.pop() {
+            if visited.contains(&i) {
+                continue;
+            }
+            visited.insert(i);
+            if let Some(list) = next.get(&i) {
+                queue.extend(list.iter().cloned());
+            }
+        }
+        visited
+    }
+
+    // Groups roads connected through shared intersections, ignoring direction. Returns every
+    // group except for the largest one, biggest first.
+    pub fn disconnected_components(&self) -> Vec<BTreeSet<OriginalRoad>> {
+        // Union-find over intersections
+        let mut parent: BTreeMap<OriginalIntersection, OriginalIntersection> = BTreeMap::new();
+        fn find(
+            parent: &mut BTreeMap<OriginalIntersection, OriginalIntersection>,
+            i: OriginalIntersection,
+        ) -> OriginalIntersection {
+            let p = *parent.entry(i).or_insert(i);
+            if p == i {
+                return i;
+            }
+            let root = find(parent, p);
+            parent.insert(i, root);
+            root
+        }
+        for id in self.roads.keys() {
+            let root1 = find(&mut parent, id.i1);
+            let root2 = find(&mut parent, id.i2);
+            if root1 != root2 {
+                parent.insert(root1, root2);
+            }
+        }
+
+        let mut components: BTreeMap<OriginalIntersection, BTreeSet<OriginalRoad>> =
+            BTreeMap::new();
+        for id in self.roads.keys() {
+            components
+                .entry(find(&mut parent, id.i1))
+                .or_insert_with(BTreeSet::new)
+                .insert(*id);
+        }
+        let mut components: Vec<BTreeSet<OriginalRoad>> =
+            components.into_iter().map(|(_, c)| c).collect();
+        // Stable sort, so ties are deterministic
+        components.sort_by_key(|c| std::cmp::Reverse(c.len()));
+        if !components.is_empty() {
+            components.remove(0);
+        }
+        components
+    }
+
+    // Intersections with only one road that aren't borders. Cars can't go anywhere from these.
+    pub fn dead_ends(&self) -> Vec<OriginalIntersection> {
+        self.intersections
+            .iter()
+            .filter(|(id, i)| {
+                i.intersection_type != IntersectionType::Border
+                    && self.roads_per_intersection(**id).len() == 1
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // Renumber intersections and ways to be contiguous from 0, fixing up every reference. Roads
+    // split from the same way keep sharing an ID. This destroys any relation to OSM, so it's only
+    // meant for synthetic maps right before exporting.
+    pub fn compact_ids(&mut self) {
+        let mut nodes: BTreeMap<OriginalIntersection, OriginalIntersection> = BTreeMap::new();
+        for id in self.intersections.keys() {
+            nodes.insert(
+                *id,
+                OriginalIntersection {
+                    osm_node_id: nodes.len() as i64,
+                },
+            );
+        }
+
+        // Ways share one ID space between roads, buildings, areas, and parking lots.
+        let mut ways: BTreeMap<i64, i64> = BTreeMap::new();
+        let old_ways = self
+            .roads
+            .keys()
+            .map(|r| r.osm_way_id)
+            .chain(self.buildings.keys().map(|b| b.osm_way_id))
+            .chain(self.areas.iter().map(|a| a.osm_id))
+            .chain(self.parking_lots.iter().map(|p| p.osm_id))
+            .collect::<Vec<_>>();
+        for id in old_ways {
+            if !ways.contains_key(&id) {
+                let new_id = ways.len() as i64;
+                ways.insert(id, new_id);
+            }
+        }
+
+        let fix_road = |r: &OriginalRoad| OriginalRoad {
+            osm_way_id: ways[&r.osm_way_id],
+            i1: nodes[&r.i1],
+            i2: nodes[&r.i2],
+        };
+        self.roads = mem::replace(&mut self.roads, BTreeMap::new())
+            .into_iter()
+            .map(|(id, mut r)| {
+                for (_, to) in &mut r.turn_restrictions {
+                    *to = fix_road(to);
+                }
+                for (via, to) in &mut r.complicated_turn_restrictions {
+                    *via = fix_road(via);
+                    *to = fix_road(to);
+                }
+                let new_id = fix_road(&id);
+                if r.osm_tags.contains_key(osm::OSM_WAY_ID) {
+                    r.osm_tags
+                        .insert(osm::OSM_WAY_ID.to_string(), new_id.osm_way_id.to_string());
+                }
+                (new_id, r)
+            })
+            .collect();
+        self.intersections = mem::replace(&mut self.intersections, BTreeMap::new())
+            .into_iter()
+            .map(|(id, i)| (nodes[&id], i))
+            .collect();
+        self.buildings = mem::replace(&mut self.buildings, BTreeMap::new())
+            .into_iter()
+            .map(|(id, b)| {
+                (
+                    OriginalBuilding {
+                        osm_way_id: ways[&id.osm_way_id],
+                    },
+                    b,
+                )
+            })
+            .collect();
+        for a in &mut self.areas {
+            a.osm_id = ways[&a.osm_id];
+        }
+        for p in &mut self.parking_lots {
+            p.osm_id = ways[&p.osm_id];
+        }
+    }
+
     // (Intersection polygon, polygons for roads, list of labeled polylines to debug)
     pub fn preview_intersection(
         &self,
@@ -293,6 +453,10 @@ pub struct RawRoad {
     pub turn_restrictions: Vec<(RestrictionType, OriginalRoad)>,
     // (via, to). For turn restrictions where 'via' is an entire road. Only BanTurns.
     pub complicated_turn_restrictions: Vec<(OriginalRoad, OriginalRoad)>,
+    // Percent grade of each segment between center_points, positive when going uphill from the
+    // first point. Empty means no elevation data, so the road is treated as flat.
+    #[serde(default)]
+    pub grades: Vec<f64>,
 }
 
 impl RawRoad {
@@ -304,6 +468,16 @@ impl RawRoad {
     pub fn synthetic(&self) -> bool {
         self.osm_tags.get(osm::SYNTHETIC) == Some(&"true".to_string())
     }
+
+    pub fn set_grade(&mut self, grades: Vec<f64>) {
+        assert_eq!(grades.len(), self.center_points.len() - 1);
+        self.grades = grades;
+    }
+
+    // Steepest grade in either direction, as a percent. 0 if there's no elevation data.
+    pub fn max_grade(&self) -> f64 {
+        self.grades.iter().fold(0.0, |max, g| max.max(g.abs()))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -337,6 +511,12 @@ pub struct RawArea {
 pub struct RawParkingLot {
     pub polygon: Polygon,
     pub osm_id: i64,
+    // Spots reserved for accessible permits
+    #[serde(default)]
+    pub num_disabled_spots: usize,
+    // Hourly price in dollars, if it isn't free
+    #[serde(default)]
+    pub cost_per_hour: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -415,3 +595,192 @@ impl DrivingSide {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn road(i1: i64, i2: i64) -> RawRoad {
+        RawRoad {
+            center_points: vec![Pt2D::new(i1 as f64, 0.0), Pt2D::new(i2 as f64, 0.0)],
+            osm_tags: BTreeMap::new(),
+            turn_restrictions: Vec::new(),
+            complicated_turn_restrictions: Vec::new(),
+            grades: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_one_way_cul_de_sac() {
+        // 0 <-> 1 -> 2, with only a one-way road into the cul-de-sac at 2.
+        let mut map = RawMap::blank("test", "test");
+        for id in 0..3 {
+            map.intersections.insert(
+                OriginalIntersection { osm_node_id: id },
+                RawIntersection {
+                    point: Pt2D::new(id as f64, 0.0),
+                    intersection_type: IntersectionType::StopSign,
+                    elevation: Distance::ZERO,
+                },
+            );
+        }
+        let mut two_way = road(0, 1);
+        two_way
+            .osm_tags
+            .insert(osm::SYNTHETIC_LANES.to_string(), "d/d".to_string());
+        let mut one_way = road(1, 2);
+        one_way
+            .osm_tags
+            .insert(osm::SYNTHETIC_LANES.to_string(), "d/".to_string());
+        for (id, (i1, i2), r) in vec![(1, (0, 1), two_way), (2, (1, 2), one_way)] {
+            map.roads.insert(
+                OriginalRoad {
+                    osm_way_id: id,
+                    i1: OriginalIntersection { osm_node_id: i1 },
+                    i2: OriginalIntersection { osm_node_id: i2 },
+                },
+                r,
+            );
+        }
+        let i = |id| OriginalIntersection { osm_node_id: id };
+
+        assert_eq!(
+            vec![i(0), i(1), i(2)],
+            map.reachable_from(i(0)).into_iter().collect::<Vec<_>>()
+        );
+        // Cars can get into the cul-de-sac, but never out.
+        assert_eq!(
+            vec![i(2)],
+            map.reachable_from(i(2)).into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_disconnected_components() {
+        let mut map = RawMap::blank("test", "test");
+        for id in 0..5 {
+            map.intersections.insert(
+                OriginalIntersection { osm_node_id: id },
+                RawIntersection {
+                    point: Pt2D::new(id as f64, 0.0),
+                    intersection_type: if id == 0 {
+                        IntersectionType::Border
+                    } else {
+                        IntersectionType::StopSign
+                    },
+                    elevation: Distance::ZERO,
+                },
+            );
+        }
+        // 0 - 1 - 2 is the main network, and 3 - 4 is an island.
+        let mut ids = Vec::new();
+        for (way, i1, i2) in vec![(1, 0, 1), (2, 1, 2), (3, 3, 4)] {
+            let id = OriginalRoad {
+                osm_way_id: way,
+                i1: OriginalIntersection { osm_node_id: i1 },
+                i2: OriginalIntersection { osm_node_id: i2 },
+            };
+            map.roads.insert(id, road(i1, i2));
+            ids.push(id);
+        }
+
+        assert_eq!(
+            vec![vec![ids[2]].into_iter().collect::<BTreeSet<_>>()],
+            map.disconnected_components()
+        );
+        assert_eq!(
+            vec![2, 3, 4],
+            map.dead_ends()
+                .into_iter()
+                .map(|i| i.osm_node_id)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_compact_ids() {
+        let mut map = RawMap::blank("test", "test");
+        for id in vec![-50, 7, 300] {
+            map.intersections.insert(
+                OriginalIntersection { osm_node_id: id },
+                RawIntersection {
+                    point: Pt2D::new(id as f64, 0.0),
+                    intersection_type: IntersectionType::StopSign,
+                    elevation: Distance::ZERO,
+                },
+            );
+        }
+        let r1 = OriginalRoad {
+            osm_way_id: 900,
+            i1: OriginalIntersection { osm_node_id: -50 },
+            i2: OriginalIntersection { osm_node_id: 7 },
+        };
+        // Split from the same way as r1
+        let r2 = OriginalRoad {
+            osm_way_id: 900,
+            i1: OriginalIntersection { osm_node_id: 7 },
+            i2: OriginalIntersection { osm_node_id: 300 },
+        };
+        let mut road1 = road(-50, 7);
+        road1
+            .osm_tags
+            .insert(osm::OSM_WAY_ID.to_string(), "900".to_string());
+        road1
+            .turn_restrictions
+            .push((RestrictionType::BanTurns, r2));
+        map.roads.insert(r1, road1);
+        map.roads.insert(r2, road(7, 300));
+        map.buildings.insert(
+            OriginalBuilding { osm_way_id: -12 },
+            RawBuilding {
+                polygon: Polygon::rectangle(1.0, 1.0),
+                osm_tags: BTreeMap::new(),
+                public_garage_name: None,
+                num_parking_spots: 0,
+                amenities: BTreeSet::new(),
+            },
+        );
+
+        map.compact_ids();
+
+        assert_eq!(
+            vec![0, 1, 2],
+            map.intersections
+                .keys()
+                .map(|i| i.osm_node_id)
+                .collect::<Vec<_>>()
+        );
+        // Intersections keep their relative order, so positions still line up.
+        assert_eq!(
+            Pt2D::new(7.0, 0.0),
+            map.intersections[&OriginalIntersection { osm_node_id: 1 }].point
+        );
+        let new_r1 = OriginalRoad {
+            osm_way_id: 0,
+            i1: OriginalIntersection { osm_node_id: 0 },
+            i2: OriginalIntersection { osm_node_id: 1 },
+        };
+        let new_r2 = OriginalRoad {
+            osm_way_id: 0,
+            i1: OriginalIntersection { osm_node_id: 1 },
+            i2: OriginalIntersection { osm_node_id: 2 },
+        };
+        assert_eq!(
+            vec![new_r1, new_r2],
+            map.roads.keys().cloned().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![(RestrictionType::BanTurns, new_r2)],
+            map.roads[&new_r1].turn_restrictions
+        );
+        assert_eq!("0", map.roads[&new_r1].osm_tags[osm::OSM_WAY_ID]);
+        assert_eq!(
+            vec![OriginalBuilding { osm_way_id: 1 }],
+            map.buildings.keys().cloned().collect::<Vec<_>>()
+        );
+        for r in map.roads.keys() {
+            assert!(map.intersections.contains_key(&r.i1));
+            assert!(map.intersections.contains_key(&r.i2));
+        }
+    }
+}
@@ -208,13 +208,22 @@ impl Road {
     }
 
     pub(crate) fn speed_limit_from_osm(&self) -> Speed {
-        if let Some(limit) = self.osm_tags.get(osm::MAXSPEED) {
-            // TODO handle other units
-            if limit.ends_with(" mph") {
-                if let Ok(mph) = limit[0..limit.len() - 4].parse::<f64>() {
-                    return Speed::miles_per_hour(mph);
-                }
-            }
+        if let Some(limit) = self
+            .osm_tags
+            .get(osm::MAXSPEED)
+            .and_then(|x| osm::parse_maxspeed(x))
+        {
+            return limit;
+        }
+        // Lanes pick up their direction's limit, so the road just reports the faster one.
+        if let Some(limit) = vec![osm::MAXSPEED_FWD, osm::MAXSPEED_BACK]
+            .into_iter()
+            .filter_map(|key| self.osm_tags.get(key).and_then(|x| osm::parse_maxspeed(x)))
+            .fold(None, |max: Option<Speed>, x| {
+                Some(max.map_or(x, |m| m.max(x)))
+            })
+        {
+            return limit;
         }
 
         if self.osm_tags.get(osm::HIGHWAY) == Some(&"primary".to_string())
@@ -138,8 +138,11 @@ impl Traversable {
 
     pub fn speed_limit(&self, map: &Map) -> Speed {
         match *self {
-            Traversable::Lane(id) => map.get_parent(id).speed_limit,
-            Traversable::Turn(id) => map.get_parent(id.dst).speed_limit,
+            Traversable::Lane(id) => map
+                .get_l(id)
+                .speed_limit
+                .unwrap_or(map.get_parent(id).speed_limit),
+            Traversable::Turn(id) => Traversable::Lane(id.dst).speed_limit(map),
         }
     }
 
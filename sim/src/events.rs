@@ -12,8 +12,16 @@ use serde::{Deserialize, Serialize};
 // has no performance impact.
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Event {
+    ParkingSpotReserved(CarID, ParkingSpot),
     CarReachedParkingSpot(CarID, ParkingSpot),
     CarLeftParkingSpot(CarID, ParkingSpot),
+    // While parking or unparking, a car blocks this driving lane
+    ParkingManeuverStarted(CarID, LaneID),
+    ParkingManeuverFinished(CarID, LaneID),
+    // A car has stayed longer than its spot's time limit. Only emitted once per stay.
+    ParkingOverstay(CarID, ParkingSpot),
+    // Nobody showed up to a reserved spot in time, so it's free again
+    ParkingReservationExpired(CarID, ParkingSpot),
 
     BusArrivedAtStop(CarID, BusRouteID, BusStopID),
     BusDepartedFromStop(CarID, BusRouteID, BusStopID),
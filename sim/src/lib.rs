@@ -18,8 +18,9 @@ pub use self::make::{
     ScenarioGenerator, SimFlags, SpawnOverTime, SpawnTrip, TripSpawner, TripSpec,
 };
 pub(crate) use self::mechanics::{
-    DrivingSimState, IntersectionSimState, ParkingSimState, WalkingSimState,
+    DrivingSimState, EntryManeuver, IntersectionSimState, ParkingSimState, WalkingSimState,
 };
+pub use self::mechanics::{ParkingSnapshot, ParkingStats, VehicleClasses};
 pub(crate) use self::pandemic::PandemicModel;
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
@@ -33,7 +34,7 @@ pub use crate::render::{
     PedCrowdLocation, UnzoomedAgent,
 };
 use abstutil::Cloneable;
-use geom::{Distance, Pt2D, Speed, Time};
+use geom::{Distance, Duration, Pt2D, Speed, Time};
 use map_model::{
     BuildingID, BusStopID, DirectedRoadID, IntersectionID, LaneID, Map, ParkingLotID, Path,
     PathConstraints, PathRequest, Position,
@@ -52,6 +53,19 @@ pub const BUS_LENGTH: Distance = Distance::const_meters(12.5);
 // At all speeds (including at rest), cars must be at least this far apart, measured from front of
 // one car to the back of the other.
 pub const FOLLOWING_DISTANCE: Distance = Distance::const_meters(1.0);
+// Moving cars also keep this much time between them, like the two-second rule.
+const FOLLOWING_TIME_GAP: Duration = Duration::const_seconds(2.0);
+
+// How far back a car going this fast stays from the one ahead of it
+pub fn following_distance(speed: Speed) -> Distance {
+    FOLLOWING_DISTANCE + speed.max(Speed::ZERO) * FOLLOWING_TIME_GAP
+}
+
+// Drivers won't go farther than this looking for a free parking spot.
+pub const MAX_PARKING_SEARCH_DIST: Distance = Distance::const_meters(10_000.0);
+// Drivers with a parking budget will go this much farther, driving or walking, to save a dollar
+// per hour.
+pub const PARKING_DIST_PER_DOLLAR: Distance = Distance::const_meters(200.0);
 
 // The numeric ID must be globally unique, without considering VehicleType. VehicleType is bundled
 // for convenient debugging.
@@ -65,6 +79,7 @@ impl fmt::Display for CarID {
             VehicleType::Car => write!(f, "Car #{}", self.0),
             VehicleType::Bus => write!(f, "Bus #{}", self.0),
             VehicleType::Bike => write!(f, "Bike #{}", self.0),
+            VehicleType::Motorcycle => write!(f, "Motorcycle #{}", self.0),
         }
     }
 }
@@ -130,6 +145,8 @@ pub enum VehicleType {
     Car,
     Bus,
     Bike,
+    // Drives like a car, but two can share one on-street parking spot
+    Motorcycle,
 }
 
 impl fmt::Display for VehicleType {
@@ -138,6 +155,7 @@ impl fmt::Display for VehicleType {
             VehicleType::Car => write!(f, "car"),
             VehicleType::Bus => write!(f, "bus"),
             VehicleType::Bike => write!(f, "bike"),
+            VehicleType::Motorcycle => write!(f, "motorcycle"),
         }
     }
 }
@@ -148,6 +166,7 @@ impl VehicleType {
             VehicleType::Car => PathConstraints::Car,
             VehicleType::Bus => PathConstraints::Bus,
             VehicleType::Bike => PathConstraints::Bike,
+            VehicleType::Motorcycle => PathConstraints::Car,
         }
     }
 }
@@ -159,6 +178,10 @@ pub struct Vehicle {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    // Has a permit to use accessible parking spots
+    pub accessible: bool,
+    // Won't park anywhere that costs more than this per hour
+    pub parking_budget: Option<Money>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -166,6 +189,8 @@ pub struct VehicleSpec {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    pub accessible: bool,
+    pub parking_budget: Option<Money>,
 }
 
 impl VehicleSpec {
@@ -177,6 +202,8 @@ impl VehicleSpec {
             vehicle_type: self.vehicle_type,
             length: self.length,
             max_speed: self.max_speed,
+            accessible: self.accessible,
+            parking_budget: self.parking_budget,
         }
     }
 }
@@ -190,6 +217,27 @@ pub enum ParkingSpot {
     Lot(ParkingLotID, usize),
 }
 
+// Stored in cents to avoid rounding problems
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Money(pub usize);
+
+impl Money {
+    pub fn dollars(x: f64) -> Money {
+        assert!(x >= 0.0);
+        Money((x * 100.0).round() as usize)
+    }
+
+    pub fn inner_dollars(self) -> f64 {
+        (self.0 as f64) / 100.0
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${}.{:02}", self.0 / 100, self.0 % 100)
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ParkedCar {
     pub vehicle: Vehicle,
@@ -496,6 +544,9 @@ pub struct CreateCar {
     pub maybe_parked_car: Option<ParkedCar>,
     // None for buses
     pub trip_and_person: Option<(TripID, PersonID)>,
+    // When the parked car first couldn't leave, because it's stuck behind others in a stacked
+    // garage
+    pub blocked_since: Option<Time>,
 }
 
 impl CreateCar {
@@ -514,6 +565,7 @@ impl CreateCar {
             start_dist: start_pos.dist_along(),
             maybe_parked_car: None,
             trip_and_person: Some((trip, person)),
+            blocked_since: None,
         }
     }
 
@@ -533,6 +585,7 @@ impl CreateCar {
             start_dist,
             maybe_parked_car: Some(parked_car),
             trip_and_person: Some((trip, person)),
+            blocked_since: None,
         }
     }
 }
@@ -543,3 +596,21 @@ impl Cloneable for CarID {}
 impl Cloneable for Scenario {}
 impl Cloneable for TripID {}
 impl Cloneable for TripMode {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_following_distance() {
+        assert_eq!(FOLLOWING_DISTANCE, following_distance(Speed::ZERO));
+        assert_eq!(
+            Distance::meters(23.352),
+            following_distance(Speed::miles_per_hour(25.0))
+        );
+        assert_eq!(
+            Distance::meters(54.6448),
+            following_distance(Speed::miles_per_hour(60.0))
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{AlertHandler, Scenario, Sim, SimOptions};
 use abstutil::CmdArgs;
+use geom::Distance;
 use map_model::{Map, MapEdits};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
@@ -47,6 +48,10 @@ impl SimFlags {
                     })
                     .unwrap_or(AlertHandler::Print),
                 pathfinding_upfront: args.enabled("--pathfinding_upfront"),
+                parked_car_buffer: args
+                    .optional_parse("--parked_car_buffer", |s| s.parse::<f64>())
+                    .map(Distance::meters)
+                    .unwrap_or(Distance::meters(0.5)),
             },
         }
     }
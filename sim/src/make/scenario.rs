@@ -158,6 +158,8 @@ impl Scenario {
             vehicle_type: VehicleType::Car,
             length,
             max_speed: None,
+            accessible: false,
+            parking_budget: None,
         }
     }
 
@@ -171,6 +173,8 @@ impl Scenario {
             vehicle_type: VehicleType::Bike,
             length: BIKE_LENGTH,
             max_speed,
+            accessible: false,
+            parking_budget: None,
         }
     }
 
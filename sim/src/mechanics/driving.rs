@@ -2,9 +2,9 @@ use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{
     ActionAtEnd, AgentID, AgentProperties, CarID, Command, CreateCar, DistanceInterval,
-    DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSimState, PersonID, Scheduler,
-    TimeInterval, TransitSimState, TripManager, UnzoomedAgent, Vehicle, WalkingSimState,
-    FOLLOWING_DISTANCE,
+    DrawCarInput, EntryManeuver, Event, IntersectionSimState, ParkedCar, ParkingSimState,
+    ParkingSpot, PersonID, Scheduler, TimeInterval, TransitSimState, TripManager, UnzoomedAgent,
+    Vehicle, WalkingSimState, FOLLOWING_DISTANCE,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::{Distance, Duration, PolyLine, Time};
@@ -14,6 +14,8 @@ use std::collections::{BTreeMap, HashSet, VecDeque};
 
 const TIME_TO_UNPARK: Duration = Duration::const_seconds(10.0);
 const TIME_TO_PARK: Duration = Duration::const_seconds(15.0);
+// Added to TIME_TO_PARK for spots that have to be reversed into
+const EXTRA_TIME_TO_BACK_IN: Duration = Duration::const_seconds(10.0);
 const TIME_TO_WAIT_AT_STOP: Duration = Duration::const_seconds(10.0);
 
 // TODO Do something else.
@@ -69,7 +71,7 @@ impl DrivingSimState {
         params: CreateCar,
         map: &Map,
         intersections: &IntersectionSimState,
-        parking: &ParkingSimState,
+        parking: &mut ParkingSimState,
         scheduler: &mut Scheduler,
     ) -> bool {
         let first_lane = params.router.head().as_lane();
@@ -95,6 +97,7 @@ impl DrivingSimState {
                 trip_and_person: params.trip_and_person,
             };
             if let Some(p) = params.maybe_parked_car {
+                parking.note_maneuver_start(car.vehicle.id, first_lane);
                 car.state = CarState::Unparking(
                     params.start_dist,
                     p.spot,
@@ -273,6 +276,7 @@ impl DrivingSimState {
                         &mut self.events,
                     );
                 }
+                parking.note_maneuver_finish(car.vehicle.id, car.router.head().as_lane());
                 car.state = car.crossing_state(front, now, map);
                 scheduler.push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
             }
@@ -461,15 +465,20 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::StartParking(spot)) => {
                         car.total_blocked_time += now - blocked_since;
+                        let time_to_park = match parking.entry_maneuver(spot, map) {
+                            EntryManeuver::PullIn => TIME_TO_PARK,
+                            EntryManeuver::BackIn => TIME_TO_PARK + EXTRA_TIME_TO_BACK_IN,
+                        };
                         car.state = CarState::Parking(
                             our_dist,
                             spot,
-                            TimeInterval::new(now, now + TIME_TO_PARK),
+                            TimeInterval::new(now, now + time_to_park),
                         );
                         // If we don't do this, then we might have another car creep up
                         // behind, see the spot free, and start parking too. This can
                         // happen with multiple lanes and certain vehicle lengths.
-                        parking.reserve_spot(spot);
+                        parking.reserve_spot(spot, car.vehicle.id, now);
+                        parking.note_maneuver_start(car.vehicle.id, car.router.head().as_lane());
                         scheduler
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                         true
@@ -541,10 +550,14 @@ impl DrivingSimState {
                 }
             }
             CarState::Parking(_, spot, _) => {
-                parking.add_parked_car(ParkedCar {
-                    vehicle: car.vehicle.clone(),
-                    spot,
-                });
+                parking.note_maneuver_finish(car.vehicle.id, car.router.head().as_lane());
+                parking.add_parked_car(
+                    ParkedCar {
+                        vehicle: car.vehicle.clone(),
+                        spot,
+                    },
+                    now,
+                );
                 trips.car_reached_parking_spot(
                     now,
                     car.vehicle.id,
@@ -564,6 +577,7 @@ impl DrivingSimState {
         c: CarID,
         now: Time,
         map: &Map,
+        parking: &mut ParkingSimState,
         scheduler: &mut Scheduler,
         intersections: &mut IntersectionSimState,
     ) -> Vehicle {
@@ -583,6 +597,13 @@ impl DrivingSimState {
         if let Some(Traversable::Turn(t)) = car.router.maybe_next() {
             intersections.cancel_request(AgentID::Car(c), t);
         }
+        // Don't leave the driving lane blocked forever
+        match car.state {
+            CarState::Parking(_, _, _) | CarState::Unparking(_, _, _) => {
+                parking.note_maneuver_finish(c, car.router.head().as_lane());
+            }
+            _ => {}
+        }
 
         self.delete_car(&mut car, dists, idx, now, map, scheduler, intersections);
         // delete_car cancels UpdateLaggyHead
@@ -951,6 +972,13 @@ impl DrivingSimState {
         self.cars[&id].router.get_path().percent_dist_crossed()
     }
 
+    // The car lost its reservation for a spot, so it shouldn't try to park there.
+    pub fn forget_parking_spot(&mut self, id: CarID, spot: ParkingSpot) {
+        if let Some(car) = self.cars.get_mut(&id) {
+            car.router.forget_parking_spot(spot);
+        }
+    }
+
     pub fn get_owner_of_car(&self, id: CarID) -> Option<PersonID> {
         let car = self.cars.get(&id)?;
         car.vehicle.owner
@@ -7,6 +7,8 @@ mod walking;
 
 pub use self::driving::DrivingSimState;
 pub use self::intersection::IntersectionSimState;
-pub use self::parking::ParkingSimState;
+pub use self::parking::{
+    EntryManeuver, ParkingSimState, ParkingSnapshot, ParkingStats, VehicleClasses,
+};
 pub use self::queue::Queue;
 pub use self::walking::WalkingSimState;
@@ -1,15 +1,23 @@
-use crate::{CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle};
+use crate::{
+    CarID, CarStatus, DrawCarInput, Event, Money, ParkedCar, ParkingSpot, PersonID, Vehicle,
+    VehicleType,
+};
 use abstutil::{
     deserialize_btreemap, deserialize_multimap, serialize_btreemap, serialize_multimap, MultiMap,
     Timer,
 };
-use geom::{Distance, PolyLine, Pt2D};
+use geom::{Angle, Distance, Duration, Line, PolyLine, Pt2D, Time};
 use map_model::{
-    BuildingID, Lane, LaneID, LaneType, Map, ParkingLotID, PathConstraints, PathStep, Position,
-    Traversable, TurnID,
+    BuildingID, Lane, LaneID, LaneType, Map, OnstreetOrientation, ParkingLotID, PathConstraints,
+    PathRequest, PathStep, Position, SpotOrientation, Traversable, TurnID,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap};
+use std::fmt;
+
+// How cars parked offstreet are laid out when drawn
+const GARAGE_COLUMNS: usize = 5;
+const GARAGE_SPOT_WIDTH: f64 = 2.5;
 
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct ParkingSimState {
@@ -23,7 +31,84 @@ pub struct ParkingSimState {
         deserialize_with = "deserialize_btreemap"
     )]
     occupants: BTreeMap<ParkingSpot, CarID>,
+    // A second motorcycle squeezed into a spot whose occupant is also a motorcycle. These spots
+    // are always in occupants too.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    shared_occupants: BTreeMap<ParkingSpot, CarID>,
     reserved_spots: BTreeSet<ParkingSpot>,
+    // The spot each car with a reservation is headed to
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    reservations: BTreeMap<CarID, ParkingSpot>,
+    // When each reserved spot was reserved
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    reserved_at: BTreeMap<ParkingSpot, Time>,
+    // Out of service, like for construction or a dumpster. Unlike reserved_spots, these stay
+    // blocked until explicitly unblocked.
+    blocked_spots: BTreeSet<ParkingSpot>,
+    // The most recent car to leave each spot, and when
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    last_vacated: BTreeMap<ParkingSpot, (CarID, Time)>,
+    // When each parked car arrived. Tracked per car, since two motorcycles sharing a spot come and
+    // go independently.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    arrivals: BTreeMap<CarID, Time>,
+    // Cars that've already had a ParkingOverstay event for their current stay
+    overstays_reported: BTreeSet<CarID>,
+    // For each spot, how many cars have left it and their total time spent parked there
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    dwell_times: BTreeMap<ParkingSpot, (usize, Duration)>,
+    // Buildings whose cars park in a stack and have to leave in the reverse order they arrived.
+    // The last car in each list is the only one that can leave.
+    stacked_offstreet: BTreeMap<BuildingID, Vec<CarID>>,
+    // Space between a parked car's body and each end of its spot, for drawing
+    parked_car_buffer: Distance,
+    // Spots reserved for one person's cars, like residential permits
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    spot_owners: BTreeMap<ParkingSpot, PersonID>,
+    // Only vehicles with an accessible permit can use these
+    disabled_spots: BTreeSet<ParkingSpot>,
+    // Hourly price of spots. Spots not listed are free.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    cost_per_hour: BTreeMap<ParkingSpot, Money>,
+    // Cars currently parking or unparking, keyed by the driving lane they're blocking
+    #[serde(
+        serialize_with = "serialize_multimap",
+        deserialize_with = "deserialize_multimap"
+    )]
+    active_maneuvers: MultiMap<LaneID, CarID>,
+    // The same cars, to quickly check one. Reservations held by these can't be taken away.
+    maneuvering: BTreeSet<CarID>,
+    // Maintained as spots change, so free_fraction doesn't have to look at every spot
+    total_spots: usize,
+    // Occupied, reserved, or blocked
+    unavailable_spots: usize,
+    // Spots that exist in the map, but aren't represented anywhere else here, because they're
+    // only reachable from a parking blackhole
+    blackholed_spots: Vec<ParkingSpot>,
 
     // On-street
     onstreet_lanes: BTreeMap<LaneID, ParkingLane>,
@@ -36,6 +121,8 @@ pub struct ParkingSimState {
 
     // Off-street
     num_spots_per_offstreet: BTreeMap<BuildingID, usize>,
+    // Garages not listed allow every vehicle
+    offstreet_classes: BTreeMap<BuildingID, VehicleClasses>,
     #[serde(
         serialize_with = "serialize_multimap",
         deserialize_with = "deserialize_multimap"
@@ -44,6 +131,8 @@ pub struct ParkingSimState {
 
     // Parking lots
     num_spots_per_lot: BTreeMap<ParkingLotID, usize>,
+    // Lots not listed allow every vehicle
+    lot_classes: BTreeMap<ParkingLotID, VehicleClasses>,
     #[serde(
         serialize_with = "serialize_multimap",
         deserialize_with = "deserialize_multimap"
@@ -55,26 +144,67 @@ pub struct ParkingSimState {
 
 impl ParkingSimState {
     // Counterintuitive: any spots located in blackholes are just not represented here. If somebody
-    // tries to drive from a blackholed spot, they couldn't reach most places.
-    pub fn new(map: &Map, timer: &mut Timer) -> ParkingSimState {
+    // tries to drive from a blackholed spot, they couldn't reach most places. They're listed in
+    // get_blackholed_spots, though.
+    pub fn new(map: &Map, parked_car_buffer: Distance, timer: &mut Timer) -> ParkingSimState {
         let mut sim = ParkingSimState {
             parked_cars: BTreeMap::new(),
             occupants: BTreeMap::new(),
+            shared_occupants: BTreeMap::new(),
             reserved_spots: BTreeSet::new(),
+            reservations: BTreeMap::new(),
+            reserved_at: BTreeMap::new(),
+            blocked_spots: BTreeSet::new(),
+            last_vacated: BTreeMap::new(),
+            arrivals: BTreeMap::new(),
+            overstays_reported: BTreeSet::new(),
+            dwell_times: BTreeMap::new(),
+            stacked_offstreet: BTreeMap::new(),
+            parked_car_buffer,
+            spot_owners: BTreeMap::new(),
+            disabled_spots: BTreeSet::new(),
+            cost_per_hour: BTreeMap::new(),
+            active_maneuvers: MultiMap::new(),
+            maneuvering: BTreeSet::new(),
+            total_spots: 0,
+            unavailable_spots: 0,
+            blackholed_spots: Vec::new(),
 
             onstreet_lanes: BTreeMap::new(),
             driving_to_parking_lanes: MultiMap::new(),
             num_spots_per_offstreet: BTreeMap::new(),
+            offstreet_classes: BTreeMap::new(),
             driving_to_offstreet: MultiMap::new(),
             num_spots_per_lot: BTreeMap::new(),
+            lot_classes: BTreeMap::new(),
             driving_to_lots: MultiMap::new(),
 
             events: Vec::new(),
         };
         for l in map.all_lanes() {
             if let Some(lane) = ParkingLane::new(l, map, timer) {
+                sim.disabled_spots
+                    .extend(lane.spots().into_iter().take(l.num_disabled_spots));
+                if let Some(cost) = l.parking_cost_per_hour {
+                    for spot in lane.spots() {
+                        sim.cost_per_hour.insert(spot, Money::dollars(cost));
+                    }
+                }
                 sim.driving_to_parking_lanes.insert(lane.driving_lane, l.id);
                 sim.onstreet_lanes.insert(lane.parking_lane, lane);
+            } else if l.is_parking()
+                && map
+                    .get_parent(l.id)
+                    .parking_to_driving(l.id)
+                    .map(|dl| map.get_l(dl).parking_blackhole.is_some())
+                    .unwrap_or(false)
+            {
+                // Same spots a ParkingLane would have
+                let num_spots =
+                    ParkingLane::spots_avoiding(l.parking_spot_lengths(), &l.no_parking).len();
+                for idx in 0..num_spots {
+                    sim.blackholed_spots.push(ParkingSpot::Onstreet(l.id, idx));
+                }
             }
         }
         for b in map.all_buildings() {
@@ -82,6 +212,10 @@ impl ParkingSimState {
                 if map.get_l(p.driving_pos.lane()).parking_blackhole.is_none() {
                     sim.num_spots_per_offstreet.insert(b.id, p.num_spots);
                     sim.driving_to_offstreet.insert(p.driving_pos.lane(), b.id);
+                } else {
+                    for idx in 0..p.num_spots {
+                        sim.blackholed_spots.push(ParkingSpot::Offstreet(b.id, idx));
+                    }
                 }
             }
         }
@@ -92,49 +226,126 @@ impl ParkingSimState {
             }
             if map.get_l(pl.driving_pos.lane()).parking_blackhole.is_none() {
                 sim.num_spots_per_lot.insert(pl.id, pl.spots.len());
+                for idx in 0..pl.num_disabled_spots.min(pl.spots.len()) {
+                    sim.disabled_spots.insert(ParkingSpot::Lot(pl.id, idx));
+                }
+                if let Some(cost) = pl.cost_per_hour {
+                    for idx in 0..pl.spots.len() {
+                        sim.cost_per_hour
+                            .insert(ParkingSpot::Lot(pl.id, idx), Money::dollars(cost));
+                    }
+                }
                 sim.driving_to_lots.insert(pl.driving_pos.lane(), pl.id);
+            } else {
+                for idx in 0..pl.spots.len() {
+                    sim.blackholed_spots.push(ParkingSpot::Lot(pl.id, idx));
+                }
             }
         }
+        sim.total_spots = sim
+            .onstreet_lanes
+            .values()
+            .map(|pl| pl.spot_dist_along.len())
+            .sum::<usize>()
+            + sim.num_spots_per_offstreet.values().sum::<usize>()
+            + sim.num_spots_per_lot.values().sum::<usize>();
         sim
     }
 
+    // Spots that exist in the map, but can't be used because they're in a parking blackhole.
+    // These usually point to a connectivity problem in the map.
+    pub fn get_blackholed_spots(&self) -> &Vec<ParkingSpot> {
+        &self.blackholed_spots
+    }
+
+    // The count_free_* methods are the same as get_free_*, but don't allocate.
+    pub fn count_free_onstreet_spots(&self, l: LaneID) -> usize {
+        let num_spots = self
+            .onstreet_lanes
+            .get(&l)
+            .map(|lane| lane.spot_dist_along.len())
+            .unwrap_or(0);
+        (0..num_spots)
+            .filter(|idx| self.is_free(ParkingSpot::Onstreet(l, *idx)))
+            .count()
+    }
+
+    pub fn count_free_offstreet_spots(&self, b: BuildingID) -> usize {
+        (0..self.num_spots_per_offstreet.get(&b).cloned().unwrap_or(0))
+            .filter(|idx| self.is_free(ParkingSpot::Offstreet(b, *idx)))
+            .count()
+    }
+
+    pub fn count_free_lot_spots(&self, pl: ParkingLotID) -> usize {
+        (0..self.num_spots_per_lot.get(&pl).cloned().unwrap_or(0))
+            .filter(|idx| self.is_free(ParkingSpot::Lot(pl, *idx)))
+            .count()
+    }
+
     pub fn get_free_onstreet_spots(&self, l: LaneID) -> Vec<ParkingSpot> {
-        let mut spots: Vec<ParkingSpot> = Vec::new();
-        if let Some(lane) = self.onstreet_lanes.get(&l) {
-            for spot in lane.spots() {
-                if self.is_free(spot) {
-                    spots.push(spot);
-                }
-            }
-        }
-        spots
+        self.all_onstreet_spots(l)
+            .into_iter()
+            .filter(|spot| self.is_free(*spot))
+            .collect()
     }
 
     pub fn get_free_offstreet_spots(&self, b: BuildingID) -> Vec<ParkingSpot> {
-        let mut spots: Vec<ParkingSpot> = Vec::new();
-        for idx in 0..self.num_spots_per_offstreet.get(&b).cloned().unwrap_or(0) {
-            let spot = ParkingSpot::Offstreet(b, idx);
-            if self.is_free(spot) {
-                spots.push(spot);
-            }
-        }
-        spots
+        self.all_offstreet_spots(b)
+            .into_iter()
+            .filter(|spot| self.is_free(*spot))
+            .collect()
     }
 
     pub fn get_free_lot_spots(&self, pl: ParkingLotID) -> Vec<ParkingSpot> {
-        let mut spots: Vec<ParkingSpot> = Vec::new();
-        for idx in 0..self.num_spots_per_lot.get(&pl).cloned().unwrap_or(0) {
-            let spot = ParkingSpot::Lot(pl, idx);
-            if self.is_free(spot) {
-                spots.push(spot);
-            }
-        }
-        spots
+        self.all_lot_spots(pl)
+            .into_iter()
+            .filter(|spot| self.is_free(*spot))
+            .collect()
+    }
+
+    // The all_*_spots methods include every spot, free or not. Empty if the place is unknown or
+    // in a parking blackhole.
+    pub fn all_onstreet_spots(&self, l: LaneID) -> Vec<ParkingSpot> {
+        self.onstreet_lanes
+            .get(&l)
+            .map(|lane| lane.spots())
+            .unwrap_or_else(Vec::new)
+    }
+
+    pub fn all_offstreet_spots(&self, b: BuildingID) -> Vec<ParkingSpot> {
+        (0..self.num_spots_per_offstreet.get(&b).cloned().unwrap_or(0))
+            .map(|idx| ParkingSpot::Offstreet(b, idx))
+            .collect()
+    }
+
+    pub fn all_lot_spots(&self, pl: ParkingLotID) -> Vec<ParkingSpot> {
+        (0..self.num_spots_per_lot.get(&pl).cloned().unwrap_or(0))
+            .map(|idx| ParkingSpot::Lot(pl, idx))
+            .collect()
     }
 
-    pub fn reserve_spot(&mut self, spot: ParkingSpot) {
-        assert!(self.is_free(spot));
+    pub fn reserve_spot(&mut self, spot: ParkingSpot, car: CarID, now: Time) {
+        // A car only holds one reservation at a time. Reserving again, even the same spot when the
+        // car finally pulls in, gives up the old one.
+        if let Some(old) = self.reservations.remove(&car) {
+            self.reserved_at.remove(&old);
+            self.reserved_spots.remove(&old);
+            if self.is_free(old) {
+                self.unavailable_spots -= 1;
+            }
+        }
+        if self.is_free(spot) {
+            self.unavailable_spots += 1;
+        } else {
+            // Squeezing in next to another motorcycle
+            assert_eq!(car.1, VehicleType::Motorcycle);
+            assert!(!self.reserved_spots.contains(&spot));
+            assert!(!self.shared_occupants.contains_key(&spot));
+        }
         self.reserved_spots.insert(spot);
+        self.reservations.insert(car, spot);
+        self.reserved_at.insert(spot, now);
+        self.events.push(Event::ParkingSpotReserved(car, spot));
 
         // Sanity check the spot exists
         match spot {
@@ -148,30 +359,288 @@ impl ParkingSimState {
                 assert!(idx < self.num_spots_per_lot[&pl]);
             }
         }
+        if cfg!(debug_assertions) {
+            self.check_invariants();
+        }
+    }
+
+    // Frees spots reserved longer than max_age ago, in case whoever reserved them is never going
+    // to show up. A car already pulling into its spot keeps it, no matter how old the reservation
+    // is. Returns the freed spots and the cars that'd reserved them, so their trips can look for
+    // somewhere else.
+    pub fn expire_reservations(
+        &mut self,
+        now: Time,
+        max_age: Duration,
+    ) -> Vec<(ParkingSpot, Option<CarID>)> {
+        let expired: Vec<(ParkingSpot, Option<CarID>)> = self
+            .reserved_at
+            .iter()
+            .filter(|(_, at)| now - **at > max_age)
+            .map(|(spot, _)| (*spot, self.reserved_by(*spot)))
+            .filter(|(_, car)| !car.map(|c| self.maneuvering.contains(&c)).unwrap_or(false))
+            .collect();
+        for (spot, car) in &expired {
+            self.cancel_reservation(*spot, *car);
+        }
+        if cfg!(debug_assertions) {
+            self.check_invariants();
+        }
+        expired
+    }
+
+    fn reserved_by(&self, spot: ParkingSpot) -> Option<CarID> {
+        self.reservations
+            .iter()
+            .find(|(_, s)| **s == spot)
+            .map(|(car, _)| *car)
+    }
+
+    fn cancel_reservation(&mut self, spot: ParkingSpot, car: Option<CarID>) {
+        self.reserved_at.remove(&spot);
+        self.reserved_spots.remove(&spot);
+        if let Some(car) = car {
+            self.reservations.remove(&car);
+            self.events
+                .push(Event::ParkingReservationExpired(car, spot));
+        }
+        if self.is_free(spot) {
+            self.unavailable_spots -= 1;
+        }
+    }
+
+    // A car already parked in the spot can still leave; nobody else can park there until it's
+    // unblocked.
+    pub fn block_spot(&mut self, spot: ParkingSpot) {
+        if self.is_free(spot) {
+            self.unavailable_spots += 1;
+        }
+        self.blocked_spots.insert(spot);
+    }
+
+    pub fn unblock_spot(&mut self, spot: ParkingSpot) {
+        if self.blocked_spots.remove(&spot) && self.is_free(spot) {
+            self.unavailable_spots -= 1;
+        }
+    }
+
+    // Cars parked here from now on have to leave in the reverse order they arrived.
+    pub fn make_stacked(&mut self, b: BuildingID) {
+        assert!(self.num_spots_per_offstreet.contains_key(&b));
+        assert!(!(0..self.num_spots_per_offstreet[&b])
+            .any(|idx| self.occupants.contains_key(&ParkingSpot::Offstreet(b, idx))));
+        self.stacked_offstreet.insert(b, Vec::new());
+    }
+
+    // If this car is stuck in a stacked garage, which car has to leave first?
+    pub fn blocking_car(&self, p: &ParkedCar) -> Option<CarID> {
+        if let ParkingSpot::Offstreet(b, _) = p.spot {
+            if let Some(top) = self
+                .stacked_offstreet
+                .get(&b)
+                .and_then(|stack| stack.last())
+            {
+                if *top != p.vehicle.id {
+                    return Some(*top);
+                }
+            }
+        }
+        None
     }
 
-    pub fn remove_parked_car(&mut self, p: ParkedCar) {
+    // Fails with the blocking car if this one is stuck in a stacked garage.
+    pub fn remove_parked_car(&mut self, p: ParkedCar, now: Time) -> Result<(), CarID> {
+        if let Some(blocker) = self.blocking_car(&p) {
+            return Err(blocker);
+        }
+        if let ParkingSpot::Offstreet(b, _) = p.spot {
+            if let Some(stack) = self.stacked_offstreet.get_mut(&b) {
+                stack.pop();
+            }
+        }
+
         self.parked_cars
             .remove(&p.vehicle.id)
             .expect("remove_parked_car missing from parked_cars");
-        self.occupants
-            .remove(&p.spot)
-            .expect("remove_parked_car missing from occupants");
+        if self.shared_occupants.get(&p.spot) == Some(&p.vehicle.id) {
+            self.shared_occupants.remove(&p.spot);
+        } else {
+            self.occupants
+                .remove(&p.spot)
+                .expect("remove_parked_car missing from occupants");
+            // The other motorcycle has the spot to itself now
+            if let Some(other) = self.shared_occupants.remove(&p.spot) {
+                self.occupants.insert(p.spot, other);
+            }
+            if self.is_free(p.spot) {
+                self.unavailable_spots -= 1;
+            }
+        }
+        self.last_vacated.insert(p.spot, (p.vehicle.id, now));
+        self.overstays_reported.remove(&p.vehicle.id);
+        if let Some(arrived) = self.arrivals.remove(&p.vehicle.id) {
+            let entry = self
+                .dwell_times
+                .entry(p.spot)
+                .or_insert((0, Duration::ZERO));
+            entry.0 += 1;
+            entry.1 += now - arrived;
+        }
         self.events
             .push(Event::CarLeftParkingSpot(p.vehicle.id, p.spot));
+        if cfg!(debug_assertions) {
+            self.check_invariants();
+        }
+        Ok(())
+    }
+
+    // Makes every car parked in the lot leave and cancels reservations there, like when the lot
+    // closes. Cars already pulling into the lot keep their spots. Returns the displaced cars, so
+    // the caller can find somewhere else for them.
+    pub fn evacuate_lot(&mut self, pl: ParkingLotID, now: Time) -> Vec<ParkedCar> {
+        let mut displaced = Vec::new();
+        for idx in 0..self.num_spots_per_lot.get(&pl).cloned().unwrap_or(0) {
+            let spot = ParkingSpot::Lot(pl, idx);
+            if self.reserved_spots.contains(&spot) {
+                let car = self.reserved_by(spot);
+                if !car.map(|c| self.maneuvering.contains(&c)).unwrap_or(false) {
+                    self.cancel_reservation(spot, car);
+                }
+            }
+            if let Some(car) = self.occupants.get(&spot) {
+                let p = self.parked_cars[car].clone();
+                // Only garages are stacked, so nothing can block this
+                self.remove_parked_car(p.clone(), now).unwrap();
+                displaced.push(p);
+            }
+        }
+        displaced
+    }
+
+    // Just where cars are parked, leaving out anything that changes constantly
+    pub fn snapshot(&self) -> ParkingSnapshot {
+        ParkingSnapshot {
+            parked_cars: self.parked_cars.clone(),
+            occupants: self.occupants.clone(),
+            shared_occupants: self.shared_occupants.clone(),
+            arrivals: self.arrivals.clone(),
+        }
+    }
+
+    // Replaces every parked car with the snapshot's. All reservations are dropped. Blocked spots
+    // and other per-spot settings stay as they are. Cars the snapshot doesn't have an arrival time
+    // for are treated as arriving now.
+    pub fn restore_snapshot(&mut self, snapshot: ParkingSnapshot, now: Time) {
+        self.parked_cars = snapshot.parked_cars;
+        self.occupants = snapshot.occupants;
+        self.shared_occupants = snapshot.shared_occupants;
+        self.reserved_spots.clear();
+        self.reservations.clear();
+        self.reserved_at.clear();
+
+        let occupants = &self.occupants;
+        let parked_cars = &self.parked_cars;
+        self.arrivals = snapshot.arrivals;
+        self.arrivals.retain(|car, _| parked_cars.contains_key(car));
+        for car in parked_cars.keys() {
+            self.arrivals.entry(*car).or_insert(now);
+        }
+        self.overstays_reported
+            .retain(|car| parked_cars.contains_key(car));
+        // The order cars arrived in is lost, so restack them by spot
+        for (b, stack) in self.stacked_offstreet.iter_mut() {
+            *stack = (0..self.num_spots_per_offstreet[b])
+                .filter_map(|idx| occupants.get(&ParkingSpot::Offstreet(*b, idx)).cloned())
+                .collect();
+        }
+        self.unavailable_spots = self.get_all_parking_spots().0.len();
+        if cfg!(debug_assertions) {
+            self.check_invariants();
+        }
     }
 
-    pub fn add_parked_car(&mut self, p: ParkedCar) {
+    pub fn add_parked_car(&mut self, p: ParkedCar, now: Time) {
         self.events
             .push(Event::CarReachedParkingSpot(p.vehicle.id, p.spot));
 
         assert!(self.reserved_spots.remove(&p.spot));
+        self.reservations.remove(&p.vehicle.id);
+        self.reserved_at.remove(&p.spot);
+        self.arrivals.insert(p.vehicle.id, now);
 
-        assert!(!self.occupants.contains_key(&p.spot));
+        if self.occupants.contains_key(&p.spot) {
+            assert_eq!(p.vehicle.vehicle_type, VehicleType::Motorcycle);
+            assert!(!self.shared_occupants.contains_key(&p.spot));
+            self.shared_occupants.insert(p.spot, p.vehicle.id);
+            assert!(!self.parked_cars.contains_key(&p.vehicle.id));
+            self.parked_cars.insert(p.vehicle.id, p);
+            if cfg!(debug_assertions) {
+                self.check_invariants();
+            }
+            return;
+        }
         self.occupants.insert(p.spot, p.vehicle.id);
+        if let ParkingSpot::Offstreet(b, _) = p.spot {
+            if let Some(stack) = self.stacked_offstreet.get_mut(&b) {
+                stack.push(p.vehicle.id);
+            }
+        }
 
         assert!(!self.parked_cars.contains_key(&p.vehicle.id));
         self.parked_cars.insert(p.vehicle.id, p);
+        if cfg!(debug_assertions) {
+            self.check_invariants();
+        }
+    }
+
+    // Panics if occupants, shared_occupants, parked_cars, and reservations disagree. Called after
+    // every change in debug builds, to catch the exact operation that breaks things.
+    pub fn check_invariants(&self) {
+        for (spot, car) in self.occupants.iter().chain(self.shared_occupants.iter()) {
+            match self.parked_cars.get(car) {
+                Some(p) => assert_eq!(
+                    p.spot, *spot,
+                    "{} occupies {:?}, but is parked at {:?}",
+                    car, spot, p.spot
+                ),
+                None => panic!("{} occupies {:?}, but isn't parked", car, spot),
+            }
+        }
+        for (car, p) in &self.parked_cars {
+            assert!(
+                self.occupants.get(&p.spot) == Some(car)
+                    || self.shared_occupants.get(&p.spot) == Some(car),
+                "{} is parked at {:?}, but doesn't occupy it",
+                car,
+                p.spot
+            );
+        }
+        for spot in self.shared_occupants.keys() {
+            assert!(
+                self.occupants.contains_key(spot),
+                "{:?} is shared, but has no main occupant",
+                spot
+            );
+        }
+        for spot in &self.reserved_spots {
+            // Only a second motorcycle can reserve an occupied spot
+            if let Some(car) = self.occupants.get(spot) {
+                assert!(
+                    car.1 == VehicleType::Motorcycle && !self.shared_occupants.contains_key(spot),
+                    "{:?} is reserved, but {} already occupies it",
+                    spot,
+                    car
+                );
+            }
+        }
+        for (car, spot) in &self.reservations {
+            assert!(
+                self.reserved_spots.contains(spot),
+                "{} has a reservation for {:?}, but the spot isn't reserved",
+                car,
+                spot
+            );
+        }
     }
 
     pub fn get_draw_cars(&self, id: LaneID, map: &Map) -> Vec<DrawCarInput> {
@@ -181,6 +650,9 @@ impl ParkingSimState {
                 if let Some(car) = self.occupants.get(&spot) {
                     cars.push(self.get_draw_car(*car, map).unwrap());
                 }
+                if let Some(car) = self.shared_occupants.get(&spot) {
+                    cars.push(self.get_draw_car(*car, map).unwrap());
+                }
             }
         }
         cars
@@ -198,11 +670,54 @@ impl ParkingSimState {
         cars
     }
 
+    pub fn get_draw_cars_in_garages(&self, id: LaneID, map: &Map) -> Vec<DrawCarInput> {
+        let mut cars = Vec::new();
+        for b in self.driving_to_offstreet.get(id) {
+            for idx in 0..self.num_spots_per_offstreet[&b] {
+                if let Some(car) = self.occupants.get(&ParkingSpot::Offstreet(*b, idx)) {
+                    cars.push(self.get_draw_car(*car, map).unwrap());
+                }
+            }
+        }
+        cars
+    }
+
     pub fn get_draw_car(&self, id: CarID, map: &Map) -> Option<DrawCarInput> {
         let p = self.parked_cars.get(&id)?;
         match p.spot {
+            ParkingSpot::Onstreet(lane, idx)
+                if self.onstreet_lanes[&lane].orientation != OnstreetOrientation::Parallel =>
+            {
+                let (center, _, heading) = self.onstreet_stall(lane, idx, map);
+                let half = p.vehicle.length / 2.0;
+                Some(DrawCarInput {
+                    id: p.vehicle.id,
+                    waiting_for_turn: None,
+                    status: CarStatus::Parked,
+                    on: Traversable::Lane(lane),
+                    label: None,
+
+                    body: PolyLine::new(vec![
+                        center.project_away(half, heading.opposite()),
+                        center.project_away(half, heading),
+                    ]),
+                })
+            }
             ParkingSpot::Onstreet(lane, idx) => {
-                let front_dist = self.onstreet_lanes[&lane].dist_along_for_car(idx, &p.vehicle);
+                let spot = &self.onstreet_lanes[&lane];
+                let buffer = self.parked_car_buffer;
+                // Two motorcycles sharing a spot go at either end of it
+                let (rear_dist, front_dist) = match self.shared_occupants.get(&p.spot) {
+                    Some(second) if *second == id => {
+                        let rear = spot.spot_dist_along[idx] - spot.spot_lengths[idx] + buffer;
+                        (rear, rear + p.vehicle.length)
+                    }
+                    Some(_) => {
+                        let front = spot.spot_dist_along[idx] - buffer;
+                        (front - p.vehicle.length, front)
+                    }
+                    None => spot.car_body(idx, &p.vehicle, buffer),
+                };
                 Some(DrawCarInput {
                     id: p.vehicle.id,
                     waiting_for_turn: None,
@@ -213,39 +728,81 @@ impl ParkingSimState {
                     body: map
                         .get_l(lane)
                         .lane_center_pts
-                        .exact_slice(front_dist - p.vehicle.length, front_dist),
+                        .exact_slice(rear_dist, front_dist),
                 })
             }
-            ParkingSpot::Offstreet(_, _) => None,
-            ParkingSpot::Lot(pl, idx) => {
-                let pl = map.get_pl(pl);
-                let (pt, angle) = pl.spots[idx];
-                let buffer = Distance::meters(0.5);
+            ParkingSpot::Offstreet(b, idx) => {
+                // Not where the car really is, just a schematic grid of spots around the
+                // building's label, so it's visible how full the garage is.
+                let bldg = map.get_b(b);
+                let (row, col) = (idx / GARAGE_COLUMNS, idx % GARAGE_COLUMNS);
+                let x = (col as f64 - (GARAGE_COLUMNS as f64 - 1.0) / 2.0) * GARAGE_SPOT_WIDTH;
+                let y = (row as f64) * map_model::PARKING_SPOT_LENGTH.inner_meters();
+                let front = bldg.label_center.offset(x, y);
+                let buffer = self.parked_car_buffer;
+                // Long vehicles are drawn shortened, so they don't overlap the next row
+                let length = p
+                    .vehicle
+                    .length
+                    .min(map_model::PARKING_SPOT_LENGTH - buffer * 2.0);
                 Some(DrawCarInput {
                     id: p.vehicle.id,
                     waiting_for_turn: None,
                     status: CarStatus::Parked,
                     // Just used for z-order
-                    on: Traversable::Lane(pl.driving_pos.lane()),
+                    on: Traversable::Lane(bldg.parking.as_ref().unwrap().driving_pos.lane()),
                     label: None,
 
                     body: PolyLine::new(vec![
+                        front.offset(0.0, (buffer + length).inner_meters()),
+                        front.offset(0.0, buffer.inner_meters()),
+                    ]),
+                })
+            }
+            ParkingSpot::Lot(pl, idx) => {
+                let pl = map.get_pl(pl);
+                let (pt, angle, orientation) = pl.spots[idx];
+                let buffer = self.parked_car_buffer;
+                let body = match orientation {
+                    SpotOrientation::Perpendicular => PolyLine::new(vec![
                         pt.project_away(buffer, angle),
                         pt.project_away(map_model::PARKING_LOT_SPOT_LENGTH - buffer, angle),
                     ]),
+                    SpotOrientation::Parallel => {
+                        let center =
+                            pt.project_away(map_model::PARKING_LOT_SPOT_WIDTH / 2.0, angle);
+                        let half = map_model::PARKING_LOT_SPOT_LENGTH / 2.0 - buffer;
+                        let along = angle.rotate_degs(90.0);
+                        PolyLine::new(vec![
+                            center.project_away(half, along.opposite()),
+                            center.project_away(half, along),
+                        ])
+                    }
+                };
+                Some(DrawCarInput {
+                    id: p.vehicle.id,
+                    waiting_for_turn: None,
+                    status: CarStatus::Parked,
+                    // Just used for z-order
+                    on: Traversable::Lane(pl.driving_pos.lane()),
+                    label: None,
+
+                    body,
                 })
             }
         }
     }
 
-    // There's no DrawCarInput for cars parked offstreet, so we need this.
+    // Cars parked offstreet use their spot in the schematic grid around the building's label, so
+    // every car in one garage has a distinct point. If the building's parking is unknown, just
+    // point at the building.
     pub fn canonical_pt(&self, id: CarID, map: &Map) -> Option<Pt2D> {
         let p = self.parked_cars.get(&id)?;
         match p.spot {
-            ParkingSpot::Onstreet(_, _) | ParkingSpot::Lot(_, _) => {
-                self.get_draw_car(id, map).map(|c| c.body.last_pt())
+            ParkingSpot::Offstreet(b, _) if map.get_b(b).parking.is_none() => {
+                Some(map.get_b(b).label_center)
             }
-            ParkingSpot::Offstreet(b, _) => Some(map.get_b(b).label_center),
+            _ => self.get_draw_car(id, map).map(|c| c.body.last_pt()),
         }
     }
 
@@ -256,8 +813,158 @@ impl ParkingSimState {
             .collect()
     }
 
+    // Best-effort guess at which occupied spots along a lane will open up within the given time,
+    // based on how long cars have stayed in each spot so far this run. Spots that nobody has left
+    // yet are never included.
+    pub fn likely_free_soon(&self, lane: LaneID, within: Duration, now: Time) -> Vec<ParkingSpot> {
+        let mut spots = Vec::new();
+        if let Some(pl) = self.onstreet_lanes.get(&lane) {
+            for spot in pl.spots() {
+                if let (Some(arrived), Some((count, total))) = (
+                    self.occupants
+                        .get(&spot)
+                        .and_then(|car| self.arrivals.get(car)),
+                    self.dwell_times.get(&spot),
+                ) {
+                    let avg_dwell = *total / (*count as f64);
+                    if (now - *arrived) + within >= avg_dwell {
+                        spots.push(spot);
+                    }
+                }
+            }
+        }
+        spots
+    }
+
+    // The driving model calls these when a car starts and finishes parking or unparking.
+    pub fn note_maneuver_start(&mut self, car: CarID, driving_lane: LaneID) {
+        self.active_maneuvers.insert(driving_lane, car);
+        self.maneuvering.insert(car);
+        self.events
+            .push(Event::ParkingManeuverStarted(car, driving_lane));
+    }
+
+    pub fn note_maneuver_finish(&mut self, car: CarID, driving_lane: LaneID) {
+        self.active_maneuvers.remove(driving_lane, car);
+        self.maneuvering.remove(&car);
+        self.events
+            .push(Event::ParkingManeuverFinished(car, driving_lane));
+    }
+
+    // How many cars are blocking this driving lane to park or unpark right now
+    pub fn active_maneuvers(&self, driving_lane: LaneID) -> usize {
+        self.active_maneuvers.get(driving_lane).len()
+    }
+
+    pub fn total_spots(&self) -> usize {
+        self.total_spots
+    }
+
+    // How many spots are free, without looking at every spot (except in debug builds, to check the
+    // counters are right)
+    pub fn available_spots(&self) -> usize {
+        let available = self.total_spots - self.unavailable_spots;
+        debug_assert_eq!(available, self.get_all_parking_spots().1.len());
+        available
+    }
+
+    // Free spots over all spots, without looking at every spot. 0 if there aren't any spots.
+    pub fn free_fraction(&self) -> f64 {
+        if self.total_spots == 0 {
+            return 0.0;
+        }
+        ((self.total_spots - self.unavailable_spots) as f64) / (self.total_spots as f64)
+    }
+
+    // The spot a car has reserved but hasn't parked in yet
+    pub fn target_spot(&self, car: CarID) -> Option<ParkingSpot> {
+        self.reservations.get(&car).cloned()
+    }
+
     pub fn is_free(&self, spot: ParkingSpot) -> bool {
-        !self.occupants.contains_key(&spot) && !self.reserved_spots.contains(&spot)
+        !self.occupants.contains_key(&spot)
+            && !self.reserved_spots.contains(&spot)
+            && !self.blocked_spots.contains(&spot)
+    }
+
+    // How many spots are occupied everywhere cars can park
+    pub fn utilization(&self) -> ParkingStats {
+        let mut stats = ParkingStats {
+            onstreet: self
+                .onstreet_lanes
+                .iter()
+                .map(|(l, lane)| (*l, (0, lane.spot_dist_along.len())))
+                .collect(),
+            offstreet: self
+                .num_spots_per_offstreet
+                .iter()
+                .map(|(b, n)| (*b, (0, *n)))
+                .collect(),
+            lots: self
+                .num_spots_per_lot
+                .iter()
+                .map(|(pl, n)| (*pl, (0, *n)))
+                .collect(),
+            fraction_occupied: 0.0,
+        };
+        for spot in self.occupants.keys() {
+            let entry = match spot {
+                ParkingSpot::Onstreet(l, _) => stats.onstreet.get_mut(l),
+                ParkingSpot::Offstreet(b, _) => stats.offstreet.get_mut(b),
+                ParkingSpot::Lot(pl, _) => stats.lots.get_mut(pl),
+            };
+            if let Some((occupied, _)) = entry {
+                *occupied += 1;
+            }
+        }
+        let (occupied, total) = stats
+            .onstreet
+            .values()
+            .chain(stats.offstreet.values())
+            .chain(stats.lots.values())
+            .fold((0, 0), |(a, b), (occupied, total)| {
+                (a + occupied, b + total)
+            });
+        if total > 0 {
+            stats.fraction_occupied = (occupied as f64) / (total as f64);
+        }
+        stats
+    }
+
+    // Like is_free, but a motorcycle can also share a spot with another motorcycle, if they both
+    // fit.
+    pub fn is_free_for(&self, spot: ParkingSpot, vehicle: &Vehicle) -> bool {
+        if self.is_free(spot) {
+            return true;
+        }
+        // The vehicle can always use the spot it reserved itself
+        let reserved_by_other = self.reserved_spots.contains(&spot)
+            && self.reservations.get(&vehicle.id) != Some(&spot);
+        if !reserved_by_other
+            && !self.occupants.contains_key(&spot)
+            && !self.blocked_spots.contains(&spot)
+        {
+            return true;
+        }
+        if vehicle.vehicle_type != VehicleType::Motorcycle
+            || reserved_by_other
+            || self.blocked_spots.contains(&spot)
+            || self.shared_occupants.contains_key(&spot)
+        {
+            return false;
+        }
+        if let (ParkingSpot::Onstreet(l, idx), Some(other)) = (spot, self.occupants.get(&spot)) {
+            let other = &self.parked_cars[other].vehicle;
+            return other.vehicle_type == VehicleType::Motorcycle
+                && other.length + vehicle.length + self.parked_car_buffer * 3.0
+                    <= self.onstreet_lanes[&l].spot_lengths[idx];
+        }
+        false
+    }
+
+    // Which car most recently left this spot, and when? The spot might be occupied again since.
+    pub fn last_vacated(&self, spot: ParkingSpot) -> Option<(CarID, Time)> {
+        self.last_vacated.get(&spot).cloned()
     }
 
     pub fn get_car_at_spot(&self, spot: ParkingSpot) -> Option<&ParkedCar> {
@@ -265,8 +972,10 @@ impl ParkingSimState {
         Some(&self.parked_cars[&car])
     }
 
-    // The vehicle's front is currently at the given driving_pos. Returns all valid spots and their
-    // driving position.
+    // The vehicle's front is currently at the given driving_pos. Returns all valid spots, their
+    // driving position, and their hourly price if they aren't free. Only spots ahead of the
+    // vehicle are included, unless include_behind is set, like for a quiet street where turning
+    // around is easy.
     pub fn get_all_free_spots(
         &self,
         driving_pos: Position,
@@ -275,7 +984,8 @@ impl ParkingSimState {
         // private spots.
         target: BuildingID,
         map: &Map,
-    ) -> Vec<(ParkingSpot, Position)> {
+        include_behind: bool,
+    ) -> Vec<(ParkingSpot, Position, Option<Money>)> {
         let mut candidates = Vec::new();
 
         for l in self.driving_to_parking_lanes.get(driving_pos.lane()) {
@@ -285,22 +995,28 @@ impl ParkingSimState {
             let lane = &self.onstreet_lanes[l];
             // Bit hacky to enumerate here to conveniently get idx.
             for (idx, spot) in lane.spots().into_iter().enumerate() {
-                if self.is_free(spot) && parking_dist < lane.dist_along_for_car(idx, vehicle) {
+                if self.is_free_for(spot, vehicle)
+                    && (include_behind || parking_dist < lane.dist_along_for_car(idx, vehicle))
+                {
                     candidates.push(spot);
                 }
             }
         }
 
         for b in self.driving_to_offstreet.get(driving_pos.lane()) {
-            let parking = map.get_b(*b).parking.as_ref().unwrap();
-            if parking.public_garage_name.is_none() && target != *b {
-                continue;
-            }
-            let bldg_dist = parking.driving_pos.dist_along();
-            if driving_pos.dist_along() < bldg_dist {
+            let bldg_dist = map
+                .get_b(*b)
+                .parking
+                .as_ref()
+                .unwrap()
+                .driving_pos
+                .dist_along();
+            if include_behind || driving_pos.dist_along() < bldg_dist {
                 for idx in 0..self.num_spots_per_offstreet[b] {
                     let spot = ParkingSpot::Offstreet(*b, idx);
-                    if self.is_free(spot) {
+                    if self.is_free_for(spot, vehicle)
+                        && self.can_use_offstreet(spot, vehicle, target, map)
+                    {
                         candidates.push(spot);
                     }
                 }
@@ -309,10 +1025,10 @@ impl ParkingSimState {
 
         for pl in self.driving_to_lots.get(driving_pos.lane()) {
             let lot_dist = map.get_pl(*pl).driving_pos.dist_along();
-            if driving_pos.dist_along() < lot_dist {
+            if include_behind || driving_pos.dist_along() < lot_dist {
                 for idx in 0..self.num_spots_per_lot[&pl] {
                     let spot = ParkingSpot::Lot(*pl, idx);
-                    if self.is_free(spot) {
+                    if self.is_free_for(spot, vehicle) {
                         candidates.push(spot);
                     }
                 }
@@ -321,33 +1037,271 @@ impl ParkingSimState {
 
         candidates
             .into_iter()
-            .map(|spot| (spot, self.spot_to_driving_pos(spot, vehicle, map)))
+            .filter(|spot| self.is_permitted(*spot, vehicle))
+            .map(|spot| {
+                (
+                    spot,
+                    self.spot_to_driving_pos(spot, vehicle, map),
+                    self.get_cost_per_hour(spot),
+                )
+            })
             .collect()
     }
 
-    pub fn spot_to_driving_pos(&self, spot: ParkingSpot, vehicle: &Vehicle, map: &Map) -> Position {
-        match spot {
-            ParkingSpot::Onstreet(l, idx) => {
-                let lane = &self.onstreet_lanes[&l];
-                Position::new(l, lane.dist_along_for_car(idx, vehicle)).equiv_pos(
-                    lane.driving_lane,
-                    vehicle.length,
-                    map,
-                )
-            }
-            ParkingSpot::Offstreet(b, _) => map.get_b(b).parking.as_ref().unwrap().driving_pos,
-            ParkingSpot::Lot(pl, _) => map.get_pl(pl).driving_pos,
-        }
+    // Only cars owned by this person will be able to find the spot.
+    pub fn assign_spot(&mut self, spot: ParkingSpot, person: PersonID) {
+        self.spot_owners.insert(spot, person);
     }
 
-    pub fn spot_to_sidewalk_pos(&self, spot: ParkingSpot, map: &Map) -> Position {
+    pub fn unassign_spot(&mut self, spot: ParkingSpot) {
+        self.spot_owners.remove(&spot);
+    }
+
+    // Replaces which spots in a building's garage belong to residents, keyed by spot index.
+    // Spots left out stay open to anybody who could use the garage before.
+    pub fn assign_offstreet_spots(&mut self, b: BuildingID, owners: BTreeMap<usize, PersonID>) {
+        let num_spots = self.num_spots_per_offstreet.get(&b).cloned().unwrap_or(0);
+        for idx in 0..num_spots {
+            self.spot_owners.remove(&ParkingSpot::Offstreet(b, idx));
+        }
+        for (idx, person) in owners {
+            assert!(idx < num_spots);
+            self.spot_owners
+                .insert(ParkingSpot::Offstreet(b, idx), person);
+        }
+    }
+
+    // None makes the spot free
+    pub fn set_cost_per_hour(&mut self, spot: ParkingSpot, cost: Option<Money>) {
+        if let Some(cost) = cost {
+            self.cost_per_hour.insert(spot, cost);
+        } else {
+            self.cost_per_hour.remove(&spot);
+        }
+    }
+
+    pub fn get_cost_per_hour(&self, spot: ParkingSpot) -> Option<Money> {
+        self.cost_per_hour.get(&spot).cloned()
+    }
+
+    pub fn set_onstreet_classes(&mut self, l: LaneID, classes: VehicleClasses) {
+        self.onstreet_lanes.get_mut(&l).unwrap().allowed_classes = classes;
+    }
+
+    pub fn set_offstreet_classes(&mut self, b: BuildingID, classes: VehicleClasses) {
+        self.offstreet_classes.insert(b, classes);
+    }
+
+    pub fn set_lot_classes(&mut self, pl: ParkingLotID, classes: VehicleClasses) {
+        self.lot_classes.insert(pl, classes);
+    }
+
+    pub fn is_disabled_spot(&self, spot: ParkingSpot) -> bool {
+        self.disabled_spots.contains(&spot)
+    }
+
+    // Can this vehicle park here, if the spot's free?
+    fn is_permitted(&self, spot: ParkingSpot, vehicle: &Vehicle) -> bool {
+        if self.is_disabled_spot(spot) && !vehicle.accessible {
+            return false;
+        }
+        let classes = match spot {
+            ParkingSpot::Onstreet(l, _) => Some(&self.onstreet_lanes[&l].allowed_classes),
+            ParkingSpot::Offstreet(b, _) => self.offstreet_classes.get(&b),
+            ParkingSpot::Lot(pl, _) => self.lot_classes.get(&pl),
+        };
+        if !classes.map(|c| c.allows(vehicle)).unwrap_or(true) {
+            return false;
+        }
+        if let (Some(budget), Some(cost)) = (vehicle.parking_budget, self.get_cost_per_hour(spot)) {
+            if cost > budget {
+                return false;
+            }
+        }
+        match self.spot_owners.get(&spot) {
+            Some(owner) => vehicle.owner == Some(*owner),
+            None => true,
+        }
+    }
+
+    // Private garages are only for trips to or from their building, except for spots assigned to
+    // the vehicle's owner. Those are always usable, like a resident's permit. Other spots in the
+    // garage follow the usual rules.
+    fn can_use_offstreet(
+        &self,
+        spot: ParkingSpot,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+    ) -> bool {
+        if let ParkingSpot::Offstreet(b, _) = spot {
+            let parking = map.get_b(b).parking.as_ref().unwrap();
+            if parking.public_garage_name.is_none() && target != b {
+                return vehicle.owner.is_some()
+                    && self.spot_owners.get(&spot) == vehicle.owner.as_ref();
+            }
+        }
+        true
+    }
+
+    // Like get_all_free_spots, but excludes spots that're more than max_walk away from the target
+    // building. This models a driver who won't park too far from their destination.
+    pub fn acceptable_spots(
+        &self,
+        driving_pos: Position,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+        max_walk: Distance,
+    ) -> Vec<(ParkingSpot, Position, Option<Money>)> {
+        within_walk(
+            self.get_all_free_spots(driving_pos, vehicle, target, map, false),
+            max_walk,
+            |spot| self.walking_dist(spot, target, map),
+        )
+    }
+
+    // The free spot closest to pt as the crow flies, ignoring how a driver would get there. Only
+    // meant for estimating things before a trip starts.
+    pub fn nearest_free_spot_to(
+        &self,
+        pt: Pt2D,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+    ) -> Option<(ParkingSpot, Distance)> {
+        self.get_all_parking_spots()
+            .1
+            .into_iter()
+            .filter(|spot| {
+                self.can_use_offstreet(*spot, vehicle, target, map)
+                    && self.is_permitted(*spot, vehicle)
+            })
+            .map(|spot| {
+                (
+                    spot,
+                    self.spot_to_sidewalk_pos(spot, map).pt(map).dist_to(pt),
+                )
+            })
+            .min_by_key(|(_, dist)| *dist)
+    }
+
+    // Finds and reserves a free spot within max_walk of the target, for planning a trip before it
+    // starts. Spots are chosen to spread reservations out, instead of everybody going to the
+    // closest spot.
+    pub fn reserve_near(
+        &mut self,
+        target: BuildingID,
+        vehicle: &Vehicle,
+        map: &Map,
+        max_walk: Distance,
+        now: Time,
+    ) -> Option<(ParkingSpot, Position)> {
+        let goal_pt = map.get_b(target).front_path.sidewalk.pt(map);
+        let mut candidates = Vec::new();
+        for spot in self.get_all_parking_spots().1 {
+            if !self.is_permitted(spot, vehicle)
+                || !self.can_use_offstreet(spot, vehicle, target, map)
+            {
+                continue;
+            }
+            // Walking is never shorter than a straight line, so skip pathfinding when possible.
+            if self
+                .spot_to_sidewalk_pos(spot, map)
+                .pt(map)
+                .dist_to(goal_pt)
+                > max_walk
+            {
+                continue;
+            }
+            if let Some(dist) = self.walking_dist(spot, target, map) {
+                if dist <= max_walk {
+                    candidates.push((spot, dist));
+                }
+            }
+        }
+
+        let spot = self.reserve_least_crowded(candidates, vehicle.id, now)?;
+        Some((spot, self.spot_to_driving_pos(spot, vehicle, map)))
+    }
+
+    // The map-independent half of reserve_near: reserves the least crowded candidate.
+    fn reserve_least_crowded(
+        &mut self,
+        candidates: Vec<(ParkingSpot, Distance)>,
+        car: CarID,
+        now: Time,
+    ) -> Option<ParkingSpot> {
+        let spot = self.least_crowded(candidates)?;
+        self.reserve_spot(spot, car, now);
+        Some(spot)
+    }
+
+    // Prefer spots on a lane, building, or lot with the fewest reservations already. Ties go to
+    // the shortest walk.
+    fn least_crowded(&self, candidates: Vec<(ParkingSpot, Distance)>) -> Option<ParkingSpot> {
+        let same_place = |s1: ParkingSpot, s2: ParkingSpot| match (s1, s2) {
+            (ParkingSpot::Onstreet(l1, _), ParkingSpot::Onstreet(l2, _)) => l1 == l2,
+            (ParkingSpot::Offstreet(b1, _), ParkingSpot::Offstreet(b2, _)) => b1 == b2,
+            (ParkingSpot::Lot(pl1, _), ParkingSpot::Lot(pl2, _)) => pl1 == pl2,
+            _ => false,
+        };
+        candidates
+            .into_iter()
+            .min_by_key(|(spot, dist)| {
+                let num_reserved = self
+                    .reserved_spots
+                    .iter()
+                    .filter(|s| same_place(**s, *spot))
+                    .count();
+                (num_reserved, *dist)
+            })
+            .map(|(spot, _)| spot)
+    }
+
+    // How far somebody parking here walks to the building. If there's no walking path, falls back
+    // to the straight line between the sidewalk positions.
+    pub fn walking_dist_to(&self, spot: ParkingSpot, b: BuildingID, map: &Map) -> Distance {
+        self.walking_dist(spot, b, map).unwrap_or_else(|| {
+            self.spot_to_sidewalk_pos(spot, map)
+                .pt(map)
+                .dist_to(map.get_b(b).front_path.sidewalk.pt(map))
+        })
+    }
+
+    // None if there's no walking path between the spot and the building.
+    fn walking_dist(&self, spot: ParkingSpot, b: BuildingID, map: &Map) -> Option<Distance> {
+        let path = map.pathfind(PathRequest {
+            start: self.spot_to_sidewalk_pos(spot, map),
+            end: map.get_b(b).front_path.sidewalk,
+            constraints: PathConstraints::Pedestrian,
+        })?;
+        Some(path.total_length())
+    }
+
+    pub fn spot_to_driving_pos(&self, spot: ParkingSpot, vehicle: &Vehicle, map: &Map) -> Position {
+        match spot {
+            ParkingSpot::Onstreet(l, idx) => {
+                let lane = &self.onstreet_lanes[&l];
+                Position::new(l, lane.dist_along_for_car(idx, vehicle)).equiv_pos(
+                    lane.driving_lane,
+                    vehicle.length,
+                    map,
+                )
+            }
+            ParkingSpot::Offstreet(b, _) => map.get_b(b).parking.as_ref().unwrap().driving_pos,
+            ParkingSpot::Lot(pl, _) => map.get_pl(pl).driving_pos,
+        }
+    }
+
+    pub fn spot_to_sidewalk_pos(&self, spot: ParkingSpot, map: &Map) -> Position {
         match spot {
             ParkingSpot::Onstreet(l, idx) => {
                 let lane = &self.onstreet_lanes[&l];
                 // Always centered in the entire parking spot
                 Position::new(
                     l,
-                    lane.spot_dist_along[idx] - (map_model::PARKING_SPOT_LENGTH / 2.0),
+                    lane.spot_dist_along[idx] - (lane.spot_lengths[idx] / 2.0),
                 )
                 .equiv_pos(lane.sidewalk, Distance::ZERO, map)
             }
@@ -356,9 +1310,63 @@ impl ParkingSimState {
         }
     }
 
+    // Parallel spots and garages are always entered forwards. For angled and perpendicular spots,
+    // compare the direction a car is heading when it reaches the spot to the direction pointing
+    // into the stall.
+    pub fn entry_maneuver(&self, spot: ParkingSpot, map: &Map) -> EntryManeuver {
+        match spot {
+            ParkingSpot::Onstreet(lane, idx) => {
+                if self.onstreet_lanes[&lane].orientation == OnstreetOrientation::Parallel {
+                    EntryManeuver::PullIn
+                } else {
+                    let (_, lane_angle, heading) = self.onstreet_stall(lane, idx, map);
+                    EntryManeuver::from_angles(lane_angle, heading)
+                }
+            }
+            ParkingSpot::Offstreet(_, _) => EntryManeuver::PullIn,
+            ParkingSpot::Lot(pl, idx) => {
+                let pl = map.get_pl(pl);
+                // The driveway goes from the lot to the driving lane, so flip it.
+                let approach = pl.driveway_line.reversed().last_line().angle();
+                // The spot's angle points out of the stall, towards the aisle.
+                let (pt, angle, _) = pl.spots[idx];
+                lot_entry_maneuver(approach, &pl.aisles, pt, angle.opposite())
+            }
+        }
+    }
+
+    // For angled and perpendicular onstreet spots, returns the middle of the spot, the direction
+    // of the lane there, and the direction pointing into the stall towards the curb.
+    fn onstreet_stall(&self, lane: LaneID, idx: usize, map: &Map) -> (Pt2D, Angle, Angle) {
+        let spot = &self.onstreet_lanes[&lane];
+        let (center, lane_angle) = map
+            .get_l(lane)
+            .lane_center_pts
+            .dist_along(spot.spot_dist_along[idx] - spot.spot_lengths[idx] / 2.0);
+        let curb = map.driving_side_angle(lane_angle.rotate_degs(270.0));
+        let heading = if spot.orientation == OnstreetOrientation::Angled {
+            center.angle_to(
+                center
+                    .project_away(Distance::meters(1.0), lane_angle)
+                    .project_away(Distance::meters(1.0), curb),
+            )
+        } else {
+            curb
+        };
+        (center, lane_angle, heading)
+    }
+
     pub fn get_owner_of_car(&self, id: CarID) -> Option<PersonID> {
         self.parked_cars.get(&id).and_then(|p| p.vehicle.owner)
     }
+    // Where all of someone's parked cars are
+    pub fn spots_owned_by(&self, person: PersonID) -> Vec<(CarID, ParkingSpot)> {
+        self.parked_cars
+            .values()
+            .filter(|p| p.vehicle.owner == Some(person))
+            .map(|p| (p.vehicle.id, p.spot))
+            .collect()
+    }
     pub fn lookup_parked_car(&self, id: CarID) -> Option<&ParkedCar> {
         self.parked_cars.get(&id)
     }
@@ -392,18 +1400,36 @@ impl ParkingSimState {
         (filled, available)
     }
 
+    // Same as get_all_parking_spots, but both lists are sorted by ParkingSpot, so the order
+    // doesn't depend on how each lane enumerates its spots. Useful for comparing snapshots.
+    pub fn get_all_parking_spots_sorted(&self) -> (Vec<ParkingSpot>, Vec<ParkingSpot>) {
+        let (mut filled, mut available) = self.get_all_parking_spots();
+        filled.sort();
+        available.sort();
+        (filled, available)
+    }
+
     // Unrealistically assumes the driver has knowledge of currently free parking spots, even if
     // they're far away. Since they don't reserve the spot in advance, somebody else can still beat
     // them there, producing some nice, realistic churn if there's too much contention.
-    // The first PathStep is the turn after start, NOT PathStep::Lane(start).
+    // The first PathStep is the turn after start, NOT PathStep::Lane(start). Gives up on spots
+    // more than max_dist of driving away.
     pub fn path_to_free_parking_spot(
         &self,
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
         map: &Map,
-    ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
+        max_dist: Distance,
+    ) -> Result<(Vec<PathStep>, ParkingSpot, Position), ParkingSearchFailure> {
+        if map.get_l(start).parking_blackhole.is_some() {
+            return Err(ParkingSearchFailure::Blackholed);
+        }
+        // Free spots the vehicle isn't allowed to use
+        let mut saw_restricted = false;
         let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
+        // Each lane's length, looked up from the map the first time the search reaches it
+        let mut lane_lengths: HashMap<LaneID, Distance> = HashMap::new();
         // Don't travel far.
         // This is a max-heap, so negate all distances. Tie breaker is lane ID, arbitrary but
         // deterministic.
@@ -412,40 +1438,39 @@ impl ParkingSimState {
 
         while !queue.is_empty() {
             let (dist_so_far, current) = queue.pop().unwrap();
+            // Everything left in the queue is even farther
+            if -dist_so_far > max_dist {
+                break;
+            }
             // If the current lane has a spot open, we wouldn't be asking. This can happen if a spot
-            // opens up on the 'start' lane, but behind the car.
-            if current != start {
+            // opens up on the 'start' lane, but behind the car. Most lanes have no parking at all,
+            // so skip them cheaply.
+            if current != start && self.has_any_parking(current) {
                 // Pick the closest to the start of the lane, since that's closest to where we came
-                // from
-                if let Some((spot, pos)) = self
+                // from. The car enters at the very start, so even a spot there is fair game.
+                if let Some((spot, pos, _)) = self
                     .get_all_free_spots(
                         Position::new(current, Distance::ZERO),
                         vehicle,
                         target,
                         map,
+                        true,
                     )
                     .into_iter()
-                    .min_by_key(|(_, pos)| pos.dist_along())
+                    .min_by_key(|(_, pos, _)| pos.dist_along())
                 {
-                    let mut steps = vec![PathStep::Lane(current)];
-                    let mut current = current;
-                    loop {
-                        if current == start {
-                            // Don't include PathStep::Lane(start)
-                            steps.pop();
-                            steps.reverse();
-                            return Some((steps, spot, pos));
-                        }
-                        let turn = backrefs[&current];
-                        steps.push(PathStep::Turn(turn));
-                        steps.push(PathStep::Lane(turn.src));
-                        current = turn.src;
-                    }
+                    return Ok((trace_back(start, current, &backrefs), spot, pos));
+                }
+                if !saw_restricted && self.any_free_spots(current) {
+                    saw_restricted = true;
                 }
             }
+            let current_length = *lane_lengths
+                .entry(current)
+                .or_insert_with(|| map.get_l(current).length());
             for turn in map.get_turns_for(current, PathConstraints::Car) {
                 if !backrefs.contains_key(&turn.id.dst) {
-                    let dist_this_step = turn.geom.length() + map.get_l(current).length();
+                    let dist_this_step = turn.geom.length() + current_length;
                     backrefs.insert(turn.id.dst, turn.id);
                     // Remember, keep things negative
                     queue.push((dist_so_far - dist_this_step, turn.id.dst));
@@ -453,7 +1478,165 @@ impl ParkingSimState {
             }
         }
 
-        None
+        if saw_restricted {
+            Err(ParkingSearchFailure::AllRestricted)
+        } else {
+            Err(ParkingSearchFailure::NoFreeSpots)
+        }
+    }
+
+    // Does this driving lane lead to any on-street, off-street, or lot parking?
+    fn has_any_parking(&self, driving_lane: LaneID) -> bool {
+        !self.driving_to_parking_lanes.get(driving_lane).is_empty()
+            || !self.driving_to_offstreet.get(driving_lane).is_empty()
+            || !self.driving_to_lots.get(driving_lane).is_empty()
+    }
+
+    // Is any spot reachable from this driving lane free, no matter who's allowed to use it?
+    fn any_free_spots(&self, driving_lane: LaneID) -> bool {
+        self.driving_to_parking_lanes
+            .get(driving_lane)
+            .iter()
+            .any(|l| self.count_free_onstreet_spots(*l) > 0)
+            || self
+                .driving_to_offstreet
+                .get(driving_lane)
+                .iter()
+                .any(|b| self.count_free_offstreet_spots(*b) > 0)
+            || self
+                .driving_to_lots
+                .get(driving_lane)
+                .iter()
+                .any(|pl| self.count_free_lot_spots(*pl) > 0)
+    }
+
+    // Cars parked longer than their spot's time limit. Only looks at lanes with a limit, so it's
+    // cheap to call often.
+    pub fn find_overstayed_cars(&self, now: Time) -> Vec<CarID> {
+        let mut cars = Vec::new();
+        for lane in self.onstreet_lanes.values() {
+            if let Some(limit) = lane.time_limit {
+                for spot in lane.spots() {
+                    for car in self
+                        .occupants
+                        .get(&spot)
+                        .into_iter()
+                        .chain(self.shared_occupants.get(&spot))
+                    {
+                        if let Some(arrived) = self.arrivals.get(car) {
+                            if now - *arrived > limit {
+                                cars.push(*car);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        cars
+    }
+
+    // Emits ParkingOverstay for cars that've newly overstayed.
+    pub fn report_overstays(&mut self, now: Time) {
+        for car in self.find_overstayed_cars(now) {
+            if self.overstays_reported.insert(car) {
+                self.events
+                    .push(Event::ParkingOverstay(car, self.parked_cars[&car].spot));
+            }
+        }
+    }
+
+    // Like path_to_free_parking_spot, but balances how far the driver goes against the price of
+    // the spot. Each dollar per hour is worth dist_per_dollar of extra driving or walking. Gives
+    // up on spots more than max_dist of driving away.
+    pub fn path_to_cheapest_parking_spot(
+        &self,
+        start: LaneID,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+        max_dist: Distance,
+        dist_per_dollar: Distance,
+    ) -> Result<(Vec<PathStep>, ParkingSpot, Position), ParkingSearchFailure> {
+        if map.get_l(start).parking_blackhole.is_some() {
+            return Err(ParkingSearchFailure::Blackholed);
+        }
+        let goal_pt = map.get_b(target).front_path.sidewalk.pt(map);
+        // Free spots the vehicle isn't allowed to use
+        let mut saw_restricted = false;
+        let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
+        // Each lane's length, looked up from the map the first time the search reaches it
+        let mut lane_lengths: HashMap<LaneID, Distance> = HashMap::new();
+        // This is a max-heap, so negate all distances.
+        let mut queue: BinaryHeap<(Distance, LaneID)> = BinaryHeap::new();
+        queue.push((Distance::ZERO, start));
+        // (score, lane, spot, position)
+        let mut best: Option<(Distance, LaneID, ParkingSpot, Position)> = None;
+
+        while let Some((dist_so_far, current)) = queue.pop() {
+            let dist_so_far = -dist_so_far;
+            // Everything left in the queue is even farther
+            if dist_so_far > max_dist {
+                break;
+            }
+            // Every score from here on is at least the distance driven, so nothing can beat the
+            // best spot anymore.
+            if let Some((score, _, _, _)) = best {
+                if dist_so_far >= score {
+                    break;
+                }
+            }
+            if current != start && self.has_any_parking(current) {
+                let candidates = self.get_all_free_spots(
+                    Position::new(current, Distance::ZERO),
+                    vehicle,
+                    target,
+                    map,
+                    false,
+                );
+                if candidates.is_empty() && !saw_restricted && self.any_free_spots(current) {
+                    saw_restricted = true;
+                }
+                for (spot, pos, cost) in candidates {
+                    let price = cost.map(|c| c.inner_dollars()).unwrap_or(0.0);
+                    let drive_and_price = dist_so_far + pos.dist_along() + dist_per_dollar * price;
+                    // Walking is never shorter than a straight line, so skip pathfinding for spots
+                    // that can't win anyway.
+                    let at_least = drive_and_price
+                        + self
+                            .spot_to_sidewalk_pos(spot, map)
+                            .pt(map)
+                            .dist_to(goal_pt);
+                    if best.map(|(s, _, _, _)| at_least >= s).unwrap_or(false) {
+                        continue;
+                    }
+                    let walk = if let Some(dist) = self.walking_dist(spot, target, map) {
+                        dist
+                    } else {
+                        continue;
+                    };
+                    let score = drive_and_price + walk;
+                    if best.map(|(s, _, _, _)| score < s).unwrap_or(true) {
+                        best = Some((score, current, spot, pos));
+                    }
+                }
+            }
+            let current_length = *lane_lengths
+                .entry(current)
+                .or_insert_with(|| map.get_l(current).length());
+            for turn in map.get_turns_for(current, PathConstraints::Car) {
+                if !backrefs.contains_key(&turn.id.dst) {
+                    let dist_this_step = turn.geom.length() + current_length;
+                    backrefs.insert(turn.id.dst, turn.id);
+                    queue.push((-dist_so_far - dist_this_step, turn.id.dst));
+                }
+            }
+        }
+
+        match best {
+            Some((_, lane, spot, pos)) => Ok((trace_back(start, lane, &backrefs), spot, pos)),
+            None if saw_restricted => Err(ParkingSearchFailure::AllRestricted),
+            None => Err(ParkingSearchFailure::NoFreeSpots),
+        }
     }
 
     pub fn collect_events(&mut self) -> Vec<Event> {
@@ -461,6 +1644,160 @@ impl ParkingSimState {
     }
 }
 
+// The durable part of ParkingSimState, for save files and comparing runs
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ParkingSnapshot {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub parked_cars: BTreeMap<CarID, ParkedCar>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub occupants: BTreeMap<ParkingSpot, CarID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub shared_occupants: BTreeMap<ParkingSpot, CarID>,
+    // When each parked car arrived
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap",
+        default
+    )]
+    pub arrivals: BTreeMap<CarID, Time>,
+}
+
+// Why path_to_free_parking_spot didn't find anything
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParkingSearchFailure {
+    // The search started in a parking blackhole, so most spots aren't reachable anyway
+    Blackholed,
+    // Nothing free within the search distance
+    NoFreeSpots,
+    // There are free spots nearby, but they're private, disabled, too expensive, or otherwise
+    // off-limits to this vehicle
+    AllRestricted,
+}
+
+impl fmt::Display for ParkingSearchFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParkingSearchFailure::Blackholed => write!(f, "the start lane is a parking blackhole"),
+            ParkingSearchFailure::NoFreeSpots => write!(f, "no free spot is within reach"),
+            ParkingSearchFailure::AllRestricted => {
+                write!(f, "all nearby free spots are off-limits to this vehicle")
+            }
+        }
+    }
+}
+
+// Which vehicles can park somewhere. The default allows everything.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
+pub struct VehicleClasses {
+    // None allows every type
+    pub types: Option<BTreeSet<VehicleType>>,
+    pub max_length: Option<Distance>,
+}
+
+impl VehicleClasses {
+    pub fn allows(&self, vehicle: &Vehicle) -> bool {
+        self.types
+            .as_ref()
+            .map(|types| types.contains(&vehicle.vehicle_type))
+            .unwrap_or(true)
+            && self
+                .max_length
+                .map(|max| vehicle.length <= max)
+                .unwrap_or(true)
+    }
+}
+
+// For each lane, building, and lot, (occupied spots, total spots)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParkingStats {
+    pub onstreet: BTreeMap<LaneID, (usize, usize)>,
+    pub offstreet: BTreeMap<BuildingID, (usize, usize)>,
+    pub lots: BTreeMap<ParkingLotID, (usize, usize)>,
+    pub fraction_occupied: f64,
+}
+
+// Keeps the spots whose walk is at most max_walk. Spots with no walking path are dropped.
+fn within_walk<F: Fn(ParkingSpot) -> Option<Distance>>(
+    spots: Vec<(ParkingSpot, Position, Option<Money>)>,
+    max_walk: Distance,
+    walking_dist: F,
+) -> Vec<(ParkingSpot, Position, Option<Money>)> {
+    spots
+        .into_iter()
+        .filter(|(spot, _, _)| {
+            walking_dist(*spot)
+                .map(|dist| dist <= max_walk)
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+// The PathSteps from start to end, following turns recorded while searching. Doesn't include
+// PathStep::Lane(start).
+fn trace_back(start: LaneID, end: LaneID, backrefs: &HashMap<LaneID, TurnID>) -> Vec<PathStep> {
+    let mut steps = vec![PathStep::Lane(end)];
+    let mut current = end;
+    while current != start {
+        let turn = backrefs[&current];
+        steps.push(PathStep::Turn(turn));
+        steps.push(PathStep::Lane(turn.src));
+        current = turn.src;
+    }
+    steps.pop();
+    steps.reverse();
+    steps
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryManeuver {
+    PullIn,
+    BackIn,
+}
+
+impl EntryManeuver {
+    // If the stall points more than 90 degrees away from the direction of travel, the driver has
+    // to swing past it and reverse in. Leave a degree of slack, so perpendicular stalls count as
+    // pull-in.
+    fn from_angles(approach: Angle, into_stall: Angle) -> EntryManeuver {
+        if approach.approx_eq(into_stall, 91.0) {
+            EntryManeuver::PullIn
+        } else {
+            EntryManeuver::BackIn
+        }
+    }
+}
+
+// Cars in a lot drive along the aisle nearest the stall, in whichever direction is closer to the
+// way they came in from the driveway.
+fn lot_entry_maneuver(
+    approach: Angle,
+    aisles: &Vec<Vec<Pt2D>>,
+    stall: Pt2D,
+    into_stall: Angle,
+) -> EntryManeuver {
+    let nearest_aisle = aisles
+        .iter()
+        .flat_map(|pts| pts.windows(2))
+        .filter_map(|pair| Line::maybe_new(pair[0], pair[1]))
+        .min_by_key(|l| l.project_pt(stall).dist_to(stall));
+    let travel = match nearest_aisle {
+        Some(l) if l.angle().approx_eq(approach, 90.0) => l.angle(),
+        Some(l) => l.angle().opposite(),
+        // Without aisles, all we know is the driveway.
+        None => approach,
+    };
+    EntryManeuver::from_angles(travel, into_stall)
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
 struct ParkingLane {
     parking_lane: LaneID,
@@ -468,6 +1805,13 @@ struct ParkingLane {
     sidewalk: LaneID,
     // The front of the parking spot (farthest along the lane)
     spot_dist_along: Vec<Distance>,
+    // The length of each spot
+    spot_lengths: Vec<Distance>,
+    // Applies to every spot
+    time_limit: Option<Duration>,
+    allowed_classes: VehicleClasses,
+    // Cars in angled or perpendicular spots are drawn facing the curb
+    orientation: OnstreetOrientation,
 }
 
 impl ParkingLane {
@@ -492,19 +1836,64 @@ impl ParkingLane {
             return None;
         };
 
+        let (spot_dist_along, spot_lengths): (Vec<Distance>, Vec<Distance>) =
+            ParkingLane::spots_avoiding(lane.parking_spot_lengths(), &lane.no_parking)
+                .into_iter()
+                .unzip();
         Some(ParkingLane {
             parking_lane: lane.id,
             driving_lane,
             sidewalk,
-            spot_dist_along: (0..lane.number_parking_spots())
-                .map(|idx| map_model::PARKING_SPOT_LENGTH * (2.0 + idx as f64))
-                .collect(),
+            spot_dist_along,
+            spot_lengths,
+            time_limit: lane.parking_time_limit,
+            allowed_classes: VehicleClasses {
+                types: None,
+                max_length: lane.parking_max_vehicle_length,
+            },
+            orientation: lane.parking_orientation,
         })
     }
 
+    // The front and length of each spot, skipping any spot that overlaps a no-parking interval.
+    fn spots_avoiding(
+        lengths: Vec<Distance>,
+        no_parking: &Vec<(Distance, Distance)>,
+    ) -> Vec<(Distance, Distance)> {
+        let mut rear = map_model::PARKING_SPOT_LENGTH;
+        let mut spots = Vec::new();
+        for length in lengths {
+            let front = rear + length;
+            if !no_parking
+                .iter()
+                .any(|(start, end)| rear < *end && *start < front)
+            {
+                spots.push((front, length));
+            }
+            rear = front;
+        }
+        spots
+    }
+
     fn dist_along_for_car(&self, spot_idx: usize, vehicle: &Vehicle) -> Distance {
         // Find the offset to center this particular car in the parking spot
-        self.spot_dist_along[spot_idx] - (map_model::PARKING_SPOT_LENGTH - vehicle.length) / 2.0
+        self.spot_dist_along[spot_idx] - (self.spot_lengths[spot_idx] - vehicle.length) / 2.0
+    }
+
+    // The (rear, front) distance along the lane of a car's body when parked in this spot. The
+    // body is centered and stays at least buffer away from each end of the spot, so cars in
+    // adjacent spots never overlap. Vehicles too long for that are drawn shortened.
+    fn car_body(
+        &self,
+        spot_idx: usize,
+        vehicle: &Vehicle,
+        buffer: Distance,
+    ) -> (Distance, Distance) {
+        let length = vehicle
+            .length
+            .min(self.spot_lengths[spot_idx] - buffer * 2.0);
+        let front = self.dist_along_for_car(spot_idx, vehicle) - (vehicle.length - length) / 2.0;
+        (front - length, front)
     }
 
     fn spots(&self) -> Vec<ParkingSpot> {
@@ -515,3 +1904,907 @@ impl ParkingLane {
         spots
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one_onstreet_lane(num_spots: usize) -> ParkingSimState {
+        let mut onstreet_lanes = BTreeMap::new();
+        onstreet_lanes.insert(
+            LaneID(0),
+            ParkingLane {
+                parking_lane: LaneID(0),
+                driving_lane: LaneID(1),
+                sidewalk: LaneID(2),
+                spot_dist_along: (0..num_spots)
+                    .map(|idx| map_model::PARKING_SPOT_LENGTH * (2.0 + idx as f64))
+                    .collect(),
+                spot_lengths: vec![map_model::PARKING_SPOT_LENGTH; num_spots],
+                time_limit: None,
+                allowed_classes: VehicleClasses::default(),
+                orientation: OnstreetOrientation::Parallel,
+            },
+        );
+        ParkingSimState {
+            parked_cars: BTreeMap::new(),
+            occupants: BTreeMap::new(),
+            shared_occupants: BTreeMap::new(),
+            reserved_spots: BTreeSet::new(),
+            reservations: BTreeMap::new(),
+            reserved_at: BTreeMap::new(),
+            blocked_spots: BTreeSet::new(),
+            last_vacated: BTreeMap::new(),
+            arrivals: BTreeMap::new(),
+            overstays_reported: BTreeSet::new(),
+            dwell_times: BTreeMap::new(),
+            stacked_offstreet: BTreeMap::new(),
+            parked_car_buffer: Distance::meters(0.5),
+            spot_owners: BTreeMap::new(),
+            disabled_spots: BTreeSet::new(),
+            cost_per_hour: BTreeMap::new(),
+            active_maneuvers: MultiMap::new(),
+            maneuvering: BTreeSet::new(),
+            total_spots: num_spots,
+            unavailable_spots: 0,
+            blackholed_spots: Vec::new(),
+
+            onstreet_lanes,
+            driving_to_parking_lanes: MultiMap::new(),
+            num_spots_per_offstreet: BTreeMap::new(),
+            offstreet_classes: BTreeMap::new(),
+            driving_to_offstreet: MultiMap::new(),
+            num_spots_per_lot: BTreeMap::new(),
+            lot_classes: BTreeMap::new(),
+            driving_to_lots: MultiMap::new(),
+
+            events: Vec::new(),
+        }
+    }
+
+    fn parked_car(id: usize, spot: ParkingSpot) -> ParkedCar {
+        ParkedCar {
+            vehicle: Vehicle {
+                id: CarID(id, VehicleType::Car),
+                owner: None,
+                vehicle_type: VehicleType::Car,
+                length: Distance::meters(4.5),
+                max_speed: None,
+                accessible: false,
+                parking_budget: None,
+            },
+            spot,
+        }
+    }
+
+    #[test]
+    fn test_blocked_spots() {
+        let mut sim = one_onstreet_lane(3);
+        let spot = ParkingSpot::Onstreet(LaneID(0), 1);
+        assert_eq!(3, sim.get_free_onstreet_spots(LaneID(0)).len());
+
+        sim.block_spot(spot);
+        assert!(!sim.is_free(spot));
+        assert_eq!(
+            vec![
+                ParkingSpot::Onstreet(LaneID(0), 0),
+                ParkingSpot::Onstreet(LaneID(0), 2)
+            ],
+            sim.get_free_onstreet_spots(LaneID(0))
+        );
+
+        sim.unblock_spot(spot);
+        assert!(sim.is_free(spot));
+        assert_eq!(3, sim.get_free_onstreet_spots(LaneID(0)).len());
+    }
+
+    #[test]
+    fn test_last_vacated() {
+        let mut sim = one_onstreet_lane(1);
+        let spot = ParkingSpot::Onstreet(LaneID(0), 0);
+        let t = |mins| Time::START_OF_DAY + Duration::minutes(mins);
+        assert_eq!(None, sim.last_vacated(spot));
+
+        for (idx, (arrive, leave)) in vec![(0, 10), (20, 30)].into_iter().enumerate() {
+            let car = parked_car(idx, spot);
+            sim.reserve_spot(spot, car.vehicle.id, t(arrive));
+            sim.add_parked_car(car.clone(), t(arrive));
+            sim.remove_parked_car(car, t(leave)).unwrap();
+            assert_eq!(
+                Some((CarID(idx, VehicleType::Car), t(leave))),
+                sim.last_vacated(spot)
+            );
+        }
+
+        // Still remembered while somebody else is parked there
+        let car = parked_car(2, spot);
+        sim.reserve_spot(spot, car.vehicle.id, t(40));
+        sim.add_parked_car(car, t(40));
+        assert_eq!(
+            Some((CarID(1, VehicleType::Car), t(30))),
+            sim.last_vacated(spot)
+        );
+    }
+
+    #[test]
+    fn test_likely_free_soon() {
+        let mut sim = one_onstreet_lane(2);
+        let spot = ParkingSpot::Onstreet(LaneID(0), 0);
+        let car = parked_car(0, spot);
+        let t = |mins| Time::START_OF_DAY + Duration::minutes(mins);
+
+        // The first car stays for an hour, so that's the average dwell time.
+        sim.reserve_spot(spot, car.vehicle.id, t(0));
+        sim.add_parked_car(car.clone(), t(0));
+        sim.remove_parked_car(car.clone(), t(60)).unwrap();
+        sim.reserve_spot(spot, car.vehicle.id, t(60));
+        sim.add_parked_car(car, t(60));
+
+        assert!(sim
+            .likely_free_soon(LaneID(0), Duration::minutes(1), t(90))
+            .is_empty());
+        assert_eq!(
+            vec![spot],
+            sim.likely_free_soon(LaneID(0), Duration::minutes(1), t(121))
+        );
+        assert_eq!(
+            vec![spot],
+            sim.likely_free_soon(LaneID(0), Duration::minutes(10), t(115))
+        );
+    }
+
+    #[test]
+    fn test_expire_reservations() {
+        let mut sim = one_onstreet_lane(2);
+        let t = |mins| Time::START_OF_DAY + Duration::minutes(mins);
+        let (car1, car2) = (CarID(0, VehicleType::Car), CarID(1, VehicleType::Car));
+        let (spot1, spot2) = (
+            ParkingSpot::Onstreet(LaneID(0), 0),
+            ParkingSpot::Onstreet(LaneID(0), 1),
+        );
+        sim.reserve_spot(spot1, car1, t(0));
+        sim.reserve_spot(spot2, car2, t(20));
+        assert_eq!(
+            vec![
+                Event::ParkingSpotReserved(car1, spot1),
+                Event::ParkingSpotReserved(car2, spot2)
+            ],
+            sim.collect_events()
+        );
+
+        assert!(sim
+            .expire_reservations(t(25), Duration::minutes(30))
+            .is_empty());
+        assert_eq!(
+            vec![(spot1, Some(car1))],
+            sim.expire_reservations(t(31), Duration::minutes(30))
+        );
+        assert!(sim.is_free(spot1));
+        assert_eq!(None, sim.target_spot(car1));
+        assert_eq!(Some(spot2), sim.target_spot(car2));
+        assert_eq!(0.5, sim.free_fraction());
+        assert_eq!(
+            vec![Event::ParkingReservationExpired(car1, spot1)],
+            sim.collect_events()
+        );
+
+        // Once a car starts pulling in, its reservation can't expire
+        sim.note_maneuver_start(car2, LaneID(1));
+        assert!(sim
+            .expire_reservations(t(60), Duration::minutes(30))
+            .is_empty());
+        sim.note_maneuver_finish(car2, LaneID(1));
+        assert_eq!(
+            vec![(spot2, Some(car2))],
+            sim.expire_reservations(t(60), Duration::minutes(30))
+        );
+    }
+
+    #[test]
+    fn test_overstays() {
+        let mut sim = one_onstreet_lane(2);
+        sim.onstreet_lanes.get_mut(&LaneID(0)).unwrap().time_limit = Some(Duration::hours(2));
+        let car = parked_car(0, ParkingSpot::Onstreet(LaneID(0), 0));
+        let t = |mins| Time::START_OF_DAY + Duration::minutes(mins);
+        sim.reserve_spot(car.spot, car.vehicle.id, Time::START_OF_DAY);
+        sim.add_parked_car(car.clone(), t(0));
+        sim.collect_events();
+
+        assert!(sim.find_overstayed_cars(t(120)).is_empty());
+        assert_eq!(vec![car.vehicle.id], sim.find_overstayed_cars(t(121)));
+
+        // Only reported once
+        sim.report_overstays(t(121));
+        sim.report_overstays(t(130));
+        assert_eq!(
+            vec![Event::ParkingOverstay(car.vehicle.id, car.spot)],
+            sim.collect_events()
+        );
+    }
+
+    #[test]
+    fn test_stacked_parking() {
+        let mut sim = one_onstreet_lane(0);
+        let b = BuildingID(0);
+        sim.num_spots_per_offstreet.insert(b, 2);
+        sim.make_stacked(b);
+
+        let first = parked_car(0, ParkingSpot::Offstreet(b, 0));
+        let second = parked_car(1, ParkingSpot::Offstreet(b, 1));
+        for car in vec![&first, &second] {
+            sim.reserve_spot(car.spot, car.vehicle.id, Time::START_OF_DAY);
+            sim.add_parked_car(car.clone(), Time::START_OF_DAY);
+        }
+
+        assert_eq!(
+            Err(second.vehicle.id),
+            sim.remove_parked_car(first.clone(), Time::START_OF_DAY)
+        );
+        assert!(!sim.is_free(first.spot));
+
+        assert_eq!(Ok(()), sim.remove_parked_car(second, Time::START_OF_DAY));
+        assert_eq!(Ok(()), sim.remove_parked_car(first, Time::START_OF_DAY));
+    }
+
+    #[test]
+    fn test_allowed_classes() {
+        let mut sim = one_onstreet_lane(1);
+        sim.num_spots_per_offstreet.insert(BuildingID(0), 1);
+        let onstreet = ParkingSpot::Onstreet(LaneID(0), 0);
+        let offstreet = ParkingSpot::Offstreet(BuildingID(0), 0);
+        let car = parked_car(0, onstreet).vehicle;
+        let mut truck = car.clone();
+        truck.length = Distance::meters(10.0);
+        assert!(sim.is_permitted(onstreet, &truck));
+
+        sim.set_onstreet_classes(
+            LaneID(0),
+            VehicleClasses {
+                types: None,
+                max_length: Some(Distance::meters(5.0)),
+            },
+        );
+        assert!(sim.is_permitted(onstreet, &car));
+        assert!(!sim.is_permitted(onstreet, &truck));
+
+        let mut bike = car.clone();
+        bike.vehicle_type = VehicleType::Motorcycle;
+        sim.set_offstreet_classes(
+            BuildingID(0),
+            VehicleClasses {
+                types: Some(vec![VehicleType::Motorcycle].into_iter().collect()),
+                max_length: None,
+            },
+        );
+        assert!(!sim.is_permitted(offstreet, &car));
+        assert!(sim.is_permitted(offstreet, &bike));
+    }
+
+    #[test]
+    fn test_evacuate_lot() {
+        let mut sim = one_onstreet_lane(1);
+        sim.num_spots_per_lot.insert(ParkingLotID(0), 4);
+        sim.total_spots += 4;
+        let spot = |idx| ParkingSpot::Lot(ParkingLotID(0), idx);
+        for idx in 0..2 {
+            sim.reserve_spot(spot(idx), CarID(idx, VehicleType::Car), Time::START_OF_DAY);
+            sim.add_parked_car(parked_car(idx, spot(idx)), Time::START_OF_DAY);
+        }
+        sim.reserve_spot(spot(2), CarID(2, VehicleType::Car), Time::START_OF_DAY);
+        // This car is already pulling in, so it keeps its spot
+        sim.reserve_spot(spot(3), CarID(3, VehicleType::Car), Time::START_OF_DAY);
+        sim.note_maneuver_start(CarID(3, VehicleType::Car), LaneID(1));
+        sim.collect_events();
+
+        let displaced = sim.evacuate_lot(ParkingLotID(0), Time::START_OF_DAY);
+        assert_eq!(
+            vec![parked_car(0, spot(0)), parked_car(1, spot(1))],
+            displaced
+        );
+        assert_eq!(3, sim.count_free_lot_spots(ParkingLotID(0)));
+        assert_eq!(4, sim.available_spots());
+        assert_eq!(None, sim.target_spot(CarID(2, VehicleType::Car)));
+        assert_eq!(Some(spot(3)), sim.target_spot(CarID(3, VehicleType::Car)));
+        assert_eq!(
+            3,
+            sim.collect_events()
+                .into_iter()
+                .filter(|ev| match ev {
+                    Event::CarLeftParkingSpot(_, _) | Event::ParkingReservationExpired(_, _) =>
+                        true,
+                    _ => false,
+                })
+                .count()
+        );
+
+        sim.note_maneuver_finish(CarID(3, VehicleType::Car), LaneID(1));
+        sim.add_parked_car(parked_car(3, spot(3)), Time::START_OF_DAY);
+        assert_eq!(3, sim.count_free_lot_spots(ParkingLotID(0)));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut sim = one_onstreet_lane(3);
+        let spot = |idx| ParkingSpot::Onstreet(LaneID(0), idx);
+        sim.reserve_spot(spot(0), CarID(0, VehicleType::Car), Time::START_OF_DAY);
+        sim.add_parked_car(parked_car(0, spot(0)), Time::START_OF_DAY);
+        let snapshot = sim.snapshot();
+
+        sim.reserve_spot(spot(1), CarID(1, VehicleType::Car), Time::START_OF_DAY);
+        sim.add_parked_car(parked_car(1, spot(1)), Time::START_OF_DAY);
+        sim.reserve_spot(spot(2), CarID(2, VehicleType::Car), Time::START_OF_DAY);
+        sim.block_spot(spot(2));
+        assert_eq!(0, sim.available_spots());
+
+        let later = Time::START_OF_DAY + Duration::hours(1);
+        sim.restore_snapshot(snapshot.clone(), later);
+        assert_eq!(snapshot, sim.snapshot());
+        assert_eq!(None, sim.target_spot(CarID(2, VehicleType::Car)));
+        // The blocked spot is still unavailable
+        assert_eq!(1, sim.available_spots());
+        assert!(sim.is_free(spot(1)));
+
+        // Cars keep their original arrival time, unless the snapshot doesn't know it
+        let car = CarID(0, VehicleType::Car);
+        assert_eq!(Some(&Time::START_OF_DAY), sim.arrivals.get(&car));
+        let mut old_snapshot = snapshot;
+        old_snapshot.arrivals.clear();
+        sim.restore_snapshot(old_snapshot, later);
+        assert_eq!(Some(&later), sim.arrivals.get(&car));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_check_invariants() {
+        let mut sim = one_onstreet_lane(2);
+        let car = parked_car(0, ParkingSpot::Onstreet(LaneID(0), 0));
+        sim.reserve_spot(car.spot, car.vehicle.id, Time::START_OF_DAY);
+        sim.add_parked_car(car.clone(), Time::START_OF_DAY);
+        sim.check_invariants();
+
+        sim.occupants
+            .insert(ParkingSpot::Onstreet(LaneID(0), 1), car.vehicle.id);
+        sim.check_invariants();
+    }
+
+    #[test]
+    fn test_all_spots() {
+        let mut sim = one_onstreet_lane(2);
+        sim.num_spots_per_lot.insert(ParkingLotID(0), 2);
+        sim.block_spot(ParkingSpot::Lot(ParkingLotID(0), 1));
+        sim.block_spot(ParkingSpot::Onstreet(LaneID(0), 0));
+
+        assert_eq!(
+            vec![
+                ParkingSpot::Lot(ParkingLotID(0), 0),
+                ParkingSpot::Lot(ParkingLotID(0), 1)
+            ],
+            sim.all_lot_spots(ParkingLotID(0))
+        );
+        assert_eq!(2, sim.all_onstreet_spots(LaneID(0)).len());
+        assert!(sim.all_offstreet_spots(BuildingID(0)).is_empty());
+        assert!(sim.all_lot_spots(ParkingLotID(1)).is_empty());
+    }
+
+    #[test]
+    fn test_count_free_spots() {
+        let mut sim = one_onstreet_lane(3);
+        sim.num_spots_per_offstreet.insert(BuildingID(0), 2);
+        sim.block_spot(ParkingSpot::Onstreet(LaneID(0), 1));
+        sim.block_spot(ParkingSpot::Offstreet(BuildingID(0), 0));
+
+        assert_eq!(
+            sim.get_free_onstreet_spots(LaneID(0)).len(),
+            sim.count_free_onstreet_spots(LaneID(0))
+        );
+        assert_eq!(2, sim.count_free_onstreet_spots(LaneID(0)));
+        assert_eq!(1, sim.count_free_offstreet_spots(BuildingID(0)));
+        assert_eq!(0, sim.count_free_lot_spots(ParkingLotID(0)));
+        assert_eq!(0, sim.count_free_onstreet_spots(LaneID(5)));
+    }
+
+    #[test]
+    fn test_free_fraction() {
+        let mut sim = one_onstreet_lane(5);
+        let spot = |idx| ParkingSpot::Onstreet(LaneID(0), idx);
+        let brute_force = |sim: &ParkingSimState| {
+            let (filled, available) = sim.get_all_parking_spots();
+            (available.len() as f64) / ((filled.len() + available.len()) as f64)
+        };
+        assert_eq!(1.0, sim.free_fraction());
+
+        sim.reserve_spot(spot(0), CarID(0, VehicleType::Car), Time::START_OF_DAY);
+        sim.add_parked_car(parked_car(0, spot(0)), Time::START_OF_DAY);
+        sim.reserve_spot(spot(1), CarID(1, VehicleType::Car), Time::START_OF_DAY);
+        sim.block_spot(spot(2));
+        // Blocking an occupied spot doesn't change anything
+        sim.block_spot(spot(0));
+        assert_eq!(brute_force(&sim), sim.free_fraction());
+        assert_eq!(0.4, sim.free_fraction());
+
+        sim.unblock_spot(spot(2));
+        sim.remove_parked_car(parked_car(0, spot(0)), Time::START_OF_DAY)
+            .unwrap();
+        assert_eq!(brute_force(&sim), sim.free_fraction());
+        assert_eq!(0.6, sim.free_fraction());
+        assert_eq!(5, sim.total_spots());
+        assert_eq!(3, sim.available_spots());
+
+        sim.expire_reservations(
+            Time::START_OF_DAY + Duration::hours(1),
+            Duration::minutes(30),
+        );
+        assert_eq!(4, sim.available_spots());
+    }
+
+    #[test]
+    fn test_utilization() {
+        let mut sim = one_onstreet_lane(3);
+        sim.num_spots_per_offstreet.insert(BuildingID(0), 1);
+        for (idx, spot) in vec![
+            ParkingSpot::Onstreet(LaneID(0), 2),
+            ParkingSpot::Offstreet(BuildingID(0), 0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let car = parked_car(idx, spot);
+            sim.reserve_spot(spot, car.vehicle.id, Time::START_OF_DAY);
+            sim.add_parked_car(car, Time::START_OF_DAY);
+        }
+
+        let stats = sim.utilization();
+        assert_eq!(Some(&(1, 3)), stats.onstreet.get(&LaneID(0)));
+        assert_eq!(Some(&(1, 1)), stats.offstreet.get(&BuildingID(0)));
+        assert!(stats.lots.is_empty());
+        assert_eq!(0.5, stats.fraction_occupied);
+    }
+
+    #[test]
+    fn test_adjacent_bodies_dont_overlap() {
+        let sim = one_onstreet_lane(3);
+        let lane = &sim.onstreet_lanes[&LaneID(0)];
+        let mut long_car = parked_car(0, ParkingSpot::Onstreet(LaneID(0), 1)).vehicle;
+        long_car.length = Distance::meters(12.0);
+        let normal_car = parked_car(1, ParkingSpot::Onstreet(LaneID(0), 0)).vehicle;
+
+        let (rear1, front1) = lane.car_body(0, &normal_car, sim.parked_car_buffer);
+        let (rear2, front2) = lane.car_body(1, &long_car, sim.parked_car_buffer);
+        let (rear3, _) = lane.car_body(2, &normal_car, sim.parked_car_buffer);
+        assert_eq!(normal_car.length, front1 - rear1);
+        assert!(front1 + sim.parked_car_buffer * 2.0 <= rear2);
+        assert!(front2 + sim.parked_car_buffer * 2.0 <= rear3);
+    }
+
+    #[test]
+    fn test_within_walk() {
+        let spot = |idx| ParkingSpot::Onstreet(LaneID(0), idx);
+        let spots: Vec<(ParkingSpot, Position, Option<Money>)> = (0..3)
+            .map(|idx| {
+                (
+                    spot(idx),
+                    Position::new(LaneID(1), Distance::meters(idx as f64)),
+                    None,
+                )
+            })
+            .collect();
+        let walking_dist = |s: ParkingSpot| match s {
+            ParkingSpot::Onstreet(_, 0) => Some(Distance::meters(50.0)),
+            ParkingSpot::Onstreet(_, 1) => Some(Distance::meters(150.0)),
+            _ => None,
+        };
+
+        assert_eq!(
+            vec![spot(0), spot(1)],
+            within_walk(spots.clone(), Distance::meters(200.0), walking_dist)
+                .into_iter()
+                .map(|(s, _, _)| s)
+                .collect::<Vec<_>>()
+        );
+        // Tightening the radius drops the farther spot, even though it's free
+        assert_eq!(
+            vec![spot(0)],
+            within_walk(spots, Distance::meters(100.0), walking_dist)
+                .into_iter()
+                .map(|(s, _, _)| s)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_least_crowded() {
+        let mut sim = one_onstreet_lane(2);
+        let mut other_lane = sim.onstreet_lanes[&LaneID(0)].clone();
+        other_lane.parking_lane = LaneID(3);
+        sim.onstreet_lanes.insert(LaneID(3), other_lane);
+
+        let candidates = vec![
+            (ParkingSpot::Onstreet(LaneID(0), 0), Distance::meters(10.0)),
+            (ParkingSpot::Onstreet(LaneID(0), 1), Distance::meters(20.0)),
+            (ParkingSpot::Onstreet(LaneID(3), 0), Distance::meters(30.0)),
+        ];
+        let first = sim.least_crowded(candidates.clone()).unwrap();
+        assert_eq!(ParkingSpot::Onstreet(LaneID(0), 0), first);
+        sim.reserve_spot(first, CarID(0, VehicleType::Car), Time::START_OF_DAY);
+
+        // The other lane is a longer walk, but nobody's headed there yet.
+        let second = sim
+            .least_crowded(
+                candidates
+                    .into_iter()
+                    .filter(|(spot, _)| sim.is_free(*spot))
+                    .collect(),
+            )
+            .unwrap();
+        assert_eq!(ParkingSpot::Onstreet(LaneID(3), 0), second);
+    }
+
+    #[test]
+    fn test_reserve_near_spreads_out() {
+        let mut sim = one_onstreet_lane(2);
+        let mut other_lane = sim.onstreet_lanes[&LaneID(0)].clone();
+        other_lane.parking_lane = LaneID(3);
+        sim.onstreet_lanes.insert(LaneID(3), other_lane);
+        sim.total_spots += 2;
+        let candidates = |sim: &ParkingSimState| {
+            vec![
+                (ParkingSpot::Onstreet(LaneID(0), 0), Distance::meters(10.0)),
+                (ParkingSpot::Onstreet(LaneID(0), 1), Distance::meters(20.0)),
+                (ParkingSpot::Onstreet(LaneID(3), 0), Distance::meters(30.0)),
+            ]
+            .into_iter()
+            .filter(|(spot, _)| sim.is_free(*spot))
+            .collect::<Vec<_>>()
+        };
+
+        let first = sim
+            .reserve_least_crowded(
+                candidates(&sim),
+                CarID(0, VehicleType::Car),
+                Time::START_OF_DAY,
+            )
+            .unwrap();
+        let second = sim
+            .reserve_least_crowded(
+                candidates(&sim),
+                CarID(1, VehicleType::Car),
+                Time::START_OF_DAY,
+            )
+            .unwrap();
+        assert_eq!(ParkingSpot::Onstreet(LaneID(0), 0), first);
+        assert_eq!(ParkingSpot::Onstreet(LaneID(3), 0), second);
+        assert_eq!(Some(first), sim.target_spot(CarID(0, VehicleType::Car)));
+        assert_eq!(Some(second), sim.target_spot(CarID(1, VehicleType::Car)));
+
+        assert_eq!(
+            Some(ParkingSpot::Onstreet(LaneID(0), 1)),
+            sim.reserve_least_crowded(
+                candidates(&sim),
+                CarID(2, VehicleType::Car),
+                Time::START_OF_DAY
+            )
+        );
+        assert_eq!(
+            None,
+            sim.reserve_least_crowded(
+                candidates(&sim),
+                CarID(3, VehicleType::Car),
+                Time::START_OF_DAY
+            )
+        );
+    }
+
+    #[test]
+    fn test_assigned_spots() {
+        let mut sim = one_onstreet_lane(2);
+        let spot = ParkingSpot::Onstreet(LaneID(0), 0);
+        sim.assign_spot(spot, PersonID(1));
+
+        let mut owner = parked_car(0, spot).vehicle;
+        owner.owner = Some(PersonID(1));
+        let mut someone_else = parked_car(1, spot).vehicle;
+        someone_else.owner = Some(PersonID(2));
+        let unowned = parked_car(2, spot).vehicle;
+
+        assert!(sim.is_permitted(spot, &owner));
+        assert!(!sim.is_permitted(spot, &someone_else));
+        assert!(!sim.is_permitted(spot, &unowned));
+        // Other spots are fine
+        assert!(sim.is_permitted(ParkingSpot::Onstreet(LaneID(0), 1), &someone_else));
+
+        sim.unassign_spot(spot);
+        assert!(sim.is_permitted(spot, &someone_else));
+    }
+
+    #[test]
+    fn test_disabled_spots() {
+        let mut sim = one_onstreet_lane(2);
+        let spot = ParkingSpot::Onstreet(LaneID(0), 0);
+        sim.disabled_spots.insert(spot);
+        assert!(sim.is_disabled_spot(spot));
+
+        let mut permit = parked_car(0, spot).vehicle;
+        permit.accessible = true;
+        let no_permit = parked_car(1, spot).vehicle;
+        assert!(sim.is_permitted(spot, &permit));
+        assert!(!sim.is_permitted(spot, &no_permit));
+        assert!(sim.is_permitted(ParkingSpot::Onstreet(LaneID(0), 1), &no_permit));
+    }
+
+    #[test]
+    fn test_parking_budget() {
+        let mut sim = one_onstreet_lane(2);
+        let spot = ParkingSpot::Onstreet(LaneID(0), 0);
+        sim.set_cost_per_hour(spot, Some(Money::dollars(3.0)));
+        assert_eq!(Some(Money::dollars(3.0)), sim.get_cost_per_hour(spot));
+
+        let mut thrifty = parked_car(0, spot).vehicle;
+        thrifty.parking_budget = Some(Money::dollars(2.5));
+        let unlimited = parked_car(1, spot).vehicle;
+        assert!(!sim.is_permitted(spot, &thrifty));
+        assert!(sim.is_permitted(spot, &unlimited));
+        assert!(sim.is_permitted(ParkingSpot::Onstreet(LaneID(0), 1), &thrifty));
+
+        sim.set_cost_per_hour(spot, None);
+        assert!(sim.is_permitted(spot, &thrifty));
+    }
+
+    #[test]
+    fn test_all_parking_spots_sorted() {
+        let mut sim = one_onstreet_lane(2);
+        sim.num_spots_per_offstreet.insert(BuildingID(0), 2);
+        sim.block_spot(ParkingSpot::Offstreet(BuildingID(0), 0));
+        sim.block_spot(ParkingSpot::Onstreet(LaneID(0), 1));
+
+        assert_eq!(
+            (
+                vec![
+                    ParkingSpot::Onstreet(LaneID(0), 1),
+                    ParkingSpot::Offstreet(BuildingID(0), 0),
+                ],
+                vec![
+                    ParkingSpot::Onstreet(LaneID(0), 0),
+                    ParkingSpot::Offstreet(BuildingID(0), 1),
+                ]
+            ),
+            sim.get_all_parking_spots_sorted()
+        );
+    }
+
+    #[test]
+    fn test_entry_maneuver() {
+        let east = Angle::new_degs(0.0);
+
+        // Perpendicular stalls on either side of the aisle can be pulled into directly.
+        assert_eq!(
+            EntryManeuver::PullIn,
+            EntryManeuver::from_angles(east, Angle::new_degs(90.0))
+        );
+        assert_eq!(
+            EntryManeuver::PullIn,
+            EntryManeuver::from_angles(east, Angle::new_degs(-90.0))
+        );
+        // Angled with the flow of traffic
+        assert_eq!(
+            EntryManeuver::PullIn,
+            EntryManeuver::from_angles(east, Angle::new_degs(45.0))
+        );
+        // Angled against the flow of traffic
+        assert_eq!(
+            EntryManeuver::BackIn,
+            EntryManeuver::from_angles(east, Angle::new_degs(135.0))
+        );
+        assert_eq!(
+            EntryManeuver::BackIn,
+            EntryManeuver::from_angles(east, Angle::new_degs(-150.0))
+        );
+    }
+
+    #[test]
+    fn test_lot_entry_maneuver() {
+        // One aisle heading east, and another far away heading north
+        let aisles = vec![
+            vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)],
+            vec![Pt2D::new(200.0, 0.0), Pt2D::new(200.0, -100.0)],
+        ];
+        // Angled against the aisle's direction
+        let stall = Pt2D::new(50.0, 5.0);
+        let into_stall = Angle::new_degs(135.0);
+
+        // Coming in heading roughly east, the car drives east down the aisle and has to back in.
+        assert_eq!(
+            EntryManeuver::BackIn,
+            lot_entry_maneuver(Angle::new_degs(20.0), &aisles, stall, into_stall)
+        );
+        // Coming in heading roughly west, the same stall can be pulled into.
+        assert_eq!(
+            EntryManeuver::PullIn,
+            lot_entry_maneuver(Angle::new_degs(200.0), &aisles, stall, into_stall)
+        );
+        // The driveway alone would say to pull in; the aisle decides.
+        assert_eq!(
+            EntryManeuver::PullIn,
+            EntryManeuver::from_angles(Angle::new_degs(90.0), into_stall)
+        );
+        assert_eq!(
+            EntryManeuver::BackIn,
+            lot_entry_maneuver(Angle::new_degs(80.0), &aisles, stall, into_stall)
+        );
+    }
+
+    #[test]
+    fn test_no_parking_interval() {
+        // Spots are 8m long, and the first one spans [8m, 16m]. A driveway at [27m, 34m] touches
+        // the spots spanning [24m, 32m] and [32m, 40m].
+        let lengths = vec![map_model::PARKING_SPOT_LENGTH; 5];
+        let spots: Vec<Distance> = ParkingLane::spots_avoiding(
+            lengths.clone(),
+            &vec![(Distance::meters(27.0), Distance::meters(34.0))],
+        )
+        .into_iter()
+        .map(|(front, _)| front)
+        .collect();
+        assert_eq!(
+            vec![
+                Distance::meters(16.0),
+                Distance::meters(24.0),
+                Distance::meters(48.0)
+            ],
+            spots
+        );
+
+        assert_eq!(5, ParkingLane::spots_avoiding(lengths, &Vec::new()).len());
+    }
+
+    #[test]
+    fn test_variable_spot_lengths() {
+        let lengths = vec![
+            Distance::meters(5.0),
+            Distance::meters(8.0),
+            Distance::meters(5.0),
+        ];
+        assert_eq!(
+            vec![
+                (Distance::meters(13.0), Distance::meters(5.0)),
+                (Distance::meters(21.0), Distance::meters(8.0)),
+                (Distance::meters(26.0), Distance::meters(5.0))
+            ],
+            ParkingLane::spots_avoiding(lengths, &Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_target_spot() {
+        let mut sim = one_onstreet_lane(2);
+        let car = parked_car(0, ParkingSpot::Onstreet(LaneID(0), 1));
+        assert_eq!(None, sim.target_spot(car.vehicle.id));
+
+        sim.reserve_spot(car.spot, car.vehicle.id, Time::START_OF_DAY);
+        assert_eq!(Some(car.spot), sim.target_spot(car.vehicle.id));
+
+        sim.add_parked_car(car.clone(), Time::START_OF_DAY);
+        assert_eq!(None, sim.target_spot(car.vehicle.id));
+    }
+
+    #[test]
+    fn test_reserve_again() {
+        let mut sim = one_onstreet_lane(2);
+        let spot = |idx| ParkingSpot::Onstreet(LaneID(0), idx);
+        let vehicle = parked_car(0, spot(0)).vehicle;
+        sim.reserve_spot(spot(0), vehicle.id, Time::START_OF_DAY);
+        assert!(sim.is_free_for(spot(0), &vehicle));
+        assert!(!sim.is_free_for(spot(0), &parked_car(1, spot(0)).vehicle));
+
+        // Like when the car starts pulling into the spot it reserved earlier
+        sim.reserve_spot(spot(0), vehicle.id, Time::START_OF_DAY);
+        assert_eq!(1, sim.available_spots());
+
+        // Switching to another spot doesn't leak the old one
+        sim.reserve_spot(spot(1), vehicle.id, Time::START_OF_DAY);
+        assert!(sim.is_free(spot(0)));
+        assert_eq!(Some(spot(1)), sim.target_spot(vehicle.id));
+        assert_eq!(1, sim.available_spots());
+        assert_eq!(
+            vec![spot(1)],
+            sim.expire_reservations(Time::START_OF_DAY + Duration::hours(1), Duration::ZERO)
+                .into_iter()
+                .map(|(spot, _)| spot)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_spots_owned_by() {
+        let mut sim = one_onstreet_lane(3);
+        for (idx, owner) in vec![Some(PersonID(1)), None, Some(PersonID(1))]
+            .into_iter()
+            .enumerate()
+        {
+            let mut car = parked_car(idx, ParkingSpot::Onstreet(LaneID(0), idx));
+            car.vehicle.owner = owner;
+            sim.reserve_spot(car.spot, car.vehicle.id, Time::START_OF_DAY);
+            sim.add_parked_car(car, Time::START_OF_DAY);
+        }
+
+        assert_eq!(
+            vec![
+                (
+                    CarID(0, VehicleType::Car),
+                    ParkingSpot::Onstreet(LaneID(0), 0)
+                ),
+                (
+                    CarID(2, VehicleType::Car),
+                    ParkingSpot::Onstreet(LaneID(0), 2)
+                )
+            ],
+            sim.spots_owned_by(PersonID(1))
+        );
+        assert!(sim.spots_owned_by(PersonID(2)).is_empty());
+    }
+
+    #[test]
+    fn test_motorcycles_share_spot() {
+        let mut sim = one_onstreet_lane(1);
+        let spot = ParkingSpot::Onstreet(LaneID(0), 0);
+        let mut motorcycles = Vec::new();
+        for id in 0..3 {
+            let mut p = parked_car(id, spot);
+            p.vehicle.id = CarID(id, VehicleType::Motorcycle);
+            p.vehicle.vehicle_type = VehicleType::Motorcycle;
+            p.vehicle.length = Distance::meters(2.0);
+            motorcycles.push(p);
+        }
+        let car = parked_car(3, spot);
+        let t = |mins| Time::START_OF_DAY + Duration::minutes(mins);
+
+        sim.reserve_spot(spot, motorcycles[0].vehicle.id, t(0));
+        sim.add_parked_car(motorcycles[0].clone(), t(0));
+        assert!(!sim.is_free_for(spot, &car.vehicle));
+        assert!(sim.is_free_for(spot, &motorcycles[1].vehicle));
+
+        sim.reserve_spot(spot, motorcycles[1].vehicle.id, t(10));
+        sim.add_parked_car(motorcycles[1].clone(), t(10));
+        assert!(!sim.is_free_for(spot, &motorcycles[2].vehicle));
+        assert_eq!(0, sim.count_free_onstreet_spots(LaneID(0)));
+
+        // The second one stays put when the first leaves, and keeps its own arrival time
+        sim.remove_parked_car(motorcycles[0].clone(), t(30))
+            .unwrap();
+        assert!(sim.lookup_parked_car(motorcycles[1].vehicle.id).is_some());
+        assert!(sim.is_free_for(spot, &motorcycles[2].vehicle));
+        assert_eq!(Some(&t(10)), sim.arrivals.get(&motorcycles[1].vehicle.id));
+
+        sim.remove_parked_car(motorcycles[1].clone(), t(40))
+            .unwrap();
+        assert!(sim.is_free(spot));
+        assert_eq!(
+            Some((motorcycles[1].vehicle.id, t(40))),
+            sim.last_vacated(spot)
+        );
+        assert_eq!(
+            Some(&(2, Duration::minutes(60))),
+            sim.dwell_times.get(&spot)
+        );
+    }
+
+    #[test]
+    fn test_shared_spot_overstays() {
+        let mut sim = one_onstreet_lane(1);
+        sim.onstreet_lanes.get_mut(&LaneID(0)).unwrap().time_limit = Some(Duration::hours(1));
+        let spot = ParkingSpot::Onstreet(LaneID(0), 0);
+        let t = |mins| Time::START_OF_DAY + Duration::minutes(mins);
+        let mut ids = Vec::new();
+        for (id, arrive) in vec![(0, 0), (1, 30)] {
+            let mut p = parked_car(id, spot);
+            p.vehicle.id = CarID(id, VehicleType::Motorcycle);
+            p.vehicle.vehicle_type = VehicleType::Motorcycle;
+            p.vehicle.length = Distance::meters(2.0);
+            ids.push(p.vehicle.id);
+            sim.reserve_spot(spot, p.vehicle.id, t(arrive));
+            sim.add_parked_car(p, t(arrive));
+        }
+
+        assert_eq!(vec![ids[0]], sim.find_overstayed_cars(t(70)));
+        assert_eq!(ids, sim.find_overstayed_cars(t(100)));
+    }
+}
@@ -1,6 +1,6 @@
 use crate::mechanics::car::{Car, CarState};
-use crate::{CarID, FOLLOWING_DISTANCE};
-use geom::{Distance, Time};
+use crate::{following_distance, CarID, FOLLOWING_DISTANCE};
+use geom::{Distance, Duration, Speed, Time};
 use map_model::{Map, Traversable};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
@@ -131,6 +131,14 @@ impl Queue {
             }
 
             let car = &cars[id];
+            // Moving cars leave a bigger gap behind their leader, but never get pushed back behind
+            // where they started crossing.
+            let bound = match (result.is_empty(), &car.state) {
+                (false, CarState::Crossing(_, dist_int)) => (bound
+                    - (following_gap(car, now) - FOLLOWING_DISTANCE))
+                    .max(dist_int.start.min(bound)),
+                _ => bound,
+            };
             let front = match car.state {
                 CarState::Queued { .. } => {
                     if car.router.last_step() {
@@ -227,6 +235,23 @@ impl Queue {
     }
 }
 
+// How far a car stays behind its leader. A crossing car keeps the gap for its speed, but it's
+// about to stop at the end of the crossing, so the gap shrinks to FOLLOWING_DISTANCE along the way.
+// That's what every car that isn't moving keeps, so nobody jumps when they switch states.
+fn following_gap(car: &Car, now: Time) -> Distance {
+    match car.state {
+        CarState::Crossing(ref time_int, ref dist_int) => {
+            let duration = time_int.end - time_int.start;
+            if duration == Duration::ZERO {
+                return FOLLOWING_DISTANCE;
+            }
+            let speed = Speed::from_dist_time(dist_int.end - dist_int.start, duration);
+            following_distance(speed * (1.0 - time_int.percent_clamp_end(now)))
+        }
+        _ => FOLLOWING_DISTANCE,
+    }
+}
+
 fn validate_positions(
     dists: Vec<(CarID, Distance)>,
     cars: &BTreeMap<CarID, Car>,
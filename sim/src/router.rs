@@ -1,6 +1,7 @@
 use crate::mechanics::Queue;
 use crate::{
-    Event, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot, TripID, TripPhaseType, Vehicle,
+    Event, Money, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot, TripID, TripPhaseType,
+    Vehicle, MAX_PARKING_SEARCH_DIST, PARKING_DIST_PER_DOLLAR,
 };
 use geom::Distance;
 use map_model::{
@@ -201,7 +202,7 @@ impl Router {
                 }
 
                 let need_new_spot = match spot {
-                    Some((s, _)) => !parking.is_free(*s),
+                    Some((s, _)) => !parking.is_free_for(*s, vehicle),
                     None => true,
                 };
                 if need_new_spot {
@@ -211,27 +212,36 @@ impl Router {
                         vehicle,
                         target,
                         map,
+                        false,
                     );
+                    // Drivers on a budget go for the cheapest spot first
+                    let price = |cost: &Option<Money>| {
+                        if vehicle.parking_budget.is_some() {
+                            cost.unwrap_or(Money(0))
+                        } else {
+                            Money(0)
+                        }
+                    };
                     let best = if let Some(ref p) = map.get_b(target).parking {
                         if p.driving_pos.lane() == current_lane {
                             let target_dist = p.driving_pos.dist_along();
                             // Closest to the building
-                            candidates
-                                .into_iter()
-                                .min_by_key(|(_, pos)| (pos.dist_along() - target_dist).abs())
+                            candidates.into_iter().min_by_key(|(_, pos, cost)| {
+                                (price(cost), (pos.dist_along() - target_dist).abs())
+                            })
                         } else {
                             // Closest to the road endpoint, I guess
                             candidates
                                 .into_iter()
-                                .min_by_key(|(_, pos)| pos.dist_along())
+                                .min_by_key(|(_, pos, cost)| (price(cost), pos.dist_along()))
                         }
                     } else {
                         // Closest to the road endpoint, I guess
                         candidates
                             .into_iter()
-                            .min_by_key(|(_, pos)| pos.dist_along())
+                            .min_by_key(|(_, pos, cost)| (price(cost), pos.dist_along()))
                     };
-                    if let Some((new_spot, new_pos)) = best {
+                    if let Some((new_spot, new_pos, _)) = best {
                         if let Some((t, p)) = trip_and_person {
                             events.push(Event::TripPhaseStarting(
                                 t,
@@ -246,34 +256,53 @@ impl Router {
                         }
                         *spot = Some((new_spot, new_pos.dist_along()));
                     } else {
-                        if let Some((new_path_steps, new_spot, new_pos)) =
-                            parking.path_to_free_parking_spot(current_lane, vehicle, target, map)
-                        {
-                            *spot = Some((new_spot, new_pos.dist_along()));
-                            for step in new_path_steps {
-                                self.path.add(step, map);
+                        let search = if vehicle.parking_budget.is_some() {
+                            parking.path_to_cheapest_parking_spot(
+                                current_lane,
+                                vehicle,
+                                target,
+                                map,
+                                MAX_PARKING_SEARCH_DIST,
+                                PARKING_DIST_PER_DOLLAR,
+                            )
+                        } else {
+                            parking.path_to_free_parking_spot(
+                                current_lane,
+                                vehicle,
+                                target,
+                                map,
+                                MAX_PARKING_SEARCH_DIST,
+                            )
+                        };
+                        match search {
+                            Ok((new_path_steps, new_spot, new_pos)) => {
+                                *spot = Some((new_spot, new_pos.dist_along()));
+                                for step in new_path_steps {
+                                    self.path.add(step, map);
+                                }
+                                events.push(Event::PathAmended(self.path.clone()));
+                                // TODO This path might not be the same as the one found here...
+                                if let Some((t, p)) = trip_and_person {
+                                    events.push(Event::TripPhaseStarting(
+                                        t,
+                                        p,
+                                        Some(PathRequest {
+                                            start: Position::new(current_lane, front),
+                                            end: new_pos,
+                                            constraints: PathConstraints::Car,
+                                        }),
+                                        TripPhaseType::Parking,
+                                    ));
+                                }
                             }
-                            events.push(Event::PathAmended(self.path.clone()));
-                            // TODO This path might not be the same as the one found here...
-                            if let Some((t, p)) = trip_and_person {
-                                events.push(Event::TripPhaseStarting(
-                                    t,
-                                    p,
-                                    Some(PathRequest {
-                                        start: Position::new(current_lane, front),
-                                        end: new_pos,
-                                        constraints: PathConstraints::Car,
-                                    }),
-                                    TripPhaseType::Parking,
-                                ));
+                            Err(reason) => {
+                                println!(
+                                    "WARNING: {} can't find parking on {} or anywhere reachable \
+                                     from it: {}",
+                                    vehicle.id, current_lane, reason
+                                );
+                                *stuck_end_dist = Some(map.get_l(current_lane).length());
                             }
-                        } else {
-                            println!(
-                                "WARNING: {} can't find parking on {} or anywhere reachable from \
-                                 it. Possibly we're just totally out of parking space!",
-                                vehicle.id, current_lane
-                            );
-                            *stuck_end_dist = Some(map.get_l(current_lane).length());
                         }
                         return Some(ActionAtEnd::GotoLaneEnd);
                     }
@@ -379,6 +408,15 @@ impl Router {
         self.path.modify_step(3, PathStep::Turn(turn2), map);
     }
 
+    // If the car was headed for this spot, it'll have to look again once it gets there.
+    pub fn forget_parking_spot(&mut self, lost: ParkingSpot) {
+        if let Goal::ParkNearBuilding { ref mut spot, .. } = self.goal {
+            if spot.map(|(s, _)| s == lost).unwrap_or(false) {
+                *spot = None;
+            }
+        }
+    }
+
     pub fn replace_path_for_serialization(&mut self, path: Path) -> Path {
         std::mem::replace(&mut self.path, path)
     }
@@ -1,10 +1,11 @@
 use crate::{
     AgentID, AlertLocation, Analytics, CarID, Command, CreateCar, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, DrivingSimState, Event, GetDrawAgents, IntersectionSimState, OrigPersonID,
-    PandemicModel, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, Person, PersonID,
-    PersonState, Router, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripEndpoint,
-    TripID, TripManager, TripMode, TripPhaseType, TripResult, TripSpawner, UnzoomedAgent, Vehicle,
-    VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH, MIN_CAR_LENGTH,
+    DrawPedestrianInput, DrivingSimState, Event, GetDrawAgents, IntersectionSimState, Money,
+    OrigPersonID, PandemicModel, ParkedCar, ParkingSimState, ParkingSnapshot, ParkingSpot,
+    ParkingStats, PedestrianID, Person, PersonID, PersonState, Router, Scheduler, SidewalkPOI,
+    SidewalkSpot, TransitSimState, TripEndpoint, TripID, TripManager, TripMode, TripPhaseType,
+    TripResult, TripSpawner, UnzoomedAgent, Vehicle, VehicleClasses, VehicleSpec, VehicleType,
+    WalkingSimState, BUS_LENGTH, MAX_PARKING_SEARCH_DIST, MIN_CAR_LENGTH,
 };
 use abstutil::Timer;
 use derivative::Derivative;
@@ -21,6 +22,8 @@ use std::panic;
 
 // TODO Do something else.
 const BLIND_RETRY_TO_SPAWN: Duration = Duration::const_seconds(5.0);
+// Give up on a trip if its car is stuck in a stacked garage for this long
+const MAX_TIME_BLOCKED_IN_GARAGE: Duration = Duration::const_seconds(3600.0);
 
 #[derive(Serialize, Deserialize, Clone, Derivative)]
 #[derivative(PartialEq)]
@@ -68,6 +71,8 @@ pub struct SimOptions {
     pub enable_pandemic_model: Option<XorShiftRng>,
     pub alerts: AlertHandler,
     pub pathfinding_upfront: bool,
+    // Space left between a parked car's body and each end of its spot
+    pub parked_car_buffer: Distance,
 }
 
 #[derive(Clone)]
@@ -97,6 +102,7 @@ impl SimOptions {
             enable_pandemic_model: None,
             alerts: AlertHandler::Print,
             pathfinding_upfront: false,
+            parked_car_buffer: Distance::meters(0.5),
         }
     }
 }
@@ -107,7 +113,7 @@ impl Sim {
         let mut scheduler = Scheduler::new();
         Sim {
             driving: DrivingSimState::new(map, opts.recalc_lanechanging),
-            parking: ParkingSimState::new(map, timer),
+            parking: ParkingSimState::new(map, opts.parked_car_buffer, timer),
             walking: WalkingSimState::new(),
             intersections: IntersectionSimState::new(
                 map,
@@ -162,11 +168,208 @@ impl Sim {
         self.parking.get_free_lot_spots(pl)
     }
 
+    // (total, available) parking spots, without looking at every spot
+    pub fn count_parking_spots(&self) -> (usize, usize) {
+        (self.parking.total_spots(), self.parking.available_spots())
+    }
+
+    pub fn get_blackholed_parking_spots(&self) -> &Vec<ParkingSpot> {
+        self.parking.get_blackholed_spots()
+    }
+
+    pub fn count_free_onstreet_spots(&self, l: LaneID) -> usize {
+        self.parking.count_free_onstreet_spots(l)
+    }
+
+    // Every usable spot along a parking lane, free or not. Unlike Lane::number_parking_spots, this
+    // leaves out spots overlapping no-parking stretches.
+    pub fn count_onstreet_spots(&self, l: LaneID) -> usize {
+        self.parking.all_onstreet_spots(l).len()
+    }
+
+    pub fn count_free_offstreet_spots(&self, b: BuildingID) -> usize {
+        self.parking.count_free_offstreet_spots(b)
+    }
+
+    pub fn count_free_lot_spots(&self, pl: ParkingLotID) -> usize {
+        self.parking.count_free_lot_spots(pl)
+    }
+
     // (Filled, available)
     pub fn get_all_parking_spots(&self) -> (Vec<ParkingSpot>, Vec<ParkingSpot>) {
         self.parking.get_all_parking_spots()
     }
 
+    // Like get_all_parking_spots, but in a fully deterministic order
+    pub fn get_all_parking_spots_sorted(&self) -> (Vec<ParkingSpot>, Vec<ParkingSpot>) {
+        self.parking.get_all_parking_spots_sorted()
+    }
+
+    pub fn parking_utilization(&self) -> ParkingStats {
+        self.parking.utilization()
+    }
+
+    // Frees parking spots reserved more than max_age ago. Long simulations should call this
+    // periodically, so spots reserved by trips that never arrive don't stay blocked forever. Cars
+    // still on their way to one of these spots go looking for another.
+    pub fn expire_parking_reservations(&mut self, max_age: Duration) -> Vec<ParkingSpot> {
+        let mut freed = Vec::new();
+        for (spot, car) in self.parking.expire_reservations(self.time, max_age) {
+            if let Some(car) = car {
+                self.trips
+                    .parking_reservation_expired(car, spot, &mut self.driving);
+            }
+            freed.push(spot);
+        }
+        freed
+    }
+
+    // Free spots reachable from driving_pos that're at most max_walk from the target building
+    pub fn acceptable_parking_spots(
+        &self,
+        driving_pos: Position,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+        max_walk: Distance,
+    ) -> Vec<(ParkingSpot, Position, Option<Money>)> {
+        self.parking
+            .acceptable_spots(driving_pos, vehicle, target, map, max_walk)
+    }
+
+    // Takes a spot out of service, like for construction. A car already parked there can still
+    // leave.
+    pub fn block_parking_spot(&mut self, spot: ParkingSpot) {
+        self.parking.block_spot(spot);
+    }
+
+    pub fn unblock_parking_spot(&mut self, spot: ParkingSpot) {
+        self.parking.unblock_spot(spot);
+    }
+
+    // Occupied spots along a parking lane that'll probably open up within the given time
+    pub fn parking_spots_likely_free_soon(&self, l: LaneID, within: Duration) -> Vec<ParkingSpot> {
+        self.parking.likely_free_soon(l, within, self.time)
+    }
+
+    // Cars parked in this building's garage from now on have to leave in the reverse order they
+    // arrived, like valet parking. The garage must be empty.
+    pub fn make_parking_garage_stacked(&mut self, b: BuildingID) {
+        self.parking.make_stacked(b);
+    }
+
+    // Free parking spots over all spots, without looking at every spot
+    pub fn free_parking_fraction(&self) -> f64 {
+        self.parking.free_fraction()
+    }
+
+    // How many cars are blocking this driving lane to park or unpark right now
+    pub fn active_parking_maneuvers(&self, driving_lane: LaneID) -> usize {
+        self.parking.active_maneuvers(driving_lane)
+    }
+
+    // Reserves a free spot within max_walk of the target for a trip that hasn't started yet,
+    // spreading out reservations for the same area.
+    pub fn reserve_parking_near(
+        &mut self,
+        target: BuildingID,
+        vehicle: &Vehicle,
+        map: &Map,
+        max_walk: Distance,
+    ) -> Option<(ParkingSpot, Position)> {
+        self.parking
+            .reserve_near(target, vehicle, map, max_walk, self.time)
+    }
+
+    // Only cars owned by this person will be able to use the spot, like a residential permit.
+    pub fn assign_parking_spot(&mut self, spot: ParkingSpot, person: PersonID) {
+        self.parking.assign_spot(spot, person);
+    }
+
+    pub fn unassign_parking_spot(&mut self, spot: ParkingSpot) {
+        self.parking.unassign_spot(spot);
+    }
+
+    // The spot a car has reserved, but hasn't parked in yet
+    pub fn target_parking_spot(&self, car: CarID) -> Option<ParkingSpot> {
+        self.parking.target_spot(car)
+    }
+
+    // Where all of someone's parked cars are
+    pub fn parking_spots_owned_by(&self, person: PersonID) -> Vec<(CarID, ParkingSpot)> {
+        self.parking.spots_owned_by(person)
+    }
+
+    // Overrides the hourly price of a spot from the map. None makes it free.
+    pub fn set_parking_cost_per_hour(&mut self, spot: ParkingSpot, cost: Option<Money>) {
+        self.parking.set_cost_per_hour(spot, cost);
+    }
+
+    // The free spot closest to pt as the crow flies, for estimates before a trip starts
+    pub fn nearest_free_parking_spot_to(
+        &self,
+        pt: Pt2D,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        map: &Map,
+    ) -> Option<(ParkingSpot, Distance)> {
+        self.parking.nearest_free_spot_to(pt, vehicle, target, map)
+    }
+
+    // Replaces which spots in a building's garage belong to residents, keyed by spot index
+    pub fn assign_offstreet_parking_spots(
+        &mut self,
+        b: BuildingID,
+        owners: BTreeMap<usize, PersonID>,
+    ) {
+        self.parking.assign_offstreet_spots(b, owners);
+    }
+
+    // Restricts which vehicles can park in a lane, replacing any limit from the map
+    pub fn set_onstreet_parking_classes(&mut self, l: LaneID, classes: VehicleClasses) {
+        self.parking.set_onstreet_classes(l, classes);
+    }
+
+    pub fn set_offstreet_parking_classes(&mut self, b: BuildingID, classes: VehicleClasses) {
+        self.parking.set_offstreet_classes(b, classes);
+    }
+
+    pub fn set_parking_lot_classes(&mut self, pl: ParkingLotID, classes: VehicleClasses) {
+        self.parking.set_lot_classes(pl, classes);
+    }
+
+    // Closes a parking lot, dropping its reservations. Returns the cars that were parked there;
+    // it's up to the caller to put them somewhere else.
+    pub fn evacuate_parking_lot(&mut self, pl: ParkingLotID) -> Vec<ParkedCar> {
+        self.parking.evacuate_lot(pl, self.time)
+    }
+
+    // Where every car is parked, to save or compare against another run
+    pub fn parking_snapshot(&self) -> ParkingSnapshot {
+        self.parking.snapshot()
+    }
+
+    // Moves parked cars back to where a snapshot had them. Reservations are dropped, so only do
+    // this when no car is searching for parking.
+    pub fn restore_parking_snapshot(&mut self, snapshot: ParkingSnapshot) {
+        self.parking.restore_snapshot(snapshot, self.time);
+    }
+
+    // How far somebody parking here walks to the building
+    pub fn parking_spot_walking_dist_to(
+        &self,
+        spot: ParkingSpot,
+        b: BuildingID,
+        map: &Map,
+    ) -> Distance {
+        self.parking.walking_dist_to(spot, b, map)
+    }
+
+    // Which car most recently left this spot, and when
+    pub fn parking_spot_last_vacated(&self, spot: ParkingSpot) -> Option<(CarID, Time)> {
+        self.parking.last_vacated(spot)
+    }
+
     // Also returns the start distance of the building. TODO Do that in the Path properly.
     pub fn walking_path_to_nearest_parking_spot(
         &self,
@@ -179,27 +382,31 @@ impl Sim {
             vehicle_type: VehicleType::Car,
             length: MIN_CAR_LENGTH,
             max_speed: None,
+            accessible: false,
+            parking_budget: None,
         };
         let driving_lane = map.find_driving_lane_near_building(b);
 
         // Anything on the current lane? TODO Should find the closest one to the sidewalk, but
         // need a new method in ParkingSimState to make that easy.
         // TODO Refactor the logic in router
-        let spot = if let Some((spot, _)) = self
+        let spot = if let Some((spot, _, _)) = self
             .parking
             .get_all_free_spots(
                 Position::new(driving_lane, Distance::ZERO),
                 &vehicle,
                 b,
                 map,
+                false,
             )
             .get(0)
         {
             spot.clone()
         } else {
-            let (_, spot, _) =
-                self.parking
-                    .path_to_free_parking_spot(driving_lane, &vehicle, b, map)?;
+            let (_, spot, _) = self
+                .parking
+                .path_to_free_parking_spot(driving_lane, &vehicle, b, map, MAX_PARKING_SEARCH_DIST)
+                .ok()?;
             spot
         };
 
@@ -227,8 +434,9 @@ impl Sim {
         self.trips.random_person(ped_speed, vehicle_specs)
     }
     pub(crate) fn seed_parked_car(&mut self, vehicle: Vehicle, spot: ParkingSpot) {
-        self.parking.reserve_spot(spot);
-        self.parking.add_parked_car(ParkedCar { vehicle, spot });
+        self.parking.reserve_spot(spot, vehicle.id, self.time);
+        self.parking
+            .add_parked_car(ParkedCar { vehicle, spot }, self.time);
     }
 
     pub fn seed_bus_route(&mut self, route: &BusRoute, map: &Map, timer: &mut Timer) -> Vec<CarID> {
@@ -245,6 +453,8 @@ impl Sim {
                 vehicle_type: VehicleType::Bus,
                 length: BUS_LENGTH,
                 max_speed: None,
+                accessible: false,
+                parking_budget: None,
             }
             .make(CarID(self.trips.new_car_id(), VehicleType::Bus), None);
             let id = vehicle.id;
@@ -280,10 +490,11 @@ impl Sim {
                         router: Router::follow_bus_route(path.clone(), end_dist),
                         maybe_parked_car: None,
                         trip_and_person: None,
+                        blocked_since: None,
                     },
                     map,
                     &self.intersections,
-                    &self.parking,
+                    &mut self.parking,
                     &mut self.scheduler,
                 ) {
                     self.transit.bus_created(id, route.id, next_stop_idx);
@@ -335,6 +546,7 @@ impl GetDrawAgents for Sim {
                 return self.parking.get_draw_cars(l, map);
             }
             results.extend(self.parking.get_draw_cars_in_lots(l, map));
+            results.extend(self.parking.get_draw_cars_in_garages(l, map));
         }
         results.extend(
             self.driving
@@ -435,13 +647,46 @@ impl Sim {
                     map,
                 );
             }
-            Command::SpawnCar(create_car, retry_if_no_room) => {
-                if self.driving.start_car_on_lane(
+            Command::SpawnCar(mut create_car, retry_if_no_room) => {
+                // A car in a stacked garage has to wait for the cars parked after it to leave.
+                let blocker = create_car
+                    .maybe_parked_car
+                    .as_ref()
+                    .and_then(|p| self.parking.blocking_car(p));
+                if let Some(blocker) = blocker {
+                    let since = *create_car.blocked_since.get_or_insert(self.time);
+                    if self.time - since < MAX_TIME_BLOCKED_IN_GARAGE {
+                        self.scheduler.push(
+                            self.time + BLIND_RETRY_TO_SPAWN,
+                            Command::SpawnCar(create_car, retry_if_no_room),
+                        );
+                    } else {
+                        // Only trips start from parked cars, so this must exist.
+                        let (trip, person) = create_car.trip_and_person.unwrap();
+                        events.push(Event::Alert(
+                            AlertLocation::Person(person),
+                            format!(
+                                "Aborting {} because {} has been stuck behind {} in a stacked \
+                                 garage since {}",
+                                trip, create_car.vehicle.id, blocker, since
+                            ),
+                        ));
+                        // The car stays parked
+                        self.trips.abort_trip(
+                            self.time,
+                            trip,
+                            None,
+                            &mut self.parking,
+                            &mut self.scheduler,
+                            map,
+                        );
+                    }
+                } else if self.driving.start_car_on_lane(
                     self.time,
                     create_car.clone(),
                     map,
                     &self.intersections,
-                    &self.parking,
+                    &mut self.parking,
                     &mut self.scheduler,
                 ) {
                     if let Some((trip, _)) = create_car.trip_and_person {
@@ -456,14 +701,16 @@ impl Sim {
                                 b,
                             ));
                         }
-                        self.parking.remove_parked_car(parked_car);
+                        self.parking
+                            .remove_parked_car(parked_car, self.time)
+                            .unwrap();
                     }
                     if let Some((trip, person)) = create_car.trip_and_person {
                         events.push(Event::TripPhaseStarting(
                             trip,
                             person,
                             Some(create_car.req.clone()),
-                            if create_car.vehicle.id.1 == VehicleType::Car {
+                            if create_car.vehicle.id.1 != VehicleType::Bike {
                                 TripPhaseType::Driving
                             } else {
                                 TripPhaseType::Biking
@@ -608,6 +855,7 @@ impl Sim {
         events.extend(self.driving.collect_events());
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
+        self.parking.report_overstays(self.time);
         events.extend(self.parking.collect_events());
         for ev in events {
             if let Some(ref mut m) = self.pandemic {
@@ -1004,17 +1252,24 @@ impl Sim {
     }
 
     pub fn lookup_car_id(&self, idx: usize) -> Option<CarID> {
-        for vt in &[VehicleType::Car, VehicleType::Bike, VehicleType::Bus] {
+        for vt in &[
+            VehicleType::Car,
+            VehicleType::Bike,
+            VehicleType::Bus,
+            VehicleType::Motorcycle,
+        ] {
             let id = CarID(idx, *vt);
             if self.driving.does_car_exist(id) {
                 return Some(id);
             }
         }
 
-        let id = CarID(idx, VehicleType::Car);
-        // Only cars can be parked.
-        if self.parking.lookup_parked_car(id).is_some() {
-            return Some(id);
+        // Only cars and motorcycles can be parked.
+        for vt in &[VehicleType::Car, VehicleType::Motorcycle] {
+            let id = CarID(idx, *vt);
+            if self.parking.lookup_parked_car(id).is_some() {
+                return Some(id);
+            }
         }
 
         None
@@ -1153,6 +1408,7 @@ impl Sim {
                 id,
                 self.time,
                 map,
+                &mut self.parking,
                 &mut self.scheduler,
                 &mut self.intersections,
             );
@@ -1,8 +1,9 @@
 use crate::{
-    AgentID, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal, Event,
-    OffMapLocation, OrigPersonID, ParkedCar, ParkingSimState, ParkingSpot, PedestrianID, PersonID,
-    Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID, TripPhaseType, TripSpec,
-    Vehicle, VehicleSpec, VehicleType, WalkingSimState,
+    AgentID, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
+    DrivingSimState, Event, OffMapLocation, OrigPersonID, ParkedCar, ParkingSimState, ParkingSpot,
+    PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TransitSimState, TripID,
+    TripPhaseType, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
+    MAX_PARKING_SEARCH_DIST,
 };
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Distance, Duration, Speed, Time};
@@ -165,6 +166,26 @@ impl TripManager {
         self.active_trip_mode.insert(agent, t);
     }
 
+    // A car's parking reservation expired before it started pulling in. If it's still driving
+    // somewhere, it has to find another spot.
+    pub fn parking_reservation_expired(
+        &mut self,
+        car: CarID,
+        spot: ParkingSpot,
+        driving: &mut DrivingSimState,
+    ) {
+        if let Some(t) = self.active_trip_mode.get(&AgentID::Car(car)) {
+            driving.forget_parking_spot(car, spot);
+            self.events.push(Event::Alert(
+                AlertLocation::Person(self.trips[t.0].person),
+                format!(
+                    "{}'s reservation for {:?} expired, so it's looking for parking again",
+                    car, spot
+                ),
+            ));
+        }
+    }
+
     pub fn car_reached_parking_spot(
         &mut self,
         now: Time,
@@ -279,10 +300,22 @@ impl TripManager {
                     trip.id, start, end
                 ),
             ));
-            // Move the car to the destination...
-            parking.remove_parked_car(parked_car.clone());
+            // Move the car to the destination... unless it's stuck in a stacked garage
+            let vehicle = match parking.remove_parked_car(parked_car.clone(), now) {
+                Ok(()) => Some(parked_car.vehicle),
+                Err(blocker) => {
+                    self.events.push(Event::Alert(
+                        AlertLocation::Person(trip.person),
+                        format!(
+                            "{} is stuck behind {} in a stacked garage, so it stays parked",
+                            parked_car.vehicle.id, blocker
+                        ),
+                    ));
+                    None
+                }
+            };
             let trip = trip.id;
-            self.abort_trip(now, trip, Some(parked_car.vehicle), parking, scheduler, map);
+            self.abort_trip(now, trip, vehicle, parking, scheduler, map);
             return;
         };
 
@@ -704,7 +737,9 @@ impl TripManager {
         };
         // Don't forget the car!
         if let Some(vehicle) = abandoned_vehicle {
-            if vehicle.vehicle_type == VehicleType::Car {
+            if vehicle.vehicle_type == VehicleType::Car
+                || vehicle.vehicle_type == VehicleType::Motorcycle
+            {
                 if let TripEndpoint::Bldg(b) = trip.end {
                     let driving_lane = map.find_driving_lane_near_building(b);
                     if let Some(spot) = parking
@@ -713,13 +748,21 @@ impl TripManager {
                             &vehicle,
                             b,
                             map,
+                            false,
                         )
                         // TODO Could pick something closer, but meh, aborted trips are bugs anyway
                         .get(0)
-                        .map(|(spot, _)| spot.clone())
+                        .map(|(spot, _, _)| spot.clone())
                         .or_else(|| {
                             parking
-                                .path_to_free_parking_spot(driving_lane, &vehicle, b, map)
+                                .path_to_free_parking_spot(
+                                    driving_lane,
+                                    &vehicle,
+                                    b,
+                                    map,
+                                    MAX_PARKING_SEARCH_DIST,
+                                )
+                                .ok()
                                 .map(|(_, spot, _)| spot)
                         })
                     {
@@ -730,8 +773,8 @@ impl TripManager {
                                 person, spot
                             ),
                         ));
-                        parking.reserve_spot(spot);
-                        parking.add_parked_car(ParkedCar { vehicle, spot });
+                        parking.reserve_spot(spot, vehicle.id, now);
+                        parking.add_parked_car(ParkedCar { vehicle, spot }, now);
                     } else {
                         self.events.push(Event::Alert(
                             AlertLocation::Person(person),
@@ -1036,16 +1079,23 @@ impl TripManager {
                             AlertLocation::Person(person.id),
                             format!("UsingParkedCar trip couldn't find the walking path {}", req),
                         ));
-                        // Move the car to the destination
-                        parking.remove_parked_car(parked_car.clone());
-                        self.abort_trip(
-                            now,
-                            trip,
-                            Some(parked_car.vehicle),
-                            parking,
-                            scheduler,
-                            map,
-                        );
+                        // Move the car to the destination, unless it's stuck in a stacked
+                        // garage
+                        let vehicle = match parking.remove_parked_car(parked_car.clone(), now) {
+                            Ok(()) => Some(parked_car.vehicle),
+                            Err(blocker) => {
+                                self.events.push(Event::Alert(
+                                    AlertLocation::Person(person.id),
+                                    format!(
+                                        "{} is stuck behind {} in a stacked garage, so it stays \
+                                         parked",
+                                        parked_car.vehicle.id, blocker
+                                    ),
+                                ));
+                                None
+                            }
+                        };
+                        self.abort_trip(now, trip, vehicle, parking, scheduler, map);
                     }
                 } else {
                     // This should only happen when a driving trip has been aborted and there was
@@ -1350,7 +1400,7 @@ impl TripMode {
         match id {
             AgentID::Pedestrian(_) => TripMode::Walk,
             AgentID::Car(id) => match id.1 {
-                VehicleType::Car => TripMode::Drive,
+                VehicleType::Car | VehicleType::Motorcycle => TripMode::Drive,
                 VehicleType::Bike => TripMode::Bike,
                 // TODO Little confusing; this means buses, not bus riders.
                 VehicleType::Bus => TripMode::Transit,